@@ -1,4 +1,5 @@
 use alloc::{borrow::ToOwned, boxed::Box, format, string::ToString, vec::Vec};
+use core::time::Duration;
 use frame_support::{
 	pallet_prelude::{StorageValue, ValueQuery},
 	traits::StorageInstance,
@@ -276,6 +277,20 @@ impl AnyClientState {
 			c => c,
 		}
 	}
+
+	/// Returns the time after the latest consensus state the client expires at, or `None` if the
+	/// client doesn't expire.
+	pub fn trusting_period(&self) -> Option<Duration> {
+		match self {
+			AnyClientState::Grandpa(client_state) =>
+				Some(client_state.relay_chain.trusting_period()),
+			AnyClientState::Beefy(client_state) => Some(client_state.relay_chain.trusting_period()),
+			AnyClientState::Tendermint(client_state) => Some(client_state.trusting_period),
+			AnyClientState::Wasm(wasm_state) => wasm_state.inner.trusting_period(),
+			#[cfg(test)]
+			AnyClientState::Mock(_) => None,
+		}
+	}
 }
 
 impl AnyClientState {
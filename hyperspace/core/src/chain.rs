@@ -37,6 +37,7 @@ use ibc::{
 		ics03_connection::msgs::{
 			conn_open_ack::MsgConnectionOpenAck, conn_open_try::MsgConnectionOpenTry,
 		},
+		ics04_channel::packet::Packet,
 		ics23_commitment::commitment::CommitmentPrefix,
 		ics24_host::identifier::{ChannelId, ClientId, ConnectionId, PortId},
 	},
@@ -65,11 +66,11 @@ use pallet_ibc::light_clients::{AnyClientMessage, AnyClientState, AnyConsensusSt
 use pallet_ibc::Timeout;
 use parachain::{ParachainClient, ParachainClientConfig};
 use primitives::{
-	mock::LocalClientTypes, Chain, CommonClientState, IbcProvider, KeyProvider, LightClientSync,
-	MisbehaviourHandler, UpdateType,
+	key_path::KeyPathType, mock::LocalClientTypes, Chain, CommonClientState, FeeEstimate,
+	IbcProvider, KeyProvider, LightClientSync, MisbehaviourHandler, UpdateType,
 };
 use serde::{Deserialize, Serialize};
-use std::{pin::Pin, time::Duration};
+use std::{net::SocketAddr, pin::Pin, time::Duration};
 use tendermint_proto::Protobuf;
 use thiserror::Error;
 
@@ -83,6 +84,53 @@ pub struct Config {
 #[derive(Serialize, Deserialize)]
 pub struct CoreConfig {
 	pub prometheus_endpoint: Option<String>,
+	/// Path to the file the last processed heights are persisted to
+	#[serde(default)]
+	pub state_path: Option<String>,
+	/// Address of the HTTP health endpoint
+	#[serde(default)]
+	pub health_endpoint: Option<String>,
+	/// Seconds within which both chains must have responded for the relayer to be ready
+	#[serde(default)]
+	pub readiness_timeout: Option<u64>,
+	/// Packet relaying is paused while this file exists. It's created when a misbehaviour is
+	/// detected, the operator removes it to resume relaying.
+	#[serde(default)]
+	pub pause_flag_path: Option<String>,
+	/// Seconds to wait on SIGINT/SIGTERM for the relaying rounds in progress to finish
+	#[serde(default)]
+	pub shutdown_timeout: Option<u64>,
+	/// Seconds between the expiry checks of the clients
+	#[serde(default)]
+	pub client_expiry_check_interval: Option<u64>,
+	/// Clients are updated once less than this fraction of their trusting period is left
+	#[serde(default)]
+	pub client_expiry_threshold: Option<f64>,
+}
+
+impl CoreConfig {
+	/// Returns an error if a configured value is out of its valid range.
+	pub fn validate(&self) -> Result<(), anyhow::Error> {
+		if let Some(threshold) = self.client_expiry_threshold {
+			if !(threshold > 0.0 && threshold < 1.0) {
+				anyhow::bail!(
+					"client_expiry_threshold must be between 0 and 1 exclusive, found {threshold}"
+				)
+			}
+		}
+		if self.client_expiry_check_interval == Some(0) {
+			anyhow::bail!("client_expiry_check_interval must be positive")
+		}
+		if let Some(endpoint) = &self.health_endpoint {
+			if let Err(e) = endpoint.parse::<SocketAddr>() {
+				anyhow::bail!("health_endpoint must be a socket address, found {endpoint}: {e}")
+			}
+		}
+		if self.readiness_timeout == Some(0) {
+			anyhow::bail!("readiness_timeout must be positive")
+		}
+		Ok(())
+	}
 }
 
 impl From<String> for AnyError {
@@ -14,18 +14,42 @@
 
 use crate::{
 	chain::{AnyConfig, Config, CoreConfig},
-	fish, relay, Mode,
+	expiry, fish,
+	health::{self, HealthReporter},
+	packets::{self, PacketScope},
+	queue::flush_message_batch,
+	relay,
+	state::RelayerState,
+	Mode, RelayOptions,
 };
 use anyhow::{anyhow, Result};
 use clap::Parser;
-use ibc::core::{ics04_channel::channel::Order, ics24_host::identifier::PortId};
+use futures::StreamExt;
+use ibc::core::{
+	ics04_channel::channel::{ChannelEnd, Order},
+	ics24_host::identifier::{ChannelId, PortId},
+};
+use ibc_proto::google::protobuf::Any;
 use metrics::{data::Metrics, handler::MetricsHandler, init_prometheus};
 use primitives::{
+	dry_run::DryRun,
+	in_flight::InFlightKey,
+	pause::PauseFlag,
+	shutdown::Shutdown,
 	utils::{create_channel, create_clients, create_connection},
 	Chain, IbcProvider,
 };
 use prometheus::Registry;
-use std::{num::NonZeroU64, path::PathBuf, str::FromStr, time::Duration};
+use std::{future::Future, num::NonZeroU64, path::PathBuf, str::FromStr, time::Duration};
+
+/// Default time to wait on shutdown for the relaying rounds in progress to finish.
+const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Largest wasm blob accepted by `upload-wasm`, after compression.
+const MAX_WASM_SIZE: usize = 3 * 1024 * 1024;
+
+/// Magic bytes at the start of a gzip stream.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
 
 #[derive(Debug, Parser)]
 pub struct Cli {
@@ -51,6 +75,11 @@ pub enum Subcommand {
 	CreateConnection(Cmd),
 	#[clap(name = "create-channel", about = "Creates a channel on the specified port")]
 	CreateChannel(Cmd),
+	#[clap(
+		name = "clear-packets",
+		about = "Relays the pending packets, acknowledgements and timeouts of a channel and exits"
+	)]
+	ClearPackets(ClearPacketsCmd),
 }
 
 #[derive(Debug, Clone, Parser)]
@@ -82,39 +111,243 @@ pub struct Cmd {
 	/// New config path for B to avoid overriding existing configuration
 	#[clap(long)]
 	pub out_config_b: Option<String>,
+	/// Build the messages without submitting them, they're printed and written to
+	/// `dry_run_dir` instead
+	#[clap(long)]
+	dry_run: bool,
+	/// Directory the messages are written to in dry-run mode
+	#[clap(long, default_value = "hyperspace-dry-run")]
+	dry_run_dir: PathBuf,
 }
 
 #[derive(Debug, Clone, Parser)]
 pub struct UploadWasmCmd {
-	/// Relayer chain config path.
+	/// Relayer chain config path, or the relayer config path if `--chain` is given.
 	#[clap(long)]
 	config: String,
+	/// Chain of the relayer config to upload the wasm to.
+	#[clap(long, value_enum)]
+	chain: Option<ChainSide>,
 	/// New config path to avoid overriding existing configuration.
 	#[clap(long)]
 	pub out_config: Option<String>,
 	/// Path to the wasm file.
-	#[clap(long)]
+	#[clap(long, alias = "wasm")]
 	wasm_path: PathBuf,
+	/// Write the code id back into the config file.
+	#[clap(long)]
+	pub save: bool,
+}
+
+/// One of the chains of a relayer config.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ChainSide {
+	A,
+	B,
+}
+
+impl ChainSide {
+	fn config(self, config: &mut Config) -> &mut AnyConfig {
+		match self {
+			ChainSide::A => &mut config.chain_a,
+			ChainSide::B => &mut config.chain_b,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct ClearPacketsCmd {
+	#[clap(flatten)]
+	pub cmd: Cmd,
+	/// Channel id on chain A, the packets are cleared in both directions
+	#[clap(long)]
+	channel: String,
+	/// Port id of the channel on chain A
+	#[clap(long)]
+	port: String,
+	/// Only clear the packets sent from this chain
+	#[clap(long, value_enum)]
+	from: Option<ChainSide>,
+	/// Only clear the packets with these sequences. Each direction has its own sequences, so the
+	/// chain that sent the packets must be given with `--from`
+	#[clap(long, value_delimiter = ',', requires = "from")]
+	sequences: Vec<u64>,
+}
+
+impl ClearPacketsCmd {
+	pub async fn run(&self) -> Result<()> {
+		let port_id =
+			PortId::from_str(&self.port).map_err(|e| anyhow!("Port id was invalid: {e:?}"))?;
+		let channel_id = ChannelId::from_str(&self.channel)
+			.map_err(|e| anyhow!("Channel id was invalid: {e:?}"))?;
+		let config = self.cmd.parse_config().await?;
+		let mut chain_a = config.chain_a.into_client().await?;
+		let mut chain_b = config.chain_b.into_client().await?;
+		self.cmd.enable_dry_run(&mut chain_a, &mut chain_b);
+		chain_a.health_check(&chain_b).await?;
+		chain_b.health_check(&chain_a).await?;
+
+		let (height_a, ..) = chain_a.latest_height_and_timestamp().await?;
+		let channel_end = chain_a
+			.query_channel_end(height_a, channel_id, port_id.clone())
+			.await?
+			.channel
+			.ok_or_else(|| anyhow!("Channel {channel_id}/{port_id} not found"))?;
+		let channel_end = ChannelEnd::try_from(channel_end)?;
+		let counterparty = channel_end.counterparty();
+		let counterparty_channel_id = counterparty
+			.channel_id
+			.ok_or_else(|| anyhow!("Channel {channel_id}/{port_id} has no counterparty"))?;
+		chain_a.set_channel_whitelist([(channel_id, port_id)].into_iter().collect());
+		chain_b.set_channel_whitelist(
+			[(counterparty_channel_id, counterparty.port_id.clone())].into_iter().collect(),
+		);
+
+		// the proofs are queried at the heights the counterparty clients know about
+		update_client(&mut chain_a, &chain_b).await?;
+		update_client(&mut chain_b, &chain_a).await?;
+
+		let mut failed = 0;
+		if self.clears_packets_from(ChainSide::A) {
+			failed += clear_packets(&chain_a, &chain_b, &self.sequences).await?;
+		}
+		if self.clears_packets_from(ChainSide::B) {
+			failed += clear_packets(&chain_b, &chain_a, &self.sequences).await?;
+		}
+		if failed > 0 {
+			return Err(anyhow!("Failed to submit {failed} batches of packet messages"))
+		}
+		Ok(())
+	}
+
+	/// Returns `true` if the packets sent from the `side` chain are cleared.
+	fn clears_packets_from(&self, side: ChainSide) -> bool {
+		self.from.map_or(true, |from| from == side)
+	}
+}
+
+/// Submits an update of the `source` client on `sink` to the latest finalized `source` height.
+async fn update_client(source: &mut impl Chain, sink: &impl Chain) -> Result<()> {
+	let finality_event = source
+		.finality_notifications()
+		.await?
+		.next()
+		.await
+		.ok_or_else(|| anyhow!("Finality notifications of {} ended", source.name()))?;
+	let updates = source.query_latest_ibc_events(finality_event, sink).await?;
+	if let Some((msg_update_client, height, ..)) = updates.into_iter().last() {
+		println!("Updating the client of {} on {} to {height}", source.name(), sink.name());
+		flush_message_batch(vec![msg_update_client], None, sink).await?;
+	}
+	Ok(())
+}
+
+/// Relays the pending packets and acknowledgements from `source` to `sink`, and the timeouts of
+/// the `source` packets back to `source`. Unlike a relaying round, all of them are relayed at once
+/// instead of at most `max_packets_to_process` per channel. Returns the number of batches that
+/// failed.
+async fn clear_packets(source: &impl Chain, sink: &impl Chain, sequences: &[u64]) -> Result<usize> {
+	let (messages, timeout_messages) =
+		packets::query_ready_and_timed_out_packets(source, sink, None, PacketScope::All(sequences))
+			.await?;
+	print_messages(&messages, sink.name());
+	print_messages(&timeout_messages, source.name());
+	let failed = !submit_messages(sink, messages).await as usize +
+		!submit_messages(source, timeout_messages).await as usize;
+	Ok(failed)
+}
+
+/// Returns `false` if the submission failed.
+async fn submit_messages(chain: &impl Chain, messages: Vec<Any>) -> bool {
+	if messages.is_empty() {
+		return true
+	}
+	match flush_message_batch(messages, None, chain).await {
+		Ok(()) => {
+			println!("Submitted the messages to {}", chain.name());
+			true
+		},
+		Err(e) => {
+			println!("Failed to submit the messages to {}: {e:?}", chain.name());
+			false
+		},
+	}
+}
+
+/// Prints the packet of each message.
+fn print_messages(messages: &[Any], chain: &str) {
+	for key in messages.iter().filter_map(InFlightKey::from_message) {
+		println!(
+			"{:?} of packet {}/{}/{} to {chain}",
+			key.kind, key.port_id, key.channel_id, key.sequence
+		);
+	}
+}
+
+/// Resolves on SIGINT or SIGTERM.
+async fn shutdown_signal() -> Result<()> {
+	let mut terminate = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+	tokio::select! {
+		result = tokio::signal::ctrl_c() => result?,
+		_ = terminate.recv() => {},
+	}
+	Ok(())
+}
+
+/// Runs the relayer until it stops after a shutdown was requested. If it takes longer than
+/// `timeout` since the request, it's dropped with the submissions still in progress.
+async fn run_until_shutdown(
+	relayer: impl Future<Output = Result<()>>,
+	shutdown: &Shutdown,
+	timeout: Duration,
+) -> Result<()> {
+	let deadline = async {
+		shutdown.requested().await;
+		tokio::time::sleep(timeout).await;
+	};
+	tokio::select! {
+		result = relayer => result,
+		_ = deadline => {
+			log::warn!(target: "hyperspace", "The relaying rounds in progress didn't finish within {timeout:?}, exiting anyway");
+			Ok(())
+		},
+	}
 }
 
 impl UploadWasmCmd {
 	pub async fn run(&self) -> Result<AnyConfig> {
-		use tokio::fs::read_to_string;
-		let path: PathBuf = self.config.parse()?;
-		let file_content = read_to_string(path).await?;
-		let mut config: AnyConfig = toml::from_str(&file_content)?;
+		let mut config = match self.chain {
+			Some(chain) => chain.config(&mut self.parse_relayer_config().await?).clone(),
+			None => toml::from_str(&tokio::fs::read_to_string(&self.config).await?)?,
+		};
 		let client = config.clone().into_client().await?;
-		let wasm = tokio::fs::read(&self.wasm_path).await?;
-		let code_id = client.upload_wasm(wasm).await?;
-		let code_id_str = hex::encode(code_id);
+		let wasm = tokio::fs::read(&self.wasm_path)
+			.await
+			.map_err(|e| anyhow!("Failed to read {}: {e}", self.wasm_path.display()))?;
+		let code_id_str = upload_wasm(&client, wasm).await?;
 		println!("{code_id_str}");
 		config.set_wasm_code_id(code_id_str);
 		Ok(config)
 	}
 
+	/// Writes the chain config with the code id to `--out-config`, or back to `--config`. With
+	/// `--chain`, the rest of the relayer config is kept.
 	pub async fn save_config(&self, new_config: &AnyConfig) -> Result<()> {
 		let path = self.out_config.as_ref().cloned().unwrap_or_else(|| self.config.clone());
-		write_config(path, new_config).await
+		match self.chain {
+			Some(chain) => {
+				let mut config = self.parse_relayer_config().await?;
+				*chain.config(&mut config) = new_config.clone();
+				write_config(path, &config).await
+			},
+			None => write_config(path, new_config).await,
+		}
+	}
+
+	async fn parse_relayer_config(&self) -> Result<Config> {
+		let config: Config = toml::from_str(&tokio::fs::read_to_string(&self.config).await?)?;
+		config.core.validate()?;
+		Ok(config)
 	}
 }
 
@@ -130,6 +363,7 @@ impl Cmd {
 		let config_b: AnyConfig = toml::from_str(&file_content)?;
 		let file_content = read_to_string(path_core).await?;
 		let config_core: CoreConfig = toml::from_str(&file_content)?;
+		config_core.validate()?;
 
 		Ok(Config { chain_a: config_a, chain_b: config_b, core: config_core })
 	}
@@ -138,8 +372,35 @@ impl Cmd {
 	/// Run the command
 	pub async fn run(&self) -> Result<()> {
 		let config = self.parse_config().await?;
-		let chain_a = config.chain_a.into_client().await?;
-		let chain_b = config.chain_b.into_client().await?;
+		let mut chain_a = config.chain_a.into_client().await?;
+		let mut chain_b = config.chain_b.into_client().await?;
+		self.enable_dry_run(&mut chain_a, &mut chain_b);
+		chain_a.health_check(&chain_b).await?;
+		chain_b.health_check(&chain_a).await?;
+		// both chains share the flag, so a misbehaviour of either one pauses both directions
+		let pause_flag = config.core.pause_flag_path.map(PauseFlag::with_file).unwrap_or_default();
+		if pause_flag.is_set() {
+			log::warn!("Packet relaying is paused until {:?} is removed", pause_flag.path());
+		}
+		chain_a.common_state_mut().pause_flag = pause_flag.clone();
+		chain_b.common_state_mut().pause_flag = pause_flag;
+		let shutdown = Shutdown::default();
+		chain_a.common_state_mut().shutdown = shutdown.clone();
+		chain_b.common_state_mut().shutdown = shutdown.clone();
+		tokio::spawn({
+			let shutdown = shutdown.clone();
+			async move {
+				match shutdown_signal().await {
+					Ok(()) => {
+						log::info!(target: "hyperspace", "Shutting down, waiting for the relaying rounds in progress to finish");
+						shutdown.request();
+					},
+					Err(e) => {
+						log::error!(target: "hyperspace", "Failed to listen for the shutdown signals: {e:?}")
+					},
+				}
+			}
+		});
 
 		let registry =
 			Registry::new_custom(None, None).expect("this can only fail if the prefix is empty");
@@ -153,7 +414,78 @@ impl Cmd {
 			tokio::spawn(init_prometheus(addr, registry.clone()));
 		}
 
-		relay(chain_a, chain_b, Some(metrics_handler_a), Some(metrics_handler_b), None).await
+		let interval = config
+			.core
+			.client_expiry_check_interval
+			.map(Duration::from_secs)
+			.unwrap_or(expiry::DEFAULT_CHECK_INTERVAL);
+		let threshold = config.core.client_expiry_threshold.unwrap_or(expiry::DEFAULT_THRESHOLD);
+		tokio::spawn(expiry::monitor_client_expiry(
+			chain_a.clone(),
+			chain_b.clone(),
+			Some(metrics_handler_a.clone()),
+			interval,
+			threshold,
+		));
+		tokio::spawn(expiry::monitor_client_expiry(
+			chain_b.clone(),
+			chain_a.clone(),
+			Some(metrics_handler_b.clone()),
+			interval,
+			threshold,
+		));
+
+		let health = match config.core.health_endpoint {
+			Some(endpoint) => {
+				let addr = endpoint
+					.parse()
+					.map_err(|e| anyhow!("Invalid health endpoint {endpoint}: {e}"))?;
+				let readiness_timeout = config
+					.core
+					.readiness_timeout
+					.map(Duration::from_secs)
+					.unwrap_or(health::DEFAULT_READINESS_TIMEOUT);
+				let reporter =
+					HealthReporter::new(&[chain_a.name(), chain_b.name()], readiness_timeout);
+				let server = health::serve(addr, reporter.subscribe())
+					.map_err(|e| anyhow!("Failed to serve the health endpoint on {addr}: {e}"))?;
+				tokio::spawn(async move {
+					if let Err(e) = server.await {
+						log::error!(target: "hyperspace", "Health endpoint failed: {e}");
+					}
+				});
+				tokio::spawn(health::monitor_chain(chain_a.clone(), reporter.clone()));
+				tokio::spawn(health::monitor_chain(chain_b.clone(), reporter.clone()));
+				Some(reporter)
+			},
+			None => None,
+		};
+
+		// nothing is submitted in dry-run mode, so the processed heights are not persisted
+		let state = config.core.state_path.filter(|_| !self.dry_run).map(RelayerState::load);
+		let options = RelayOptions {
+			chain_a_metrics: Some(metrics_handler_a),
+			chain_b_metrics: Some(metrics_handler_b),
+			mode: None,
+			state,
+			health,
+		};
+		let relayer = relay(chain_a, chain_b, options);
+		let timeout = config
+			.core
+			.shutdown_timeout
+			.map(Duration::from_secs)
+			.unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT);
+		run_until_shutdown(relayer, &shutdown, timeout).await
+	}
+
+	/// Makes both chains record the outgoing messages instead of submitting them, if enabled.
+	fn enable_dry_run(&self, chain_a: &mut impl Chain, chain_b: &mut impl Chain) {
+		if self.dry_run {
+			let dry_run = DryRun::new(&self.dry_run_dir);
+			chain_a.common_state_mut().dry_run = Some(dry_run.clone());
+			chain_b.common_state_mut().dry_run = Some(dry_run);
+		}
 	}
 
 	/// Run fisherman
@@ -202,10 +534,9 @@ impl Cmd {
 
 		let chain_a_clone = chain_a.clone();
 		let chain_b_clone = chain_b.clone();
+		let options = RelayOptions { mode: Some(Mode::Light), ..Default::default() };
 		let handle = tokio::task::spawn(async move {
-			relay(chain_a_clone, chain_b_clone, None, None, Some(Mode::Light))
-				.await
-				.unwrap();
+			relay(chain_a_clone, chain_b_clone, options).await.unwrap();
 		});
 
 		let (connection_id_a, connection_id_b) =
@@ -240,10 +571,9 @@ impl Cmd {
 
 		let chain_a_clone = chain_a.clone();
 		let chain_b_clone = chain_b.clone();
+		let options = RelayOptions { mode: Some(Mode::Light), ..Default::default() };
 		let handle = tokio::task::spawn(async move {
-			relay(chain_a_clone, chain_b_clone, None, None, Some(Mode::Light))
-				.await
-				.unwrap();
+			relay(chain_a_clone, chain_b_clone, options).await.unwrap();
 		});
 
 		let order = Order::from_str(order).expect("Expected one of 'ordered' or 'unordered'");
@@ -275,8 +605,196 @@ impl Cmd {
 	}
 }
 
-async fn write_config(path: String, config: &AnyConfig) -> Result<()> {
+/// Uploads the wasm to the `chain`, compressed if the chain requires it, and returns its code id in
+/// hex.
+async fn upload_wasm(chain: &impl Chain, wasm: Vec<u8>) -> Result<String> {
+	let wasm = prepare_wasm(wasm, chain.requires_gzipped_wasm())?;
+	let code_id = chain
+		.upload_wasm(wasm)
+		.await
+		.map_err(|e| anyhow!("Failed to upload the wasm to {}: {e:?}", chain.name()))?;
+	Ok(hex::encode(code_id))
+}
+
+/// Compresses the wasm if the chain requires it (`gzip`) and it isn't compressed already, and
+/// checks that it fits in [`MAX_WASM_SIZE`].
+fn prepare_wasm(wasm: Vec<u8>, gzip: bool) -> Result<Vec<u8>> {
+	use flate2::{write::GzEncoder, Compression};
+	use std::io::Write;
+
+	let wasm = if gzip && !wasm.starts_with(&GZIP_MAGIC) {
+		let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+		encoder.write_all(&wasm)?;
+		encoder.finish()?
+	} else {
+		wasm
+	};
+	if wasm.len() > MAX_WASM_SIZE {
+		return Err(anyhow!(
+			"Wasm is {} bytes, which exceeds the limit of {MAX_WASM_SIZE} bytes",
+			wasm.len()
+		))
+	}
+	Ok(wasm)
+}
+
+async fn write_config(path: String, config: &impl serde::Serialize) -> Result<()> {
 	tokio::fs::write(path.parse::<PathBuf>()?, toml::to_string(config)?)
 		.await
 		.map_err(|e| anyhow!(e))
 }
+
+#[cfg(test)]
+pub mod tests {
+	use super::{
+		prepare_wasm, run_until_shutdown, upload_wasm, ChainSide, ClearPacketsCmd, UploadWasmCmd,
+		GZIP_MAGIC, MAX_WASM_SIZE,
+	};
+	use crate::chain::CoreConfig;
+	use clap::Parser;
+	use ibc::core::ics24_host::identifier::ClientId;
+	use primitives::{mock::MockChain, shutdown::Shutdown};
+	use std::{
+		sync::{
+			atomic::{AtomicUsize, Ordering},
+			Arc,
+		},
+		time::Duration,
+	};
+
+	/// Relays a round every millisecond until a shutdown is requested, then takes `drain` to
+	/// finish the round in progress.
+	async fn relayer(
+		shutdown: Shutdown,
+		drain: Duration,
+		rounds: Arc<AtomicUsize>,
+	) -> anyhow::Result<()> {
+		while !shutdown.is_requested() {
+			rounds.fetch_add(1, Ordering::SeqCst);
+			tokio::time::sleep(Duration::from_millis(1)).await;
+		}
+		tokio::time::sleep(drain).await;
+		rounds.fetch_add(1, Ordering::SeqCst);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn shutdown_waits_for_the_round_in_progress() {
+		let timeout = Duration::from_millis(100);
+		for (drain, finished) in
+			[(Duration::from_millis(10), true), (Duration::from_secs(10), false)]
+		{
+			let shutdown = Shutdown::default();
+			let rounds = Arc::new(AtomicUsize::new(0));
+			let task = tokio::spawn({
+				let (shutdown, rounds) = (shutdown.clone(), rounds.clone());
+				async move {
+					run_until_shutdown(relayer(shutdown.clone(), drain, rounds), &shutdown, timeout)
+						.await
+				}
+			});
+			tokio::time::sleep(Duration::from_millis(20)).await;
+			shutdown.request();
+			let rounds_before = rounds.load(Ordering::SeqCst);
+			assert!(rounds_before > 0);
+			tokio::time::timeout(timeout * 2, task).await.unwrap().unwrap().unwrap();
+			// the round in progress was completed, unless it took longer than the timeout
+			assert_eq!(rounds.load(Ordering::SeqCst) == rounds_before + 1, finished);
+		}
+	}
+
+	#[test]
+	fn wasm_is_compressed_once_and_size_checked() {
+		let wasm = b"\0asm".repeat(1024);
+		assert_eq!(prepare_wasm(wasm.clone(), false).unwrap(), wasm);
+
+		let compressed = prepare_wasm(wasm.clone(), true).unwrap();
+		assert!(compressed.starts_with(&GZIP_MAGIC));
+		assert!(compressed.len() < wasm.len());
+		// already compressed blobs are uploaded as is
+		assert_eq!(prepare_wasm(compressed.clone(), true).unwrap(), compressed);
+
+		let oversized = vec![0xab; MAX_WASM_SIZE + 1];
+		let err = prepare_wasm(oversized.clone(), false).unwrap_err();
+		assert!(err.to_string().contains("exceeds the limit"), "{err}");
+		assert!(prepare_wasm(oversized, true).is_ok());
+	}
+
+	#[tokio::test]
+	async fn wasm_is_uploaded_as_the_chain_requires() {
+		let cmd = UploadWasmCmd::parse_from([
+			"upload-wasm",
+			"--config",
+			"config.toml",
+			"--chain",
+			"b",
+			"--wasm",
+			"client.wasm",
+		]);
+		assert_eq!(cmd.chain, Some(ChainSide::B));
+		assert!(!cmd.save);
+
+		let wasm = b"\0asm".repeat(1024);
+		let chain = MockChain::new("chain", ClientId::new("07-tendermint", 0).unwrap());
+		assert_eq!(upload_wasm(&chain, wasm.clone()).await.unwrap(), "00");
+		chain.state().requires_gzipped_wasm = true;
+		assert_eq!(upload_wasm(&chain, wasm.clone()).await.unwrap(), "01");
+
+		let codes = chain.state().wasm_codes.clone();
+		assert_eq!(codes[0], wasm);
+		assert_eq!(codes[1], prepare_wasm(wasm, true).unwrap());
+		// an oversized wasm is rejected before it's uploaded
+		assert!(upload_wasm(&chain, vec![0xab; MAX_WASM_SIZE + 1]).await.is_err());
+		assert_eq!(chain.state().wasm_codes.len(), 2);
+	}
+
+	#[test]
+	fn client_expiry_threshold_is_a_fraction() {
+		let config = |threshold: &str| {
+			toml::from_str::<CoreConfig>(&format!(
+				"prometheus_endpoint = \"127.0.0.1:9090\"\nclient_expiry_threshold = {threshold}"
+			))
+			.unwrap()
+		};
+		assert!(config("0.33").validate().is_ok());
+		for threshold in ["0.0", "1.0", "1.5", "-0.1", "nan"] {
+			assert!(config(threshold).validate().is_err(), "{threshold}");
+		}
+	}
+
+	#[test]
+	fn health_endpoint_must_be_an_address() {
+		let config = |health: &str| {
+			toml::from_str::<CoreConfig>(&format!(
+				"prometheus_endpoint = \"127.0.0.1:9090\"\n{health}"
+			))
+			.unwrap()
+		};
+		assert!(config("health_endpoint = \"127.0.0.1:8080\"").validate().is_ok());
+		assert!(config("health_endpoint = \"localhost\"").validate().is_err());
+		assert!(config("readiness_timeout = 0").validate().is_err());
+	}
+
+	#[test]
+	fn sequences_are_cleared_in_the_direction_they_were_sent() {
+		let parse = |args: &[&str]| {
+			let config =
+				["--config-a", "a.toml", "--config-b", "b.toml", "--config-core", "core.toml"];
+			let channel = ["--channel", "channel-0", "--port", "transfer"];
+			ClearPacketsCmd::try_parse_from(
+				["clear-packets"].iter().chain(&config).chain(&channel).chain(args),
+			)
+		};
+		let both = parse(&[]).unwrap();
+		assert!(both.clears_packets_from(ChainSide::A) && both.clears_packets_from(ChainSide::B));
+
+		let from_b = parse(&["--from", "b", "--sequences", "1,2"]).unwrap();
+		assert_eq!(from_b.sequences, vec![1, 2]);
+		assert!(
+			!from_b.clears_packets_from(ChainSide::A) && from_b.clears_packets_from(ChainSide::B)
+		);
+
+		// the sequences alone don't say which direction they belong to
+		assert!(parse(&["--sequences", "1,2"]).is_err());
+	}
+}
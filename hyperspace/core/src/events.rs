@@ -14,7 +14,10 @@
 
 #[cfg(feature = "testing")]
 use crate::send_packet_relay::packet_relay_status;
-use crate::Mode;
+use crate::{
+	packets::{process_packets_concurrently, utils::is_packet_filtered},
+	Mode,
+};
 use codec::Encode;
 use ibc::{
 	core::{
@@ -43,8 +46,14 @@ use ibc::{
 	Height,
 };
 use ibc_proto::google::protobuf::Any;
+use metrics::handler::MetricsHandler;
 use pallet_ibc::light_clients::AnyClientState;
-use primitives::{error::Error, mock::LocalClientTypes, Chain};
+use primitives::{
+	error::Error,
+	in_flight::{InFlightKey, InFlightType},
+	mock::LocalClientTypes,
+	Chain,
+};
 use std::str::FromStr;
 use tendermint_proto::Protobuf;
 
@@ -63,8 +72,10 @@ pub async fn parse_events(
 	sink: &mut impl Chain,
 	events: Vec<IbcEvent>,
 	mode: Option<Mode>,
+	metrics: Option<&MetricsHandler>,
 ) -> Result<Vec<Any>, anyhow::Error> {
 	let mut messages = vec![];
+	let mut packet_events = vec![];
 	// 1. translate events to messages
 	for event in events {
 		match event {
@@ -439,141 +450,28 @@ pub async fn parse_events(
 				let msg = Any { value, type_url: msg.type_url() };
 				messages.push(msg)
 			},
-			IbcEvent::SendPacket(send_packet) => {
-				#[cfg(feature = "testing")]
-				if !packet_relay_status() {
-					continue
-				}
-				// can we send this packet?
-				// 1. query the connection and get the connection delay.
-				// 2. if none, send message immediately
-				// 3. otherwise skip.
-				let port_id = send_packet.packet.source_port.clone();
-				let channel_id = send_packet.packet.source_channel;
-				let channel_response = source
-					.query_channel_end(send_packet.height, channel_id, port_id.clone())
-					.await?;
-				let channel_end =
-					ChannelEnd::try_from(channel_response.channel.ok_or_else(|| {
-						Error::Custom(
-							"Failed to convert to concrete channel end from raw channel end"
-								.to_string(),
-						)
-					})?)?;
-				let connection_id = channel_end
-					.connection_hops
-					.get(0)
-					.ok_or_else(|| Error::Custom("Channel end missing connection id".to_string()))?
-					.clone();
-				let connection_response =
-					source.query_connection_end(send_packet.height, connection_id.clone()).await?;
-				let connection_end =
-					ConnectionEnd::try_from(connection_response.connection.ok_or_else(|| {
-						Error::Custom(format!("ConnectionEnd not found for {connection_id:?}"))
-					})?)?;
-				if !connection_end.delay_period().is_zero() {
-					// We can't send this packet immediately because of connection delays
-					log::debug!(
-						target: "hyperspace",
-						"Skipping packet relay because of connection delays {:?}",
-						connection_end.delay_period()
-					);
-					continue
-				}
-				let seq = u64::from(send_packet.packet.sequence);
-				let packet = send_packet.packet;
-
-				if packet.timeout_height.is_zero() && packet.timeout_timestamp.nanoseconds() == 0 {
-					log::warn!(
-						target: "hyperspace",
-						"Skipping packet relay because packet timeout is zero: {}",
-						packet.sequence
-					);
-					continue
-				}
-
-				let packet_commitment_response = source
-					.query_packet_commitment(send_packet.height, &port_id, &channel_id, seq)
-					.await?;
-				let commitment_proof =
-					CommitmentProofBytes::try_from(packet_commitment_response.proof)?;
-
-				let proof_height = packet_commitment_response
-					.proof_height
-					.expect("Proof height should be present");
-				let proof_height =
-					Height::new(proof_height.revision_number, proof_height.revision_height);
-				let msg = MsgRecvPacket {
-					packet: packet.clone(),
-					proofs: Proofs::new(commitment_proof, None, None, None, proof_height)?,
-					signer: sink.account_id(),
-				};
-
-				let value = msg.encode_vec()?;
-				let msg = Any { value, type_url: msg.type_url() };
-				messages.push(msg);
-				log::debug!(target: "hyperspace", "Sending packet {:?}", packet);
-			},
-			IbcEvent::WriteAcknowledgement(write_ack) => {
-				let port_id = &write_ack.packet.destination_port.clone();
-				let channel_id = &write_ack.packet.destination_channel.clone();
-				let channel_response = source
-					.query_channel_end(write_ack.height, *channel_id, port_id.clone())
-					.await?;
-				let channel_end =
-					ChannelEnd::try_from(channel_response.channel.ok_or_else(|| {
-						Error::Custom(
-							"Failed to convert to concrete channel end from raw channel end"
-								.to_string(),
-						)
-					})?)?;
-				let connection_id = channel_end
-					.connection_hops
-					.get(0)
-					.ok_or_else(|| Error::Custom("Channel end missing connection id".to_string()))?
-					.clone();
-				let connection_response =
-					source.query_connection_end(write_ack.height, connection_id.clone()).await?;
-				let connection_end =
-					ConnectionEnd::try_from(connection_response.connection.ok_or_else(|| {
-						Error::Custom(format!("ConnectionEnd not found for {connection_id:?}"))
-					})?)?;
-				if !connection_end.delay_period().is_zero() {
-					log::debug!(target: "hyperspace", "Skipping write acknowledgement because of connection delay {:?}",
-						connection_end.delay_period());
-					// We can't send this packet immediately because of connection delays
-					continue
-				}
-				let seq = u64::from(write_ack.packet.sequence);
-				let packet = write_ack.packet;
-				let packet_acknowledgement_response = source
-					.query_packet_acknowledgement(write_ack.height, port_id, channel_id, seq)
-					.await?;
-				let acknowledgement = write_ack.ack;
-				let commitment_proof =
-					CommitmentProofBytes::try_from(packet_acknowledgement_response.proof)?;
-
-				let proof_height = packet_acknowledgement_response
-					.proof_height
-					.expect("Proof height should be present");
-				let proof_height =
-					Height::new(proof_height.revision_number, proof_height.revision_height);
-				let msg = MsgAcknowledgement {
-					packet,
-					acknowledgement: acknowledgement.into(),
-					proofs: Proofs::new(commitment_proof, None, None, None, proof_height)?,
-
-					signer: sink.account_id(),
-				};
-
-				let value = msg.encode_vec()?;
-				let msg = Any { value, type_url: msg.type_url() };
-				messages.push(msg)
-			},
+			IbcEvent::SendPacket(_) | IbcEvent::WriteAcknowledgement(_) =>
+				packet_events.push(event),
 			_ => continue,
 		}
 	}
 
+	// the proofs of the packets are queried concurrently
+	let (source, sink) = (&*source, &*sink);
+	messages.extend(
+		process_packets_concurrently(
+			packet_events,
+			source.common_state().max_concurrent_packet_proofs,
+			|event| match event {
+				IbcEvent::SendPacket(ev) => u64::from(ev.packet.sequence),
+				IbcEvent::WriteAcknowledgement(ev) => u64::from(ev.packet.sequence),
+				_ => 0,
+			},
+			|event| packet_event_message(source, sink, metrics, event),
+		)
+		.await,
+	);
+
 	// In light mode do not try to query channel state
 	if let Some(Mode::Light) = mode {
 		return Ok(messages)
@@ -582,6 +480,169 @@ pub async fn parse_events(
 	Ok(messages)
 }
 
+/// Returns the message delivering the packet or acknowledgement of a send packet or write
+/// acknowledgement event to the sink, or `None` if it shouldn't be relayed right away.
+async fn packet_event_message(
+	source: &impl Chain,
+	sink: &impl Chain,
+	metrics: Option<&MetricsHandler>,
+	event: IbcEvent,
+) -> Result<Option<Any>, anyhow::Error> {
+	match event {
+		IbcEvent::SendPacket(send_packet) => {
+			#[cfg(feature = "testing")]
+			if !packet_relay_status() {
+				return Ok(None)
+			}
+			// can we send this packet?
+			// 1. query the connection and get the connection delay.
+			// 2. if none, send message immediately
+			// 3. otherwise skip.
+			let port_id = send_packet.packet.source_port.clone();
+			let channel_id = send_packet.packet.source_channel;
+			let channel_response = source
+				.query_channel_end(send_packet.height, channel_id, port_id.clone())
+				.await?;
+			let channel_end = ChannelEnd::try_from(channel_response.channel.ok_or_else(|| {
+				Error::Custom(
+					"Failed to convert to concrete channel end from raw channel end".to_string(),
+				)
+			})?)?;
+			let connection_id = channel_end
+				.connection_hops
+				.get(0)
+				.ok_or_else(|| Error::Custom("Channel end missing connection id".to_string()))?
+				.clone();
+			let connection_response =
+				source.query_connection_end(send_packet.height, connection_id.clone()).await?;
+			let connection_end =
+				ConnectionEnd::try_from(connection_response.connection.ok_or_else(|| {
+					Error::Custom(format!("ConnectionEnd not found for {connection_id:?}"))
+				})?)?;
+			let delay = source.common_state().channel_delay(
+				channel_id,
+				port_id.clone(),
+				connection_end.delay_period(),
+			);
+			if !delay.is_zero() {
+				// We can't send this packet immediately because of connection delays
+				log::debug!(
+					target: "hyperspace",
+					"Skipping packet relay because of connection delays {:?}",
+					delay
+				);
+				return Ok(None)
+			}
+			let seq = u64::from(send_packet.packet.sequence);
+			let packet = send_packet.packet;
+
+			if packet.timeout_height.is_zero() && packet.timeout_timestamp.nanoseconds() == 0 {
+				log::warn!(
+					target: "hyperspace",
+					"Skipping packet relay because packet timeout is zero: {}",
+					packet.sequence
+				);
+				return Ok(None)
+			}
+
+			if is_packet_filtered(source, metrics, &packet) {
+				return Ok(None)
+			}
+
+			let in_flight_key = InFlightKey::new(&packet, InFlightType::RecvPacket);
+			if sink.common_state().in_flight_packets.contains(&in_flight_key) {
+				log::debug!(target: "hyperspace", "Skipping packet as it's already in flight: {:?}", packet);
+				return Ok(None)
+			}
+
+			let packet_commitment_response = source
+				.query_packet_commitment(send_packet.height, &port_id, &channel_id, seq)
+				.await?;
+			let commitment_proof =
+				CommitmentProofBytes::try_from(packet_commitment_response.proof)?;
+
+			let proof_height =
+				packet_commitment_response.proof_height.expect("Proof height should be present");
+			let proof_height =
+				Height::new(proof_height.revision_number, proof_height.revision_height);
+			let msg = MsgRecvPacket {
+				packet: packet.clone(),
+				proofs: Proofs::new(commitment_proof, None, None, None, proof_height)?,
+				signer: sink.account_id(),
+			};
+
+			let value = msg.encode_vec()?;
+			let msg = Any { value, type_url: msg.type_url() };
+			log::debug!(target: "hyperspace", "Sending packet {:?}", packet);
+			Ok(Some(msg))
+		},
+		IbcEvent::WriteAcknowledgement(write_ack) => {
+			let port_id = &write_ack.packet.destination_port.clone();
+			let channel_id = &write_ack.packet.destination_channel.clone();
+			let channel_response =
+				source.query_channel_end(write_ack.height, *channel_id, port_id.clone()).await?;
+			let channel_end = ChannelEnd::try_from(channel_response.channel.ok_or_else(|| {
+				Error::Custom(
+					"Failed to convert to concrete channel end from raw channel end".to_string(),
+				)
+			})?)?;
+			let connection_id = channel_end
+				.connection_hops
+				.get(0)
+				.ok_or_else(|| Error::Custom("Channel end missing connection id".to_string()))?
+				.clone();
+			let connection_response =
+				source.query_connection_end(write_ack.height, connection_id.clone()).await?;
+			let connection_end =
+				ConnectionEnd::try_from(connection_response.connection.ok_or_else(|| {
+					Error::Custom(format!("ConnectionEnd not found for {connection_id:?}"))
+				})?)?;
+			let delay = source.common_state().channel_delay(
+				*channel_id,
+				port_id.clone(),
+				connection_end.delay_period(),
+			);
+			if !delay.is_zero() {
+				log::debug!(target: "hyperspace", "Skipping write acknowledgement because of connection delay {:?}",
+					delay);
+				// We can't send this packet immediately because of connection delays
+				return Ok(None)
+			}
+			let seq = u64::from(write_ack.packet.sequence);
+			let packet = write_ack.packet;
+			let in_flight_key = InFlightKey::new(&packet, InFlightType::Acknowledgement);
+			if sink.common_state().in_flight_packets.contains(&in_flight_key) {
+				log::debug!(target: "hyperspace", "Skipping acknowledgement for packet as it's already in flight: {:?}", packet);
+				return Ok(None)
+			}
+			let packet_acknowledgement_response = source
+				.query_packet_acknowledgement(write_ack.height, port_id, channel_id, seq)
+				.await?;
+			let acknowledgement = write_ack.ack;
+			let commitment_proof =
+				CommitmentProofBytes::try_from(packet_acknowledgement_response.proof)?;
+
+			let proof_height = packet_acknowledgement_response
+				.proof_height
+				.expect("Proof height should be present");
+			let proof_height =
+				Height::new(proof_height.revision_number, proof_height.revision_height);
+			let msg = MsgAcknowledgement {
+				packet,
+				acknowledgement: acknowledgement.into(),
+				proofs: Proofs::new(commitment_proof, None, None, None, proof_height)?,
+
+				signer: sink.account_id(),
+			};
+
+			let value = msg.encode_vec()?;
+			let msg = Any { value, type_url: msg.type_url() };
+			Ok(Some(msg))
+		},
+		_ => Ok(None),
+	}
+}
+
 /// Fetch the consensus state proof for the sink chain.
 async fn query_host_consensus_state_proof(
 	sink: &impl Chain,
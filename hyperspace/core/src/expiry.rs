@@ -0,0 +1,178 @@
+// Copyright 2022 ComposableFi
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use metrics::handler::MetricsHandler;
+use primitives::{client_expires_within, client_trusting_period_and_age, Chain};
+use std::{sync::atomic::Ordering, time::Duration};
+
+/// Default interval between the expiry checks of a client.
+pub const DEFAULT_CHECK_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// By default the client is updated once less than a third of its trusting period is left.
+pub const DEFAULT_THRESHOLD: f64 = 1.0 / 3.0;
+
+/// Periodically checks the expiry of the client of `source` on `sink`, see
+/// [`check_client_expiry`], until a shutdown is requested.
+///
+/// Without packet traffic the optional client updates are skipped, so an idle path would let the
+/// client expire, which can't be recovered from without governance.
+pub async fn monitor_client_expiry<A: Chain, B: Chain>(
+	source: A,
+	sink: B,
+	metrics: Option<MetricsHandler>,
+	interval: Duration,
+	threshold: f64,
+) {
+	let shutdown = source.common_state().shutdown.clone();
+	let mut checks = tokio::time::interval(interval);
+	loop {
+		tokio::select! {
+			_ = checks.tick() => {},
+			_ = shutdown.requested() => return,
+		}
+		if let Err(e) = check_client_expiry(&source, &sink, metrics.as_ref(), threshold).await {
+			log::warn!(target: "hyperspace", "Failed to check the expiry of the client of {} on {}: {e:?}", source.name(), sink.name());
+		}
+	}
+}
+
+/// Records the time left until the client of `source` on `sink` expires. If less than `threshold`
+/// of its trusting period is left, the client is updated in the next relaying round from `source`
+/// to `sink`, even if the update is optional.
+pub async fn check_client_expiry<A: Chain, B: Chain>(
+	source: &A,
+	sink: &B,
+	metrics: Option<&MetricsHandler>,
+	threshold: f64,
+) -> anyhow::Result<()> {
+	let (height, timestamp) = sink.latest_height_and_timestamp().await?;
+	let client_id = source.client_id();
+	let (trusting_period, age) =
+		client_trusting_period_and_age(sink, height, timestamp, client_id.clone()).await?;
+	let trusting_period = match trusting_period {
+		Some(trusting_period) => trusting_period,
+		None => return Ok(()),
+	};
+	let remaining = trusting_period.saturating_sub(age);
+	if let Some(metrics) = metrics {
+		metrics.handle_client_expiry(client_id.as_str(), remaining);
+	}
+	// the update is due once the client would expire within the last `threshold` of its trusting
+	// period
+	let update_window = trusting_period.mul_f64(threshold);
+	if remaining.is_zero() {
+		log::error!(target: "hyperspace", "Client {client_id} of {} on {} has expired", source.name(), sink.name());
+	} else if client_expires_within(sink, height, timestamp, client_id.clone(), update_window)
+		.await?
+	{
+		log::info!(target: "hyperspace", "Client {client_id} of {} on {} expires in {remaining:?}, forcing an update", source.name(), sink.name());
+		source.common_state().force_client_update.store(true, Ordering::SeqCst);
+	} else {
+		log::debug!(target: "hyperspace", "Client {client_id} of {} on {} expires in {remaining:?}", source.name(), sink.name());
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+	use super::{check_client_expiry, DEFAULT_THRESHOLD};
+	use crate::{process_messages, process_updates};
+	use ibc::{core::ics24_host::identifier::ClientId, timestamp::Timestamp, Height};
+	use ibc_proto::google::protobuf::Any;
+	use ics10_grandpa::{client_state::ClientState, consensus_state::ConsensusState};
+	use pallet_ibc::light_clients::HostFunctionsManager;
+	use primitives::{mock::MockChain, UpdateType};
+	use std::{sync::atomic::Ordering, time::Duration};
+
+	/// Returns a source chain and a sink chain with the client of the source, last updated `age`
+	/// ago.
+	fn chains_with_client_age(age: Duration) -> (MockChain, MockChain) {
+		let client_id = ClientId::new("10-grandpa", 0).unwrap();
+		let source = MockChain::new("source", client_id.clone());
+		let sink = MockChain::new("sink", ClientId::new("07-tendermint", 0).unwrap());
+		{
+			let mut state = sink.state();
+			let updated_at = Timestamp::from_nanoseconds(
+				state.latest_timestamp.nanoseconds() - age.as_nanos() as u64,
+			)
+			.unwrap();
+			let client_state = ClientState::<HostFunctionsManager>::default();
+			let consensus_state =
+				ConsensusState::new(vec![0; 32], updated_at.into_tm_time().unwrap());
+			state.consensus_states.insert(
+				(client_id.clone(), client_state.latest_height()),
+				consensus_state.to_any(),
+			);
+			state.client_states.insert(client_id, client_state.to_any());
+		}
+		(source, sink)
+	}
+
+	/// Relays an optional client update without any events from `source` to `sink`, returns the
+	/// submitted batches.
+	async fn relay_optional_update(source: &mut MockChain, sink: &mut MockChain) -> Vec<Vec<Any>> {
+		let update =
+			Any { type_url: "/ibc.core.client.v1.MsgUpdateClient".to_string(), value: vec![1] };
+		let updates = vec![(update, Height::new(0, 2), vec![], UpdateType::Optional)];
+		let mut msgs = vec![];
+		process_updates(source, sink, &mut None, None, updates, &mut msgs)
+			.await
+			.unwrap();
+		process_messages(sink, &mut None, msgs).await.unwrap();
+		sink.submitted()
+	}
+
+	#[tokio::test]
+	async fn forced_client_update_is_submitted() {
+		let trusting_period =
+			ClientState::<HostFunctionsManager>::default().relay_chain.trusting_period();
+
+		// the optional update of a recently updated client is skipped
+		let (mut source, mut sink) = chains_with_client_age(Duration::from_secs(60));
+		check_client_expiry(&source, &sink, None, DEFAULT_THRESHOLD).await.unwrap();
+		assert!(!source.common_state.force_client_update.load(Ordering::SeqCst));
+		assert!(relay_optional_update(&mut source, &mut sink).await.is_empty());
+
+		// while the update forced by the monitor is submitted, even if the relaying round alone
+		// wouldn't update the client yet
+		let (mut source, mut sink) = chains_with_client_age(trusting_period / 2);
+		check_client_expiry(&source, &sink, None, 0.9).await.unwrap();
+		assert!(source.common_state.force_client_update.load(Ordering::SeqCst));
+		let submitted = relay_optional_update(&mut source, &mut sink).await;
+		assert_eq!(submitted.len(), 1);
+		assert_eq!(submitted[0][0].type_url, "/ibc.core.client.v1.MsgUpdateClient");
+		// the update is forced once
+		assert!(!source.common_state.force_client_update.load(Ordering::SeqCst));
+	}
+
+	#[tokio::test]
+	async fn update_is_forced_close_to_expiry() {
+		let trusting_period =
+			ClientState::<HostFunctionsManager>::default().relay_chain.trusting_period();
+		let update_forced = |age: Duration, threshold: f64| async move {
+			let (source, sink) = chains_with_client_age(age);
+			check_client_expiry(&source, &sink, None, threshold).await.unwrap();
+			source.common_state.force_client_update.load(Ordering::SeqCst)
+		};
+		assert!(!update_forced(Duration::ZERO, DEFAULT_THRESHOLD).await);
+		assert!(!update_forced(trusting_period / 2, DEFAULT_THRESHOLD).await);
+		assert!(update_forced(trusting_period * 3 / 4, DEFAULT_THRESHOLD).await);
+
+		// a higher threshold updates the client earlier
+		assert!(update_forced(trusting_period / 2, 0.9).await);
+		assert!(!update_forced(trusting_period * 3 / 4, 0.0).await);
+		// an expired client can't be updated anymore
+		assert!(!update_forced(trusting_period * 2, DEFAULT_THRESHOLD).await);
+	}
+}
@@ -0,0 +1,489 @@
+// Copyright 2022 ComposableFi
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+	packets::{
+		process_packets_concurrently,
+		utils::{
+			construct_ack_message, construct_recv_message, contiguous_from_next_sequence,
+			is_packet_skipped, report_blocked_ordered_channel,
+		},
+	},
+	process_messages,
+	queue::flush_message_batch,
+};
+use anyhow::anyhow;
+use futures::StreamExt;
+use ibc::{
+	core::{
+		ics02_client::client_state::ClientState as ClientStateT,
+		ics03_connection::connection::ConnectionEnd,
+		ics04_channel::{
+			channel::{ChannelEnd, Order, State},
+			packet::Packet,
+		},
+		ics24_host::identifier::{ChannelId, PortId},
+	},
+	events::IbcEvent,
+	timestamp::Timestamp,
+	Height,
+};
+use ibc_proto::google::protobuf::Any;
+use metrics::handler::MetricsHandler;
+use pallet_ibc::light_clients::AnyClientState;
+use primitives::{
+	find_suitable_proof_height_for_client,
+	in_flight::{InFlightKey, InFlightType},
+	Chain,
+};
+use std::{
+	collections::{HashMap, HashSet},
+	time::Instant,
+};
+
+/// Maximum number of polls of the sink while waiting for the client update to be finalized.
+const MAX_CLIENT_UPDATE_POLLS: usize = 20;
+
+/// Packet messages to relay as soon as the events they were observed in are finalized.
+#[derive(Debug, Default)]
+pub struct PendingFlush {
+	/// The highest height of the events
+	height: Height,
+	/// The packets of the events with their acknowledgements, if the event is a written
+	/// acknowledgement
+	packets: HashMap<InFlightKey, (Packet, Option<Vec<u8>>)>,
+	observed_at: Option<Instant>,
+}
+
+impl PendingFlush {
+	/// Adds the message for the packet event, if it's a send packet or a write acknowledgement
+	/// event of a whitelisted channel. Returns `true` if it was added.
+	pub fn add(
+		&mut self,
+		channel_whitelist: &HashSet<(ChannelId, PortId)>,
+		event: &IbcEvent,
+	) -> bool {
+		let (height, key, packet) = match event {
+			IbcEvent::SendPacket(ev)
+				if channel_whitelist
+					.contains(&(ev.packet.source_channel, ev.packet.source_port.clone())) =>
+				(
+					ev.height,
+					InFlightKey::new(&ev.packet, InFlightType::RecvPacket),
+					(ev.packet.clone(), None),
+				),
+			IbcEvent::WriteAcknowledgement(ev)
+				if channel_whitelist.contains(&(
+					ev.packet.destination_channel,
+					ev.packet.destination_port.clone(),
+				)) =>
+				(
+					ev.height,
+					InFlightKey::new(&ev.packet, InFlightType::Acknowledgement),
+					(ev.packet.clone(), Some(ev.ack.clone())),
+				),
+			_ => return false,
+		};
+		self.height = self.height.max(height);
+		self.packets.insert(key, packet);
+		self.observed_at.get_or_insert_with(Instant::now);
+		true
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.packets.is_empty()
+	}
+
+	pub fn take(&mut self) -> Self {
+		std::mem::take(self)
+	}
+
+	/// Groups the packets by the channel of the source chain they were sent from or acknowledged
+	/// on, separating the sent packets from the acknowledgements.
+	fn by_channel(self) -> HashMap<(ChannelId, PortId), (Vec<Packet>, Vec<(Packet, Vec<u8>)>)> {
+		let mut channels = HashMap::<_, (Vec<_>, Vec<_>)>::new();
+		for (packet, ack) in self.packets.into_values() {
+			match ack {
+				None => channels
+					.entry((packet.source_channel, packet.source_port.clone()))
+					.or_default()
+					.0
+					.push(packet),
+				Some(ack) => channels
+					.entry((packet.destination_channel, packet.destination_port.clone()))
+					.or_default()
+					.1
+					.push((packet, ack)),
+			}
+		}
+		channels
+	}
+}
+
+/// Relays the pending packets and acknowledgements from `source` to `sink` once the height of
+/// their events is finalized on `source`, without waiting for the next finality notification.
+///
+/// The client of `source` on `sink` is updated to the height of the events first, then the
+/// messages are built for the pending packets only. Packets of channels with a connection delay,
+/// timed out packets and packets that were already delivered or are in flight are left to the
+/// regular relaying. Submitted messages are marked as in flight, so they're not built again by
+/// the regular relaying.
+pub async fn eager_flush<A: Chain, B: Chain>(
+	mut source: A,
+	mut sink: B,
+	mut metrics: Option<MetricsHandler>,
+	pending: PendingFlush,
+) -> anyhow::Result<()> {
+	let observed_at = pending.observed_at;
+	let pending_count = pending.packets.len();
+	let client_height =
+		update_client_to(&mut source, &sink, metrics.as_ref(), pending.height).await?;
+
+	let (sink_height, sink_timestamp) = sink.latest_height_and_timestamp().await?;
+	let proof_height = find_suitable_proof_height_for_client(
+		&source,
+		&sink,
+		sink_height,
+		source.client_id(),
+		Height::new(client_height.revision_number, pending.height.revision_height),
+		None,
+		client_height,
+	)
+	.await
+	.ok_or_else(|| {
+		anyhow!("No proof height found on {} for the events at {}", sink.name(), pending.height)
+	})?;
+
+	let mut messages = vec![];
+	for ((channel_id, port_id), (packets, acks)) in pending.by_channel() {
+		match build_channel_messages(
+			&source,
+			&sink,
+			metrics.as_ref(),
+			(channel_id, port_id.clone()),
+			packets,
+			acks,
+			proof_height,
+			(sink_height, sink_timestamp),
+		)
+		.await
+		{
+			Ok(msgs) => messages.extend(msgs),
+			Err(e) => {
+				log::warn!(target: "hyperspace", "Failed to eagerly build the messages of {channel_id}/{port_id} from {} to {}: {e:?}", source.name(), sink.name())
+			},
+		}
+	}
+	log::info!(
+		target: "hyperspace",
+		"Eagerly flushing {} of {} pending messages from {} to {}, {:?} after their events were observed",
+		messages.len(),
+		pending_count,
+		source.name(),
+		sink.name(),
+		observed_at.map(|at| at.elapsed()).unwrap_or_default(),
+	);
+	process_messages(&mut sink, &mut metrics, messages).await
+}
+
+/// Makes sure the client of `source` on `sink` can verify the proofs of the `source` state at
+/// `height`, submitting client updates as `source` finalizes new blocks if it can't yet. Returns
+/// the latest height of the client.
+async fn update_client_to<A: Chain, B: Chain>(
+	source: &mut A,
+	sink: &B,
+	metrics: Option<&MetricsHandler>,
+	height: Height,
+) -> anyhow::Result<Height> {
+	let target_height = source.get_proof_height(height).await;
+	let client_height = query_client_height(&*source, sink).await?;
+	if client_height >= target_height {
+		return Ok(client_height)
+	}
+
+	let mut finality = source.finality_notifications().await?;
+	loop {
+		let finality_event = finality
+			.next()
+			.await
+			.ok_or_else(|| anyhow!("Finality notifications of {} ended", source.name()))?;
+		let updates = source.query_latest_ibc_events(finality_event, sink).await?;
+		// the updates can be capped below the finalized height, so each one is submitted
+		let Some((msg_update_client, update_height, ..)) = updates.into_iter().last() else {
+			continue
+		};
+		log::debug!(target: "hyperspace", "Eagerly updating the client of {} on {} to {update_height}", source.name(), sink.name());
+		flush_message_batch(vec![msg_update_client], metrics, sink).await?;
+		if update_height >= target_height {
+			break
+		}
+	}
+
+	for _ in 0..MAX_CLIENT_UPDATE_POLLS {
+		let client_height = query_client_height(&*source, sink).await?;
+		if client_height >= target_height {
+			return Ok(client_height)
+		}
+		tokio::time::sleep(sink.poll_interval()).await;
+	}
+	Err(anyhow!(
+		"Client of {} on {} didn't reach {target_height} after the update",
+		source.name(),
+		sink.name()
+	))
+}
+
+/// Returns the latest height of the client of `source` on `sink`.
+pub async fn query_client_height(source: &impl Chain, sink: &impl Chain) -> anyhow::Result<Height> {
+	let (sink_height, _) = sink.latest_height_and_timestamp().await?;
+	let client_state = sink
+		.query_client_state(sink_height, source.client_id())
+		.await?
+		.client_state
+		.ok_or_else(|| anyhow!("Client of {} not found on {}", source.name(), sink.name()))?;
+	let client_state = AnyClientState::try_from(client_state).map_err(|e| {
+		anyhow!("Invalid client state of {} on {}: {e:?}", source.name(), sink.name())
+	})?;
+	Ok(client_state.latest_height())
+}
+
+/// Builds the recv messages of the `packets` sent from the channel of `source`, and the
+/// acknowledgement messages of the `acks` written on it, proven at `proof_height`.
+#[allow(clippy::too_many_arguments)]
+async fn build_channel_messages(
+	source: &impl Chain,
+	sink: &impl Chain,
+	metrics: Option<&MetricsHandler>,
+	(channel_id, port_id): (ChannelId, PortId),
+	packets: Vec<Packet>,
+	acks: Vec<(Packet, Vec<u8>)>,
+	proof_height: Height,
+	(sink_height, sink_timestamp): (Height, Timestamp),
+) -> anyhow::Result<Vec<Any>> {
+	let (source_height, _) = source.latest_height_and_timestamp().await?;
+	let channel_end = source
+		.query_channel_end(source_height, channel_id, port_id.clone())
+		.await?
+		.channel
+		.ok_or_else(|| anyhow!("Channel {channel_id}/{port_id} not found on {}", source.name()))?;
+	let channel_end = ChannelEnd::try_from(channel_end)?;
+	if channel_end.state != State::Open {
+		return Ok(vec![])
+	}
+	let connection_id = channel_end
+		.connection_hops
+		.first()
+		.ok_or_else(|| anyhow!("Channel {channel_id}/{port_id} has no connection"))?
+		.clone();
+	let connection_end = source
+		.query_connection_end(source_height, connection_id.clone())
+		.await?
+		.connection
+		.ok_or_else(|| anyhow!("Connection {connection_id} not found on {}", source.name()))?;
+	let connection_end = ConnectionEnd::try_from(connection_end)?;
+	let delay = source.common_state().channel_delay(
+		channel_id,
+		port_id.clone(),
+		connection_end.delay_period(),
+	);
+	if !delay.is_zero() {
+		log::debug!(target: "hyperspace", "Not flushing {channel_id}/{port_id} eagerly, its packets wait for a delay of {delay:?}");
+		return Ok(vec![])
+	}
+	let sink_channel_id = channel_end
+		.counterparty()
+		.channel_id
+		.ok_or_else(|| anyhow!("Channel {channel_id}/{port_id} has no counterparty"))?;
+	let sink_port_id = channel_end.counterparty().port_id.clone();
+	let max_concurrent_packet_proofs = source.common_state().max_concurrent_packet_proofs;
+	let in_flight_packets = &sink.common_state().in_flight_packets;
+
+	// the packets that are never relayed, an ordered channel can't get past them
+	let mut filtered_sequences = HashSet::new();
+	let packets = packets
+		.into_iter()
+		.filter(|packet| {
+			// timeouts are relayed by the regular relaying
+			if packet.timed_out(&sink_timestamp, sink_height) ||
+				(packet.timeout_height.is_zero() && packet.timeout_timestamp.nanoseconds() == 0)
+			{
+				return false
+			}
+			if is_packet_skipped(source, metrics, packet) {
+				filtered_sequences.insert(u64::from(packet.sequence));
+				return false
+			}
+			let in_flight_key = InFlightKey::new(packet, InFlightType::RecvPacket);
+			!in_flight_packets.contains(&in_flight_key)
+		})
+		.collect::<Vec<_>>();
+	let sequences = packets.iter().map(|packet| u64::from(packet.sequence)).collect::<Vec<_>>();
+	let next_sequence_recv = match channel_end.ordering {
+		Order::Ordered => Some(
+			sink.query_next_sequence_recv(sink_height, &sink_port_id, &sink_channel_id)
+				.await?
+				.next_sequence_receive,
+		),
+		_ => None,
+	};
+	let undelivered = match next_sequence_recv {
+		Some(next_sequence_recv) => sequences
+			.into_iter()
+			.filter(|sequence| *sequence >= next_sequence_recv)
+			.collect(),
+		None if sequences.is_empty() => vec![],
+		None =>
+			sink.query_unreceived_packets(
+				sink_height,
+				sink_channel_id,
+				sink_port_id.clone(),
+				sequences,
+			)
+			.await?,
+	};
+	let packets = packets
+		.into_iter()
+		.filter(|packet| undelivered.contains(&u64::from(packet.sequence)))
+		.collect();
+	let mut recv_messages = process_packets_concurrently(
+		packets,
+		max_concurrent_packet_proofs,
+		|packet| u64::from(packet.sequence),
+		|packet| async move {
+			let sequence = u64::from(packet.sequence);
+			let msg = construct_recv_message(source, sink, packet, proof_height).await?;
+			Ok(Some((sequence, msg)))
+		},
+	)
+	.await;
+	if let Some(next_sequence_recv) = next_sequence_recv {
+		let (contiguous, missing) = contiguous_from_next_sequence(
+			&sink_channel_id,
+			next_sequence_recv,
+			|sequence| {
+				in_flight_packets.contains(&InFlightKey {
+					channel_id,
+					port_id: port_id.clone(),
+					sequence: sequence.into(),
+					kind: InFlightType::RecvPacket,
+				})
+			},
+			recv_messages,
+		);
+		report_blocked_ordered_channel(
+			metrics,
+			&channel_id,
+			&port_id,
+			missing,
+			&filtered_sequences,
+		);
+		recv_messages = contiguous;
+	}
+
+	let acks = acks
+		.into_iter()
+		.filter(|(packet, _)| {
+			let in_flight_key = InFlightKey::new(packet, InFlightType::Acknowledgement);
+			!sink.common_state().in_flight_packets.contains(&in_flight_key)
+		})
+		.collect::<Vec<_>>();
+	let sequences = acks.iter().map(|(packet, _)| u64::from(packet.sequence)).collect::<Vec<_>>();
+	let undelivered = if sequences.is_empty() {
+		vec![]
+	} else {
+		sink.query_unreceived_acknowledgements(
+			sink_height,
+			sink_channel_id,
+			sink_port_id,
+			sequences,
+		)
+		.await?
+	};
+	let acks = acks
+		.into_iter()
+		.filter(|(packet, _)| undelivered.contains(&u64::from(packet.sequence)))
+		.collect();
+	let ack_messages = process_packets_concurrently(
+		acks,
+		max_concurrent_packet_proofs,
+		|(packet, _)| u64::from(packet.sequence),
+		|(packet, ack)| async move {
+			construct_ack_message(source, sink, packet, ack, proof_height).await.map(Some)
+		},
+	)
+	.await;
+
+	Ok(recv_messages.into_iter().map(|(_, msg)| msg).chain(ack_messages).collect())
+}
+
+#[cfg(test)]
+pub mod tests {
+	use super::PendingFlush;
+	use ibc::{
+		core::{
+			ics04_channel::{
+				events::{SendPacket, WriteAcknowledgement},
+				packet::{Packet, Sequence},
+			},
+			ics24_host::identifier::{ChannelId, PortId},
+		},
+		events::IbcEvent,
+		Height,
+	};
+
+	fn packet(channel: u64, sequence: u64) -> Packet {
+		Packet {
+			sequence: Sequence::from(sequence),
+			source_port: PortId::transfer(),
+			source_channel: ChannelId::new(channel),
+			destination_port: PortId::transfer(),
+			destination_channel: ChannelId::new(channel),
+			..Default::default()
+		}
+	}
+
+	fn send_packet(channel: u64, sequence: u64, height: u64) -> IbcEvent {
+		IbcEvent::SendPacket(SendPacket {
+			height: Height::new(0, height),
+			packet: packet(channel, sequence),
+		})
+	}
+
+	#[test]
+	fn pending_flush_collects_packet_events_of_whitelisted_channels() {
+		let whitelist = [(ChannelId::new(0), PortId::transfer())].into_iter().collect();
+		let mut pending = PendingFlush::default();
+		assert!(pending.add(&whitelist, &send_packet(0, 1, 10)));
+		assert!(pending.add(&whitelist, &send_packet(0, 2, 12)));
+		assert!(pending.add(
+			&whitelist,
+			&IbcEvent::WriteAcknowledgement(WriteAcknowledgement {
+				height: Height::new(0, 11),
+				packet: packet(0, 1),
+				ack: vec![1],
+			})
+		));
+		// other channels and events are ignored
+		assert!(!pending.add(&whitelist, &send_packet(1, 3, 20)));
+		assert!(!pending.add(&whitelist, &IbcEvent::Empty("".to_string())));
+
+		let taken = pending.take();
+		assert!(pending.is_empty());
+		assert_eq!(taken.packets.len(), 3);
+		assert_eq!(taken.height, Height::new(0, 12));
+		let channels = taken.by_channel();
+		let (packets, acks) = &channels[&(ChannelId::new(0), PortId::transfer())];
+		assert_eq!((packets.len(), acks.len()), (2, 1));
+	}
+}
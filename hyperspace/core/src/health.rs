@@ -0,0 +1,326 @@
+// Copyright 2022 ComposableFi
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use hyper::{
+	http::StatusCode,
+	server::Server,
+	service::{make_service_fn, service_fn},
+	Body, Request, Response,
+};
+use ibc::Height;
+use primitives::Chain;
+use serde::Serialize;
+use std::{
+	collections::BTreeMap,
+	convert::Infallible,
+	future::Future,
+	net::SocketAddr,
+	sync::Arc,
+	time::{Duration, Instant},
+};
+use tokio::sync::watch;
+
+/// The relayer is ready if both chains responded within this time, unless configured otherwise.
+pub const DEFAULT_READINESS_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Number of times the chains are queried within the readiness timeout.
+const PROBES_PER_READINESS_TIMEOUT: u32 = 5;
+
+/// Identifiers of the relayed path on a chain, and the last time the chain responded.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ChainStatus {
+	/// Id of the client of the chain on its counterparty
+	pub client_id: String,
+	pub connection_id: Option<String>,
+	/// Whitelisted `(channel, port)` pairs
+	pub channels: Vec<(String, String)>,
+	#[serde(skip)]
+	pub last_response: Option<Instant>,
+}
+
+/// Progress of relaying in one direction.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DirectionStatus {
+	/// The last source height whose events were relayed
+	pub processed_height: Option<Height>,
+	/// Packet messages found ready to be relayed in the last round
+	pub pending_packets: usize,
+	/// Timeout messages found ready to be relayed in the last round
+	pub pending_timeouts: usize,
+}
+
+/// Relayer status, updated by the relaying loop and served by the health endpoint.
+#[derive(Debug, Clone)]
+pub struct RelayerStatus {
+	/// Keyed by the chain name
+	pub chains: BTreeMap<String, ChainStatus>,
+	/// Keyed by `"{source}->{sink}"`
+	pub directions: BTreeMap<String, DirectionStatus>,
+	/// The relayer is ready if all the chains responded within this time
+	pub readiness_timeout: Duration,
+}
+
+impl RelayerStatus {
+	/// Returns `true` if all the chains responded within the readiness timeout.
+	pub fn is_ready(&self, now: Instant) -> bool {
+		!self.chains.is_empty() &&
+			self.chains.values().all(|chain| {
+				chain
+					.last_response
+					.map_or(false, |at| now.duration_since(at) < self.readiness_timeout)
+			})
+	}
+}
+
+/// Feeds the relaying progress to the health endpoint, so that it never queries the chains itself.
+#[derive(Debug, Clone)]
+pub struct HealthReporter {
+	status: Arc<watch::Sender<RelayerStatus>>,
+}
+
+impl HealthReporter {
+	/// Creates a reporter for the given chains, which are not ready until they respond.
+	pub fn new(chains: &[&str], readiness_timeout: Duration) -> Self {
+		let status = RelayerStatus {
+			chains: chains.iter().map(|name| (name.to_string(), Default::default())).collect(),
+			directions: Default::default(),
+			readiness_timeout,
+		};
+		Self { status: Arc::new(watch::channel(status).0) }
+	}
+
+	pub fn subscribe(&self) -> watch::Receiver<RelayerStatus> {
+		self.status.subscribe()
+	}
+
+	/// Records that the chain responded.
+	pub fn chain_responded(&self, chain: &impl Chain) {
+		let now = Instant::now();
+		self.status.send_modify(|status| {
+			let (name, mut chain) = chain_status(chain);
+			chain.last_response = Some(now);
+			status.chains.insert(name, chain);
+		});
+	}
+
+	/// Records a relaying round from `source` to `sink`. Both chains responded during the round.
+	pub fn round_completed(
+		&self,
+		source: &impl Chain,
+		sink: &impl Chain,
+		processed_height: Option<Height>,
+		pending_packets: usize,
+		pending_timeouts: usize,
+	) {
+		let now = Instant::now();
+		self.status.send_modify(|status| {
+			for chain in [chain_status(source), chain_status(sink)] {
+				let (name, mut chain) = chain;
+				chain.last_response = Some(now);
+				status.chains.insert(name, chain);
+			}
+			let direction = status
+				.directions
+				.entry(format!("{}->{}", source.name(), sink.name()))
+				.or_default();
+			direction.processed_height = processed_height.or(direction.processed_height);
+			direction.pending_packets = pending_packets;
+			direction.pending_timeouts = pending_timeouts;
+		});
+	}
+}
+
+fn chain_status(chain: &impl Chain) -> (String, ChainStatus) {
+	let mut channels = chain
+		.channel_whitelist()
+		.into_iter()
+		.map(|(channel_id, port_id)| (channel_id.to_string(), port_id.to_string()))
+		.collect::<Vec<_>>();
+	channels.sort();
+	let status = ChainStatus {
+		client_id: chain.client_id().to_string(),
+		connection_id: chain.connection_id().map(|id| id.to_string()),
+		channels,
+		last_response: None,
+	};
+	(chain.name().to_string(), status)
+}
+
+/// Queries the latest height of the chain a few times within the readiness timeout, and reports
+/// when it responds, so that the readiness doesn't depend on the relaying rounds, which only run
+/// when the chain finalizes new blocks.
+pub async fn monitor_chain(chain: impl Chain, reporter: HealthReporter) {
+	let interval = reporter.status.borrow().readiness_timeout / PROBES_PER_READINESS_TIMEOUT;
+	loop {
+		match tokio::time::timeout(interval, chain.latest_height_and_timestamp()).await {
+			Ok(Ok(_)) => reporter.chain_responded(&chain),
+			Ok(Err(e)) => {
+				log::warn!(target: "hyperspace", "Failed to query the latest height of {}: {e:?}", chain.name())
+			},
+			Err(_) => {
+				log::warn!(target: "hyperspace", "{} didn't respond within {interval:?}", chain.name())
+			},
+		}
+		tokio::time::sleep(interval).await;
+	}
+}
+
+/// Serves `/healthz` (the process is alive), `/readyz` (both chains responded recently) and
+/// `/status` (the relaying progress as JSON).
+pub fn handle_request(req: &Request<Body>, status: &RelayerStatus) -> Response<Body> {
+	let now = Instant::now();
+	let (code, body) = match req.uri().path() {
+		"/healthz" => (StatusCode::OK, "OK".to_string()),
+		"/readyz" if status.is_ready(now) => (StatusCode::OK, "OK".to_string()),
+		"/readyz" => (StatusCode::SERVICE_UNAVAILABLE, "Not ready".to_string()),
+		"/status" => {
+			let chains = status
+				.chains
+				.iter()
+				.map(|(name, chain)| {
+					let mut value = serde_json::to_value(chain).unwrap_or_default();
+					value["seconds_since_last_response"] =
+						chain.last_response.map(|at| now.duration_since(at).as_secs()).into();
+					(name.clone(), value)
+				})
+				.collect::<serde_json::Map<_, _>>();
+			let body = serde_json::json!({
+				"ready": status.is_ready(now),
+				"chains": chains,
+				"directions": status.directions,
+			});
+			(StatusCode::OK, body.to_string())
+		},
+		_ => (StatusCode::NOT_FOUND, "Not found.".to_string()),
+	};
+	let mut response = Response::new(Body::from(body));
+	*response.status_mut() = code;
+	response
+}
+
+/// Binds an HTTP server serving the health endpoints, returns the future running it.
+pub fn serve(
+	addr: SocketAddr,
+	status: watch::Receiver<RelayerStatus>,
+) -> Result<impl Future<Output = Result<(), hyper::Error>>, hyper::Error> {
+	let service = make_service_fn(move |_| {
+		let status = status.clone();
+		async move {
+			Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
+				let response = handle_request(&req, &status.borrow());
+				async move { Ok::<_, Infallible>(response) }
+			}))
+		}
+	});
+	Ok(Server::try_bind(&addr)?.serve(service))
+}
+
+#[cfg(test)]
+pub mod tests {
+	use super::{
+		handle_request, monitor_chain, serve, ChainStatus, DirectionStatus, HealthReporter,
+		RelayerStatus, DEFAULT_READINESS_TIMEOUT,
+	};
+	use hyper::{http::StatusCode, Body, Request};
+	use ibc::{core::ics24_host::identifier::ClientId, Height};
+	use primitives::mock::MockChain;
+	use std::time::{Duration, Instant};
+	use tokio::sync::watch;
+
+	async fn get(status: &watch::Receiver<RelayerStatus>, path: &str) -> (StatusCode, String) {
+		let req = Request::get(path).body(Body::empty()).unwrap();
+		let response = handle_request(&req, &status.borrow());
+		let code = response.status();
+		let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+		(code, String::from_utf8(body.to_vec()).unwrap())
+	}
+
+	#[tokio::test]
+	async fn endpoints_follow_relaying_progress() {
+		let chains = ["a", "b"].map(|name| (name.to_string(), ChainStatus::default()));
+		let (sender, receiver) = watch::channel(RelayerStatus {
+			chains: chains.into_iter().collect(),
+			directions: Default::default(),
+			readiness_timeout: DEFAULT_READINESS_TIMEOUT,
+		});
+
+		// nothing was relayed yet
+		assert_eq!(get(&receiver, "/healthz").await.0, StatusCode::OK);
+		assert_eq!(get(&receiver, "/readyz").await.0, StatusCode::SERVICE_UNAVAILABLE);
+		assert_eq!(get(&receiver, "/unknown").await.0, StatusCode::NOT_FOUND);
+
+		// a round of the relaying loop
+		let now = Instant::now();
+		sender.send_modify(|status| {
+			for chain in status.chains.values_mut() {
+				chain.client_id = "07-tendermint-0".to_string();
+				chain.last_response = Some(now);
+			}
+			status.directions.insert(
+				"a->b".to_string(),
+				DirectionStatus {
+					processed_height: Some(Height::new(1, 10)),
+					pending_packets: 2,
+					pending_timeouts: 1,
+				},
+			);
+		});
+		assert_eq!(get(&receiver, "/readyz").await.0, StatusCode::OK);
+		let (code, body) = get(&receiver, "/status").await;
+		assert_eq!(code, StatusCode::OK);
+		let status: serde_json::Value = serde_json::from_str(&body).unwrap();
+		assert_eq!(status["ready"], true);
+		assert_eq!(status["chains"]["a"]["client_id"], "07-tendermint-0");
+		assert_eq!(status["directions"]["a->b"]["pending_packets"], 2);
+		assert_eq!(status["directions"]["a->b"]["processed_height"]["revision_height"], 10);
+
+		// one of the chains stopped responding
+		let stale = now.checked_sub(DEFAULT_READINESS_TIMEOUT).unwrap();
+		sender
+			.send_modify(|status| status.chains.get_mut("b").unwrap().last_response = Some(stale));
+		assert!(!receiver.borrow().is_ready(Instant::now()));
+		assert_eq!(get(&receiver, "/readyz").await.0, StatusCode::SERVICE_UNAVAILABLE);
+	}
+
+	#[tokio::test]
+	async fn chains_are_ready_once_they_respond() {
+		let chain_a = MockChain::new("a", ClientId::new("07-tendermint", 0).unwrap());
+		let chain_b = MockChain::new("b", ClientId::new("07-tendermint", 1).unwrap());
+		let reporter = HealthReporter::new(&["a", "b"], Duration::from_secs(5));
+		let status = reporter.subscribe();
+		assert_eq!(get(&status, "/readyz").await.0, StatusCode::SERVICE_UNAVAILABLE);
+
+		// no relaying round is needed, the chains only have to respond
+		tokio::spawn(monitor_chain(chain_a, reporter.clone()));
+		tokio::spawn(monitor_chain(chain_b, reporter));
+		tokio::time::timeout(Duration::from_secs(5), async {
+			while !status.borrow().is_ready(Instant::now()) {
+				tokio::time::sleep(Duration::from_millis(10)).await;
+			}
+		})
+		.await
+		.expect("chains should be ready");
+		let (code, body) = get(&status, "/status").await;
+		assert_eq!(code, StatusCode::OK);
+		let status: serde_json::Value = serde_json::from_str(&body).unwrap();
+		assert_eq!(status["chains"]["b"]["client_id"], "07-tendermint-1");
+	}
+
+	#[tokio::test]
+	async fn serving_on_a_taken_address_fails() {
+		let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let reporter = HealthReporter::new(&["a", "b"], DEFAULT_READINESS_TIMEOUT);
+		assert!(serve(listener.local_addr().unwrap(), reporter.subscribe()).is_err());
+	}
+}
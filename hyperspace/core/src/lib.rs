@@ -17,22 +17,37 @@
 pub mod chain;
 pub mod command;
 pub mod events;
+pub mod expiry;
+pub mod flush;
+pub mod health;
 pub mod logging;
 mod macros;
+pub mod misbehaviour;
 pub mod packets;
 pub mod queue;
+pub mod state;
 pub mod substrate;
 mod utils;
 
-use crate::utils::RecentStream;
+use crate::{
+	flush::PendingFlush,
+	health::HealthReporter,
+	packets::PacketScope,
+	state::RelayerState,
+	utils::{supervise, RecentStream},
+};
 use anyhow::anyhow;
 use events::{has_packet_events, parse_events};
-use futures::{future::ready, StreamExt, TryFutureExt};
+use futures::{future::ready, Stream, StreamExt, TryFutureExt};
 use ibc::{events::IbcEvent, Height};
 use ibc_proto::google::protobuf::Any;
 use metrics::handler::MetricsHandler;
-use primitives::{Chain, IbcProvider, UndeliveredType, UpdateType};
-use std::collections::HashSet;
+use primitives::{
+	add_opened_channels_to_whitelist, client_update_age, resolve_channel_whitelist, Chain,
+	CommonClientState, IbcProvider, UndeliveredType, UpdateType,
+};
+use std::{collections::HashSet, pin::Pin, sync::atomic::Ordering, time::Duration};
+use tokio::task::JoinSet;
 
 #[derive(Copy, Debug, Clone)]
 pub enum Mode {
@@ -40,47 +55,228 @@ pub enum Mode {
 	Light,
 }
 
+/// Delay before restarting a failed relaying direction, doubled after every consecutive failure.
+const DIRECTION_RESTART_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_DIRECTION_RESTART_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Optional settings of [`relay`], none of them are enabled by default.
+#[derive(Clone, Default)]
+pub struct RelayOptions {
+	/// Metrics of chain A
+	pub chain_a_metrics: Option<MetricsHandler>,
+	/// Metrics of chain B
+	pub chain_b_metrics: Option<MetricsHandler>,
+	/// Relaying mode, packets are relayed unless it's [`Mode::Light`]
+	pub mode: Option<Mode>,
+	/// State the processed heights are persisted to and resumed from
+	pub state: Option<RelayerState>,
+	/// Reporter of the relaying rounds, for the health endpoint
+	pub health: Option<HealthReporter>,
+}
+
 /// Core relayer loop, waits for new finality events and forwards any new [`ibc::IbcEvents`]
 /// to the counter party chain.
+///
+/// Each relaying direction runs in its own task, so that an error or a stall on one chain doesn't
+/// hold back the other direction. A failed direction is restarted with a backoff, and dropping the
+/// returned future stops both.
 pub async fn relay<A, B>(
 	mut chain_a: A,
 	mut chain_b: B,
-	mut chain_a_metrics: Option<MetricsHandler>,
-	mut chain_b_metrics: Option<MetricsHandler>,
-	mode: Option<Mode>,
+	options: RelayOptions,
 ) -> Result<(), anyhow::Error>
 where
 	A: Chain,
 	B: Chain,
 {
-	let stream_a = RecentStream::new(chain_a.finality_notifications().await?);
-	let stream_b = RecentStream::new(chain_b.finality_notifications().await?);
-	let (mut chain_a_finality, mut chain_b_finality) = (stream_a, stream_b);
+	let RelayOptions { chain_a_metrics, chain_b_metrics, mode, state, health } = options;
+	if let Err(e) = resolve_channel_whitelist(&mut chain_a).await {
+		log::warn!(target: "hyperspace", "Failed to resolve the channel whitelist of {}: {e:?}", chain_a.name());
+	}
+	if let Err(e) = resolve_channel_whitelist(&mut chain_b).await {
+		log::warn!(target: "hyperspace", "Failed to resolve the channel whitelist of {}: {e:?}", chain_b.name());
+	}
+
+	// resume from the persisted processed heights, unless their blocks were reorged out
+	if let Some(state) = &state {
+		if let Some(height) = state.verify_processed_height(&chain_a, chain_b.name()).await {
+			chain_a.common_state().set_processed_height(height);
+		}
+		if let Some(height) = state.verify_processed_height(&chain_b, chain_a.name()).await {
+			chain_b.common_state().set_processed_height(height);
+		}
+	}
 
-	// Introduce altering between branches so that each branch gets a chance to execute first after
-	// another one
-	let mut first_executed = false;
+	let a_to_b = {
+		let (source, sink) = (chain_a.clone(), chain_b.clone());
+		let (state, health) = (state.clone(), health.clone());
+		supervise(
+			format!("{} -> {}", chain_a.name(), chain_b.name()),
+			DIRECTION_RESTART_BACKOFF,
+			MAX_DIRECTION_RESTART_BACKOFF,
+			move || {
+				relay_direction(
+					source.clone(),
+					sink.clone(),
+					chain_a_metrics.clone(),
+					mode,
+					state.clone(),
+					health.clone(),
+				)
+			},
+		)
+	};
+	let b_to_a = supervise(
+		format!("{} -> {}", chain_b.name(), chain_a.name()),
+		DIRECTION_RESTART_BACKOFF,
+		MAX_DIRECTION_RESTART_BACKOFF,
+		move || {
+			relay_direction(
+				chain_b.clone(),
+				chain_a.clone(),
+				chain_b_metrics.clone(),
+				mode,
+				state.clone(),
+				health.clone(),
+			)
+		},
+	);
+	futures::future::join(a_to_b, b_to_a).await;
+	Ok(())
+}
 
-	// loop forever
+/// Relays the events of `source` to `sink` as the finality notifications of `source` arrive.
+///
+/// With eager flushing enabled, the packet events of `source` are also relayed as soon as they
+/// are finalized, see [`flush::eager_flush`].
+///
+/// Returns once a shutdown is requested, after the submissions in progress are completed.
+async fn relay_direction<A: Chain, B: Chain>(
+	mut source: A,
+	mut sink: B,
+	mut metrics: Option<MetricsHandler>,
+	mode: Option<Mode>,
+	state: Option<RelayerState>,
+	health: Option<HealthReporter>,
+) -> anyhow::Result<()> {
+	if let Some(processed_height) = state
+		.as_ref()
+		.and_then(|state| state.processed_height(source.name(), sink.name()))
+	{
+		if let Err(e) =
+			backfill_from_processed_height(&mut source, &mut sink, &mut metrics, processed_height)
+				.await
+		{
+			log::warn!(target: "hyperspace", "Failed to backfill the blocks of {} after {processed_height}: {e:?}", source.name());
+		}
+	}
+	let mut finality = RecentStream::new(source.finality_notifications().await?);
+	let mut events = match source.common_state().eager_flush {
+		true => Some(source.ibc_events().await),
+		false => None,
+	};
+	let mut pending = PendingFlush::default();
+	// at most one flush runs at a time, the events observed meanwhile are flushed after it
+	let mut flushes = JoinSet::new();
+	let shutdown = source.common_state().shutdown.clone();
+
+	// loop until a shutdown is requested
 	loop {
+		if flushes.is_empty() && !pending.is_empty() {
+			flushes.spawn(flush::eager_flush(
+				source.clone(),
+				sink.clone(),
+				metrics.clone(),
+				pending.take(),
+			));
+		}
 		tokio::select! {
-			// new finality event from chain A
-			result = chain_a_finality.next(), if !first_executed => {
-				first_executed = true;
-				process_finality_event(&mut chain_a, &mut chain_b, &mut chain_a_metrics, mode, result, &mut chain_a_finality, &mut chain_b_finality).await?;
-			}
-			// new finality event from chain B
-			result = chain_b_finality.next() => {
-				first_executed = false;
-				process_finality_event(&mut chain_b, &mut chain_a, &mut chain_b_metrics, mode, result, &mut chain_b_finality, &mut chain_a_finality).await?;
-			}
-			else => {
-				first_executed = false;
+			result = finality.next() => {
+				process_finality_event(&mut source, &mut sink, &mut metrics, mode, state.as_ref(), health.as_ref(), result, &mut finality).await?;
 			}
+			event = next_event(&mut events) => match event {
+				Some(event) if !misbehaviour::is_relaying_paused(&source, &sink) => {
+					pending.add(&source.channel_whitelist(), &event);
+				},
+				Some(_) => {},
+				None => {
+					log::warn!(target: "hyperspace", "Event stream closed for {}, disabling eager flushing", source.name());
+					events = None;
+				},
+			},
+			Some(result) = flushes.join_next() => match result {
+				Ok(Ok(())) => (),
+				Ok(Err(e)) => log::warn!(target: "hyperspace", "Failed to eagerly flush packets from {} to {}: {e:?}", source.name(), sink.name()),
+				Err(e) => log::warn!(target: "hyperspace", "Eager flush from {} to {} failed: {e}", source.name(), sink.name()),
+			},
+			// the round in progress, if any, was finished before getting here
+			_ = shutdown.requested() => {
+				if !flushes.is_empty() {
+					log::info!(target: "hyperspace", "Waiting for the eager flush from {} to {} to finish", source.name(), sink.name());
+				}
+				while let Some(result) = flushes.join_next().await {
+					if let Ok(Err(e)) = result {
+						log::warn!(target: "hyperspace", "Failed to eagerly flush packets from {} to {}: {e:?}", source.name(), sink.name());
+					}
+				}
+				let processed_height = state.as_ref().and_then(|state| state.processed_height(source.name(), sink.name()));
+				log::info!(target: "hyperspace", "Stopped relaying from {} to {}, processed height: {processed_height:?}", source.name(), sink.name());
+				return Ok(())
+			},
 		}
 	}
 }
 
+/// Relays the packets of the blocks of `source` finalized while the relayer was down, if the
+/// client of `source` on `sink` was updated past the persisted processed height meanwhile, e.g.
+/// by another relayer.
+///
+/// The events are queried from the client height, so the events of the blocks between the
+/// processed height and the client height aren't queried anymore, and can't be proven at their
+/// heights either, since the client has no consensus states for them. Their packets, acks and
+/// timeouts are found by their sequences instead, and proven at the heights known to the client.
+async fn backfill_from_processed_height<A: Chain, B: Chain>(
+	source: &mut A,
+	sink: &mut B,
+	metrics: &mut Option<MetricsHandler>,
+	processed_height: Height,
+) -> anyhow::Result<()> {
+	let client_height = flush::query_client_height(&*source, &*sink).await?;
+	if client_height <= processed_height.increment() {
+		return Ok(())
+	}
+	log::warn!(
+		target: "hyperspace",
+		"Client of {} on {} was updated to {client_height} past the processed height {processed_height}, backfilling the packets of the skipped blocks",
+		source.name(), sink.name()
+	);
+	let (ready_packets, timeout_msgs) = packets::query_ready_and_timed_out_packets(
+		&*source,
+		&*sink,
+		metrics.as_ref(),
+		PacketScope::Round,
+	)
+	.await?;
+	log::info!(
+		target: "hyperspace",
+		"Backfilling {} packets and {} timeouts from {} to {}",
+		ready_packets.len(), timeout_msgs.len(), source.name(), sink.name()
+	);
+	process_messages(sink, metrics, ready_packets).await?;
+	process_timeouts(source, metrics, timeout_msgs).await?;
+	Ok(())
+}
+
+/// Returns the next event of the stream, or never resolves if there is no stream.
+async fn next_event(
+	events: &mut Option<Pin<Box<dyn Stream<Item = IbcEvent> + Send + 'static>>>,
+) -> Option<IbcEvent> {
+	match events {
+		Some(events) => events.next().await,
+		None => futures::future::pending().await,
+	}
+}
+
 pub async fn fish<A, B>(chain_a: A, chain_b: B) -> Result<(), anyhow::Error>
 where
 	A: Chain,
@@ -117,10 +313,12 @@ where
 				};
 				// The corresponding transaction on tendermint may not be indexed yet, so we wait for a bit
 				if chain_a.client_type() == "07-tendermint" {
-					tokio::time::sleep(chain_a.expected_block_time()).await;
+					tokio::time::sleep(chain_a.poll_interval()).await;
 				}
 				let message = chain_a.query_client_message(update).await.map_err(|e| { log::info!("error: {}", e); e })?;
-				chain_b.check_for_misbehaviour(&chain_a, message).await.map_err(|e| { log::info!("error: {}", e); e })?;
+				if let Some(evidence) = chain_b.check_for_misbehaviour(&chain_a, message).await.map_err(|e| { log::info!("error: {}", e); e })? {
+					queue::flush_message_batch(vec![evidence], None, &chain_a).await.map_err(|e| anyhow!("Failed to submit misbehaviour report: {e:?}"))?;
+				}
 			}
 			// new finality event from chain B
 			update = chain_b_client_updates.next() => {
@@ -130,10 +328,12 @@ where
 				};
 				// The corresponding transaction on tendermint may not be indexed yet, so we wait for a bit
 				if chain_a.client_type() == "07-tendermint" {
-					tokio::time::sleep(chain_a.expected_block_time()).await;
+					tokio::time::sleep(chain_a.poll_interval()).await;
 				}
 				let message = chain_b.query_client_message(update).await.map_err(|e| { log::info!("error: {}", e); e })?;
-				chain_a.check_for_misbehaviour(&chain_b, message).await.map_err(|e| { log::info!("error: {}", e); e })?;
+				if let Some(evidence) = chain_a.check_for_misbehaviour(&chain_b, message).await.map_err(|e| { log::info!("error: {}", e); e })? {
+					queue::flush_message_batch(vec![evidence], None, &chain_b).await.map_err(|e| anyhow!("Failed to submit misbehaviour report: {e:?}"))?;
+				}
 			}
 		}
 	}
@@ -141,14 +341,16 @@ where
 	Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn process_finality_event<A: Chain, B: Chain>(
 	source: &mut A,
 	sink: &mut B,
 	metrics: &mut Option<MetricsHandler>,
 	mode: Option<Mode>,
+	state: Option<&RelayerState>,
+	health: Option<&HealthReporter>,
 	result: Option<A::FinalityEvent>,
 	stream_source: &mut RecentStream<A::FinalityEvent>,
-	stream_sink: &mut RecentStream<B::FinalityEvent>,
 ) -> anyhow::Result<()> {
 	match result {
 		// stream closed
@@ -164,23 +366,42 @@ async fn process_finality_event<A: Chain, B: Chain>(
 					},
 				};
 			};
-			*stream_sink = loop {
-				match sink.finality_notifications().await {
-					Ok(stream) => break RecentStream::new(stream),
-					Err(e) => {
-						log::error!("Failed to get finality notifications for {} {:?}. Trying again in 30 seconds...", sink.name(), e);
-						tokio::time::sleep(std::time::Duration::from_secs(30)).await;
-						let _ = sink.reconnect().await;
-					},
-				};
-			};
 		},
 		Some(finality_event) => {
 			log::info!("=======================================================");
 			log::info!("Received finality notification from {}", source.name(),);
+			if let Some(height) = source.finality_event_height(&finality_event).await {
+				if !is_finality_progressing(
+					source.name(),
+					source.common_state(),
+					sink.common_state(),
+					height,
+				) {
+					// the processed height may have been rewound
+					if let (Some(state), Some(processed_height)) =
+						(state, source.common_state().processed_height())
+					{
+						if let Err(e) = state
+							.rewind_processed_height(source.name(), sink.name(), processed_height)
+							.await
+						{
+							log::warn!(target: "hyperspace", "Failed to persist the processed height of {}: {e:?}", source.name());
+						}
+					}
+					return Ok(())
+				}
+			}
 
-			let result =
-				process_some_finality_event(source, sink, metrics, mode, finality_event).await;
+			let result = process_some_finality_event(
+				source,
+				sink,
+				metrics,
+				mode,
+				state,
+				health,
+				finality_event,
+			)
+			.await;
 
 			match result {
 				Ok(()) => {
@@ -204,24 +425,87 @@ async fn process_finality_event<A: Chain, B: Chain>(
 	Ok(())
 }
 
+/// Returns `false` if the height finalized by a finality event of the `source` chain isn't above
+/// its processed height, e.g. because of a misbehaving RPC, in which case the event is skipped.
+///
+/// If the height went back by more than the finality regression threshold, the state derived
+/// from the `source` blocks above it can't be trusted: the processed height is moved back, the
+/// undelivered sequences of `source` are reset, and the messages in flight to `sink`, which are
+/// all proven with the `source` state, are forgotten, so that they're rebuilt once the finalized
+/// height moves forward again.
+fn is_finality_progressing(
+	name: &str,
+	source: &CommonClientState,
+	sink: &CommonClientState,
+	finalized_height: Height,
+) -> bool {
+	let processed_height = match source.processed_height() {
+		Some(processed_height) if finalized_height <= processed_height => processed_height,
+		_ => return true,
+	};
+	log::warn!(target: "hyperspace", "Skipping finality notification of {name} at {finalized_height}, already processed up to {processed_height}");
+	let regression = processed_height
+		.revision_height
+		.saturating_sub(finalized_height.revision_height);
+	if finalized_height.revision_number < processed_height.revision_number ||
+		regression > source.finality_regression_threshold
+	{
+		log::warn!(target: "hyperspace", "Finalized height of {name} went back from {processed_height} to {finalized_height}, invalidating the packets in flight");
+		source.rewind_processed_height(finalized_height);
+		source.maybe_has_undelivered_packets.lock().unwrap().clear();
+		sink.in_flight_packets.clear();
+	}
+	false
+}
+
 async fn process_some_finality_event<A: Chain, B: Chain>(
 	source: &mut A,
 	sink: &mut B,
 	metrics: &mut Option<MetricsHandler>,
 	mode: Option<Mode>,
+	state: Option<&RelayerState>,
+	health: Option<&HealthReporter>,
 	finality_event: <A as IbcProvider>::FinalityEvent,
 ) -> anyhow::Result<()> {
-	let updates = source
+	let mut updates = source
 		.query_latest_ibc_events(finality_event, &*sink)
 		.await
 		.map_err(|e| anyhow!("Failed to fetch IBC events for finality event {e}"))?;
+	// while packet relaying is paused the clients are still updated, so that they don't expire
+	let is_paused = misbehaviour::is_relaying_paused(&*source, &*sink);
+	if is_paused {
+		log::warn!(target: "hyperspace", "Packet relaying between {} and {} is paused after a misbehaviour", source.name(), sink.name());
+	} else {
+		let events =
+			updates.iter().flat_map(|(_, _, events, _)| events.clone()).collect::<Vec<_>>();
+		match misbehaviour::check_client_updates(&*source, &*sink, metrics.as_ref(), &events).await
+		{
+			Ok(true) => return Ok(()),
+			Ok(false) => {},
+			Err(e) => {
+				log::warn!(target: "hyperspace", "Failed to check the client updates on {} for misbehaviour: {e:?}", source.name())
+			},
+		}
+	}
+	if let Some(state) = state {
+		state.skip_processed_events(source.name(), sink.name(), &mut updates);
+	}
+	let processed_height = updates.iter().map(|(_, height, ..)| *height).max();
 	log::trace!(target: "hyperspace", "Received updates count: {}", updates.len());
 	// query packets that can now be sent, at this sink height because of connection
 	// delay.
-	let (ready_packets, timeout_msgs) =
-		packets::query_ready_and_timed_out_packets(&*source, &*sink)
-			.await
-			.map_err(|e| anyhow!("Failed to parse events: {:?}", e))?;
+	let (ready_packets, timeout_msgs) = if is_paused {
+		(vec![], vec![])
+	} else {
+		packets::query_ready_and_timed_out_packets(
+			&*source,
+			&*sink,
+			metrics.as_ref(),
+			PacketScope::Round,
+		)
+		.await
+		.map_err(|e| anyhow!("Failed to parse events: {:?}", e))?
+	};
 
 	let mut msgs = Vec::new();
 
@@ -249,10 +533,35 @@ async fn process_some_finality_event<A: Chain, B: Chain>(
 
 	process_updates(source, sink, metrics, mode, updates, &mut msgs).await?;
 
+	let (pending_packets, pending_timeouts) = (ready_packets.len(), timeout_msgs.len());
 	msgs.extend(ready_packets);
 
 	process_messages(sink, metrics, msgs).await?;
 	process_timeouts(source, metrics, timeout_msgs).await?;
+
+	if let Some(height) = processed_height {
+		source.common_state().set_processed_height(height);
+		if let Some(state) = state {
+			let block_hash = source.get_block(height).await.unwrap_or_else(|e| {
+				log::warn!(target: "hyperspace", "Failed to query the block of {} at {height}: {e:?}", source.name());
+				None
+			});
+			if let Err(e) =
+				state.set_processed_height(source.name(), sink.name(), height, block_hash).await
+			{
+				log::warn!(target: "hyperspace", "Failed to persist the processed height of {}: {e:?}", source.name());
+			}
+		}
+	}
+	if let Some(health) = health {
+		health.round_completed(
+			&*source,
+			&*sink,
+			processed_height,
+			pending_packets,
+			pending_timeouts,
+		);
+	}
 	Ok(())
 }
 
@@ -277,7 +586,26 @@ async fn process_updates<A: Chain, B: Chain>(
 			HashSet::new()
 		};
 
-	for (msg_update_client, height, events, update_type) in updates {
+	// If all the updates are optional, they would normally be skipped when there's no packet
+	// traffic, so make sure the client on the sink doesn't go stale in the meantime. The expiry
+	// monitor asks for an update before the client expires, see [`expiry::check_client_expiry`]
+	let client_update_forced = !updates.is_empty() &&
+		source.common_state().force_client_update.swap(false, Ordering::SeqCst);
+	let client_needs_refresh = client_update_forced ||
+		(source.common_state().skip_optional_client_updates &&
+			!updates.is_empty() &&
+			updates.iter().all(|(.., update_type)| update_type.is_optional()) &&
+			is_client_refresh_needed(source, sink).await.unwrap_or_else(|e| {
+				log::warn!(target: "hyperspace", "Failed to check the staleness of the client of {} on {}: {e:?}", source.name(), sink.name());
+				false
+			}));
+	let last_update_index = updates.len().saturating_sub(1);
+	// only the client updates are relayed while packet relaying is paused
+	let is_paused = misbehaviour::is_relaying_paused(&*source, &*sink);
+
+	for (i, (msg_update_client, height, events, update_type)) in updates.into_iter().enumerate() {
+		source.common_state().in_flight_packets.remove_events(&events);
+		add_opened_channels_to_whitelist(source, &events);
 		if let Some(metrics) = metrics.as_mut() {
 			if let Err(e) = metrics.handle_events(events.as_slice()).await {
 				log::error!("Failed to handle metrics for {} {:?}", source.name(), e);
@@ -285,9 +613,13 @@ async fn process_updates<A: Chain, B: Chain>(
 		}
 
 		let event_types = events.iter().map(|ev| ev.event_type()).collect::<Vec<_>>();
-		let mut messages = parse_events(source, sink, events, mode)
-			.await
-			.map_err(|e| anyhow!("Failed to parse events: {:?}", e))?;
+		let mut messages = if is_paused {
+			vec![]
+		} else {
+			parse_events(source, sink, events, mode, metrics.as_ref())
+				.await
+				.map_err(|e| anyhow!("Failed to parse events: {:?}", e))?
+		};
 
 		log::trace!(
 			target: "hyperspace",
@@ -298,35 +630,40 @@ async fn process_updates<A: Chain, B: Chain>(
 		let need_to_send_proofs_for_sequences = (sink_has_undelivered_acks ||
 			source_has_undelivered_acks) &&
 			mandatory_heights_for_undelivered_seqs.contains(&height.revision_height);
+		let need_to_refresh_client = client_needs_refresh && i == last_update_index;
 		let common_state = source.common_state();
 		let skip_optional_updates = common_state.skip_optional_client_updates;
 
-		// We want to send client update if packet messages exist but where not sent due
-		// to a connection delay even if client update message is optional
-		match (
-			// TODO: we actually may send only when timeout of some packet has reached,
-			// not when we have *any* undelivered packets. But this requires rewriting
-			// `find_suitable_proof_height_for_client` function, that uses binary
-			// search, which won't work in this case
-			skip_optional_updates &&
-				update_type.is_optional() &&
-				!need_to_send_proofs_for_sequences,
-			has_packet_events(&event_types),
-			messages.is_empty(),
+		// TODO: we actually may send only when timeout of some packet has reached,
+		// not when we have *any* undelivered packets. But this requires rewriting
+		// `find_suitable_proof_height_for_client` function, that uses binary
+		// search, which won't work in this case
+		if can_skip_client_update(
+			&update_type,
+			skip_optional_updates,
+			need_to_send_proofs_for_sequences || need_to_refresh_client,
+			!is_paused && has_packet_events(&event_types),
+			!messages.is_empty(),
 		) {
-			(true, false, true) => {
-				// skip sending ibc messages if no new events
-				log::info!("Skipping finality notification for {}", sink.name());
-				continue
-			},
-			(false, _, true) =>
-				if update_type.is_optional() && need_to_send_proofs_for_sequences {
-					log::info!("Sending an optional update because source ({}) chain has undelivered sequences", sink.name());
-				} else {
-					log::info!("Sending mandatory client update message for {}", sink.name())
-				},
-			_ => log::info!("Received finalized events from: {} {event_types:#?}", source.name()),
-		};
+			// skip sending ibc messages if no new events
+			log::info!("Skipping finality notification for {}", sink.name());
+			if let Some(metrics) = metrics.as_ref() {
+				metrics.handle_skipped_client_update();
+			}
+			continue
+		}
+		if !messages.is_empty() {
+			log::info!("Received finalized events from: {} {event_types:#?}", source.name());
+		} else if update_type.is_optional() && need_to_send_proofs_for_sequences {
+			log::info!(
+				"Sending an optional update because source ({}) chain has undelivered sequences",
+				sink.name()
+			);
+		} else if need_to_refresh_client {
+			log::info!("Sending an optional update because the client of {} on {} is stale or close to expiry", source.name(), sink.name());
+		} else {
+			log::info!("Sending mandatory client update message for {}", sink.name())
+		}
 		msgs.push(msg_update_client);
 		msgs.append(&mut messages);
 	}
@@ -403,6 +740,45 @@ async fn find_mandatory_heights_for_undelivered_sequences<A: Chain>(
 	mandatory_updates_for_undelivered_seqs
 }
 
+/// Returns `true` if an optional client update can be skipped: the update isn't needed for proofs
+/// or for refreshing the client, and there are no messages to send with it.
+fn can_skip_client_update(
+	update_type: &UpdateType,
+	skip_optional_updates: bool,
+	client_update_needed: bool,
+	has_packet_events: bool,
+	has_messages: bool,
+) -> bool {
+	// We want to send client update if packet messages exist but where not sent due
+	// to a connection delay even if client update message is optional
+	skip_optional_updates &&
+		update_type.is_optional() &&
+		!client_update_needed &&
+		!has_packet_events &&
+		!has_messages
+}
+
+/// Returns `true` if the light client of the `source` chain on the `sink` chain should be updated
+/// even if there are no messages to relay, because it wasn't updated for longer than
+/// [`primitives::CommonClientState::max_client_staleness`].
+async fn is_client_refresh_needed<A: Chain, B: Chain>(
+	source: &A,
+	sink: &B,
+) -> anyhow::Result<bool> {
+	let Some(max_staleness) = source.common_state().max_client_staleness else { return Ok(false) };
+	let (sink_height, sink_timestamp) = sink.latest_height_and_timestamp().await?;
+	let age = client_update_age(sink, sink_height, sink_timestamp, source.client_id()).await?;
+	let is_stale = is_client_stale(age, max_staleness);
+	if is_stale {
+		log::debug!(target: "hyperspace", "Client of {} on {} wasn't updated for {age:?}", source.name(), sink.name());
+	}
+	Ok(is_stale)
+}
+
+fn is_client_stale(age: Duration, max_staleness: Duration) -> bool {
+	age >= max_staleness
+}
+
 #[cfg(feature = "testing")]
 pub mod send_packet_relay {
 	use std::sync::atomic::{AtomicBool, Ordering};
@@ -418,3 +794,219 @@ pub mod send_packet_relay {
 		RELAY_PACKETS.store(status, Ordering::SeqCst);
 	}
 }
+
+#[cfg(test)]
+pub mod tests {
+	use super::{
+		can_skip_client_update, is_client_stale, is_finality_progressing, process_finality_event,
+		relay, RelayOptions,
+	};
+	use crate::{state::RelayerState, utils::RecentStream};
+	use ibc::{
+		core::{
+			ics04_channel::{
+				events::SendPacket,
+				msgs::recv_packet::MsgRecvPacket,
+				packet::{Packet, Sequence},
+			},
+			ics23_commitment::commitment::CommitmentProofBytes,
+			ics24_host::identifier::ClientId,
+		},
+		events::IbcEvent,
+		proofs::Proofs,
+		tx_msg::Msg,
+		Height,
+	};
+	use ibc_proto::google::protobuf::Any;
+	use primitives::{
+		in_flight::{InFlightKey, InFlightType},
+		mock::MockChain,
+		Chain, CommonClientState, UndeliveredType, UpdateType,
+	};
+	use std::time::Duration;
+
+	/// Queues optional client updates of `chain` at the given heights, each with a packet event, so
+	/// that an update is only submitted if its events are processed.
+	fn queue_updates(chain: &MockChain, heights: std::ops::RangeInclusive<u64>) {
+		let mut state = chain.state();
+		state.finality_events.push(Height::new(0, *heights.end()));
+		for height in heights {
+			let update = Any {
+				type_url: "/ibc.core.client.v1.MsgUpdateClient".to_string(),
+				value: vec![height as u8],
+			};
+			let packet = Packet { sequence: Sequence::from(height), ..Default::default() };
+			let event = IbcEvent::SendPacket(SendPacket { height: Height::new(0, height), packet });
+			state
+				.updates
+				.insert(Height::new(0, height), (update, vec![event], UpdateType::Optional));
+		}
+	}
+
+	/// Relays from `source` to `sink` until `height` is processed, then kills the relayer.
+	async fn relay_until(
+		source: &MockChain,
+		sink: &MockChain,
+		state_path: &std::path::Path,
+		height: u64,
+	) {
+		let state = RelayerState::load(state_path);
+		let options = RelayOptions { state: Some(state.clone()), ..Default::default() };
+		let relayer = tokio::spawn(relay(source.clone(), sink.clone(), options));
+		tokio::time::timeout(Duration::from_secs(10), async {
+			while state.processed_height(source.name(), sink.name()) != Some(Height::new(0, height))
+			{
+				tokio::time::sleep(Duration::from_millis(10)).await;
+			}
+		})
+		.await
+		.expect("relayer should process the events");
+		relayer.abort();
+		assert!(relayer.await.unwrap_err().is_cancelled());
+	}
+
+	#[tokio::test]
+	async fn killed_relayer_resumes_without_dropping_or_repeating_events() {
+		let state_path = std::env::temp_dir()
+			.join(format!("hyperspace-relay-restart-{}.json", std::process::id()));
+		let _ = std::fs::remove_file(&state_path);
+		let source = MockChain::new("source", ClientId::new("07-tendermint", 0).unwrap());
+		let sink = MockChain::new("sink", ClientId::new("07-tendermint", 1).unwrap());
+
+		queue_updates(&source, 1..=5);
+		relay_until(&source, &sink, &state_path, 5).await;
+
+		// after the restart the events are queried from the client height, which is behind
+		queue_updates(&source, 3..=8);
+		relay_until(&source, &sink, &state_path, 8).await;
+
+		let relayed_heights = sink
+			.submitted()
+			.into_iter()
+			.flatten()
+			.map(|msg| msg.value[0] as u64)
+			.collect::<Vec<_>>();
+		assert_eq!(relayed_heights, (1..=8).collect::<Vec<_>>());
+		std::fs::remove_file(&state_path).unwrap();
+	}
+
+	#[test]
+	fn finality_regressions_are_skipped_until_reconverged() {
+		let (source, sink) = (CommonClientState::default(), CommonClientState::default());
+		let threshold = source.finality_regression_threshold;
+		// the relaying loop, returns the processed heights
+		let process = |heights: &[u64]| {
+			let mut processed = vec![];
+			for height in heights {
+				let finalized_height = Height::new(0, *height);
+				if is_finality_progressing("a", &source, &sink, finalized_height) {
+					source.set_processed_height(finalized_height);
+					processed.push(*height);
+				}
+			}
+			processed
+		};
+
+		assert_eq!(process(&[1, 2, 3, 100, 95, 100]), vec![1, 2, 3, 100]);
+		// small regressions are skipped
+		assert_eq!(process(&[100, 100 - threshold, 101]), vec![101]);
+		assert_eq!(source.processed_height(), Some(Height::new(0, 101)));
+
+		// a deep regression invalidates the state derived from the blocks above it
+		let proof = CommitmentProofBytes::try_from(vec![1]).unwrap();
+		let proofs = Proofs::new(proof, None, None, None, Height::new(0, 101)).unwrap();
+		let packet = Packet { data: vec![1], ..Default::default() };
+		let msg = MsgRecvPacket::new(packet.clone(), proofs, "signer".parse().unwrap()).to_any();
+		sink.in_flight_packets.insert_messages(&[msg]);
+		source
+			.maybe_has_undelivered_packets
+			.lock()
+			.unwrap()
+			.insert(UndeliveredType::Recvs, true);
+		let regressed_height = 101 - threshold - 1;
+		assert_eq!(process(&[regressed_height]), vec![]);
+		assert_eq!(source.processed_height(), Some(Height::new(0, regressed_height)));
+		assert!(!source.has_undelivered_sequences(UndeliveredType::Recvs));
+		let key = InFlightKey::new(&packet, InFlightType::RecvPacket);
+		assert!(!sink.in_flight_packets.contains(&key));
+
+		// and the following heights are processed again
+		let heights = [regressed_height + 1, regressed_height + 2, 102];
+		assert_eq!(process(&heights), heights);
+	}
+
+	#[tokio::test]
+	async fn relaying_loop_reconverges_after_finality_regressions() {
+		let state_path = std::env::temp_dir()
+			.join(format!("hyperspace-finality-regression-{}.json", std::process::id()));
+		let _ = std::fs::remove_file(&state_path);
+		let state = RelayerState::load(&state_path);
+		let mut source = MockChain::new("source", ClientId::new("07-tendermint", 0).unwrap());
+		let mut sink = MockChain::new("sink", ClientId::new("07-tendermint", 1).unwrap());
+		let mut finality = RecentStream::new(futures::stream::pending());
+		let regressed_height = 100 - source.common_state.finality_regression_threshold - 1;
+
+		for height in [1, 2, 3, 100, 95, regressed_height, 101] {
+			queue_updates(&source, height..=height);
+			let finality_event = Some(Height::new(0, height));
+			process_finality_event(
+				&mut source,
+				&mut sink,
+				&mut None,
+				None,
+				Some(&state),
+				None,
+				finality_event,
+				&mut finality,
+			)
+			.await
+			.unwrap();
+			if height == regressed_height {
+				// the rewound height survives a restart
+				let processed_height =
+					RelayerState::load(&state_path).processed_height("source", "sink");
+				assert_eq!(processed_height, Some(Height::new(0, regressed_height)));
+			}
+		}
+
+		// the block at 95 is processed again once finality moves past the rewound height
+		let relayed_heights = sink
+			.submitted()
+			.into_iter()
+			.flatten()
+			.map(|msg| msg.value[0] as u64)
+			.collect::<Vec<_>>();
+		assert_eq!(relayed_heights, vec![1, 2, 3, 100, 95, 101]);
+		assert_eq!(source.common_state.processed_height(), Some(Height::new(0, 101)));
+		assert_eq!(
+			RelayerState::load(&state_path).processed_height("source", "sink"),
+			Some(Height::new(0, 101))
+		);
+		std::fs::remove_file(&state_path).unwrap();
+	}
+
+	#[test]
+	fn optional_updates_without_messages_are_skipped() {
+		assert!(can_skip_client_update(&UpdateType::Optional, true, false, false, false));
+		// anything to send with the update
+		assert!(!can_skip_client_update(&UpdateType::Optional, true, false, true, false));
+		assert!(!can_skip_client_update(&UpdateType::Optional, true, false, false, true));
+		// skipping is disabled
+		assert!(!can_skip_client_update(&UpdateType::Optional, false, false, false, false));
+	}
+
+	#[test]
+	fn mandatory_updates_are_never_skipped() {
+		assert!(!can_skip_client_update(&UpdateType::Mandatory, true, false, false, false));
+	}
+
+	#[test]
+	fn stale_client_is_updated() {
+		let max_staleness = Duration::from_secs(60 * 60);
+		assert!(!is_client_stale(Duration::from_secs(60), max_staleness));
+		assert!(is_client_stale(max_staleness, max_staleness));
+		// the staleness overrides skipping the optional update
+		let needed = is_client_stale(Duration::from_secs(2 * 60 * 60), max_staleness);
+		assert!(!can_skip_client_update(&UpdateType::Optional, true, needed, false, false));
+	}
+}
@@ -107,6 +107,19 @@ macro_rules! chains {
 				}
 			}
 
+			async fn finality_event_height(&self, finality_event: &Self::FinalityEvent) -> Option<Height> {
+				#[allow(unreachable_patterns)]
+				match (self, finality_event) {
+					$(
+						$(#[$($meta)*])*
+						(Self::$name(chain), AnyFinalityEvent::$name(finality_event)) =>
+							chain.finality_event_height(finality_event).await,
+					)*
+					(Self::Wasm(c), finality_event) => c.inner.finality_event_height(finality_event).await,
+					_ => None,
+				}
+			}
+
 			async fn ibc_events(&self) -> Pin<Box<dyn Stream<Item = IbcEvent> + Send + 'static>> {
 				match self {
 					$(
@@ -188,6 +201,16 @@ macro_rules! chains {
 				}
 			}
 
+			fn full_key_path(&self, key_path_type: KeyPathType, packet: &Packet) -> Vec<u8> {
+				match self {
+					$(
+						$(#[$($meta)*])*
+						Self::$name(chain) => chain.full_key_path(key_path_type, packet),
+					)*
+					AnyChain::Wasm(c) => c.inner.full_key_path(key_path_type, packet),
+				}
+			}
+
 			async fn query_proof(&self, at: Height, keys: Vec<Vec<u8>>) -> Result<Vec<u8>, Self::Error> {
 				match self {
 					$(
@@ -201,6 +224,23 @@ macro_rules! chains {
 				}
 			}
 
+			async fn query_proofs(
+				&self,
+				at: Height,
+				keys: Vec<Vec<u8>>,
+			) -> Result<Vec<Vec<u8>>, Self::Error> {
+				match self {
+					$(
+						$(#[$($meta)*])*
+						Self::$name(chain) => chain
+							.query_proofs(at, keys)
+							.await
+							.map_err(AnyError::$name),
+					)*
+					AnyChain::Wasm(c) => c.inner.query_proofs(at, keys).await,
+				}
+			}
+
 			async fn query_packet_commitment(
 				&self,
 				at: Height,
@@ -548,6 +588,16 @@ macro_rules! chains {
 				}
 			}
 
+			async fn get_block(&self, height: Height) -> Result<Option<Vec<u8>>, Self::Error> {
+				match self {
+					$(
+						$(#[$($meta)*])*
+						Self::$name(chain) => chain.get_block(height).await.map_err(AnyError::$name),
+					)*
+					Self::Wasm(c) => c.inner.get_block(height).await,
+				}
+			}
+
 			async fn query_clients(&self) -> Result<Vec<ClientId>, Self::Error> {
 				match self {
 					$(
@@ -645,6 +695,16 @@ macro_rules! chains {
 				}
 			}
 
+			fn requires_gzipped_wasm(&self) -> bool {
+				match self {
+					$(
+						$(#[$($meta)*])*
+						Self::$name(chain) => chain.requires_gzipped_wasm(),
+					)*
+					Self::Wasm(c) => c.inner.requires_gzipped_wasm(),
+				}
+			}
+
 			async fn query_connection_id_from_tx_hash(
 				&self,
 				tx_id: Self::TransactionId,
@@ -720,7 +780,7 @@ macro_rules! chains {
 				&self,
 				counterparty: &C,
 				client_message: AnyClientMessage,
-			) -> Result<(), anyhow::Error> {
+			) -> Result<Option<Any>, anyhow::Error> {
 				match self {
 					$(
 						$(#[$($meta)*])*
@@ -776,6 +836,16 @@ macro_rules! chains {
 				}
 			}
 
+			async fn estimate_fee(&self, msgs: &[Any]) -> Result<FeeEstimate, Self::Error> {
+				match self {
+					$(
+						$(#[$($meta)*])*
+						Self::$name(chain) => chain.estimate_fee(msgs).await.map_err(AnyError::$name),
+					)*
+					Self::Wasm(c) => c.inner.estimate_fee(msgs).await,
+				}
+			}
+
 			async fn finality_notifications(
 				&self,
 			) -> Result<Pin<Box<dyn Stream<Item = Self::FinalityEvent> + Send + Sync>>, Self::Error> {
@@ -897,6 +967,54 @@ macro_rules! chains {
 					Self::Wasm(c) => c.inner.reconnect().await,
 				}
 			}
+
+			fn validate_signer(&self) -> Result<Signer, Self::Error> {
+				match self {
+					$(
+						$(#[$($meta)*])*
+						Self::$name(chain) => chain.validate_signer().map_err(AnyError::$name),
+					)*
+					Self::Wasm(c) => c.inner.validate_signer(),
+				}
+			}
+
+			fn submission_error_class(&self, error: &Self::Error) -> primitives::SubmissionErrorClass {
+				match (self, error) {
+					$(
+						$(#[$($meta)*])*
+						(Self::$name(chain), AnyError::$name(e)) => chain.submission_error_class(e),
+					)*
+					(Self::Wasm(c), e) => c.inner.submission_error_class(e),
+					#[allow(unreachable_patterns)]
+					(_, _) => primitives::SubmissionErrorClass::Other,
+				}
+			}
+
+			async fn query_clients_expiring_within(
+				&self,
+				duration: std::time::Duration,
+			) -> Result<Vec<ClientId>, Self::Error> {
+				match self {
+					$(
+						$(#[$($meta)*])*
+						Self::$name(chain) => chain
+							.query_clients_expiring_within(duration)
+							.await
+							.map_err(AnyError::$name),
+					)*
+					Self::Wasm(c) => c.inner.query_clients_expiring_within(duration).await,
+				}
+			}
+
+			async fn health_check<C: Chain>(&self, counterparty: &C) -> Result<(), anyhow::Error> {
+				match self {
+					$(
+						$(#[$($meta)*])*
+						Self::$name(chain) => chain.health_check(counterparty).await,
+					)*
+					Self::Wasm(c) => c.inner.health_check(counterparty).await,
+				}
+			}
 		}
 
 		#[async_trait]
@@ -1030,7 +1148,7 @@ macro_rules! chains {
 					$(
 						$(#[$($meta)*])*
 						Self::$name(chain) => {
-							chain.channel_whitelist.push((channel_id, port_id));
+							chain.channel_whitelist.push((channel_id, port_id).into());
 						},
 					)*
 				}
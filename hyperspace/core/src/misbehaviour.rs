@@ -0,0 +1,179 @@
+// Copyright 2022 ComposableFi
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::queue::flush_message_batch;
+use ibc::{
+	core::{ics02_client::events::UpdateClient, ics24_host::identifier::ClientId},
+	events::IbcEvent,
+};
+use ibc_proto::google::protobuf::Any;
+use metrics::handler::MetricsHandler;
+use primitives::Chain;
+
+/// Returns the updates of the client with the given id among the events.
+pub fn client_updates(events: &[IbcEvent], client_id: &ClientId) -> Vec<UpdateClient> {
+	events
+		.iter()
+		.filter_map(|event| match event {
+			IbcEvent::UpdateClient(update) if update.client_id() == client_id =>
+				Some(update.clone()),
+			_ => None,
+		})
+		.collect()
+}
+
+/// Returns `true` if packet relaying between the chains was paused, see [`report_misbehaviour`].
+pub fn is_relaying_paused(source: &impl Chain, sink: &impl Chain) -> bool {
+	source.common_state().pause_flag.is_set() || sink.common_state().pause_flag.is_set()
+}
+
+/// Checks the updates of the `sink` client observed in the `source` events for misbehaviour, i.e.
+/// headers that conflict with the `sink` chain. Returns `true` if misbehaviour was found, in which
+/// case it's reported, see [`report_misbehaviour`].
+pub async fn check_client_updates<A: Chain, B: Chain>(
+	source: &A,
+	sink: &B,
+	metrics: Option<&MetricsHandler>,
+	events: &[IbcEvent],
+) -> Result<bool, anyhow::Error> {
+	for update in client_updates(events, &sink.client_id()) {
+		let client_message = source.query_client_message(update).await?;
+		if let Some(evidence) = sink.check_for_misbehaviour(source, client_message).await? {
+			report_misbehaviour(source, sink, metrics, evidence).await?;
+			return Ok(true)
+		}
+	}
+	Ok(false)
+}
+
+/// Pauses packet relaying between the chains and submits the evidence of the `sink` misbehaviour
+/// to `source`, ahead of any other message. Relaying stays paused until the operator clears the
+/// pause flag.
+pub async fn report_misbehaviour<A: Chain, B: Chain>(
+	source: &A,
+	sink: &B,
+	metrics: Option<&MetricsHandler>,
+	evidence: Any,
+) -> Result<(), anyhow::Error> {
+	let reason = format!(
+		"Misbehaviour of {} detected in the updates of client {} on {}",
+		sink.name(),
+		sink.client_id(),
+		source.name()
+	);
+	log::error!(target: "hyperspace", "🚨 {reason}, packet relaying between {} and {} is paused", source.name(), sink.name());
+	if let Some(metrics) = metrics {
+		metrics.handle_misbehaviour();
+	}
+	let pause_flag = &source.common_state().pause_flag;
+	pause_flag.set(&reason)?;
+	if let Some(path) = pause_flag.path() {
+		log::error!(target: "hyperspace", "Remove {} to resume packet relaying", path.display());
+	}
+	flush_message_batch(vec![evidence], metrics, source).await
+}
+
+#[cfg(test)]
+pub mod tests {
+	use super::{client_updates, is_relaying_paused};
+	use crate::{process_finality_event, utils::RecentStream};
+	use ibc::{
+		core::{
+			ics02_client::events::{Attributes, UpdateClient},
+			ics04_channel::{events::SendPacket, packet::Packet},
+			ics24_host::identifier::ClientId,
+		},
+		events::IbcEvent,
+		Height,
+	};
+	use ibc_proto::google::protobuf::Any;
+	use ics11_beefy::client_message::ClientMessage;
+	use pallet_ibc::light_clients::AnyClientMessage;
+	use primitives::{mock::MockChain, pause::PauseFlag, UpdateType};
+
+	fn update(client_id: &ClientId) -> IbcEvent {
+		IbcEvent::UpdateClient(UpdateClient::from(Attributes {
+			height: Height::new(0, 1),
+			client_id: client_id.clone(),
+			client_type: "07-tendermint".to_string(),
+			consensus_height: Height::new(0, 1),
+		}))
+	}
+
+	#[test]
+	fn misbehaviour_in_tracked_client_updates_pauses_relaying() {
+		let tracked = ClientId::new("07-tendermint", 0).unwrap();
+		let other = ClientId::new("07-tendermint", 1).unwrap();
+		let events = vec![update(&other), IbcEvent::Empty("".to_string()), update(&tracked)];
+		let updates = client_updates(&events, &tracked);
+		assert_eq!(updates.len(), 1);
+		assert_eq!(updates[0].client_id(), &tracked);
+
+		// both chains are given the same flag, so setting it on the source pauses both directions
+		let source_flag = PauseFlag::default();
+		let sink_flag = source_flag.clone();
+		assert!(!sink_flag.is_set());
+		source_flag.set("misbehaviour").unwrap();
+		assert!(sink_flag.is_set());
+	}
+
+	#[tokio::test]
+	async fn detected_misbehaviour_pauses_relaying() {
+		let mut source = MockChain::new("source", ClientId::new("07-tendermint", 0).unwrap());
+		let mut sink = MockChain::new("sink", ClientId::new("10-grandpa", 0).unwrap());
+		let evidence = Any { type_url: "evidence".to_string(), value: vec![1] };
+		// every update of the sink client observed on the source conflicts with the sink
+		source.state().client_message =
+			Some(AnyClientMessage::Beefy(ClientMessage::Misbehaviour(())));
+		sink.state().misbehaviour = Some(evidence.clone());
+		let mut finality = RecentStream::new(futures::stream::pending());
+
+		let update_client = update(&sink.client_id);
+		let send_packet = |height| {
+			IbcEvent::SendPacket(SendPacket {
+				height: Height::new(0, height),
+				packet: Packet { sequence: height.into(), data: vec![1], ..Default::default() },
+			})
+		};
+		let client_update = |height| Any { type_url: "update".to_string(), value: vec![height] };
+		let rounds = [
+			(1, update_client, UpdateType::Mandatory),
+			(2, send_packet(2), UpdateType::Optional),
+			(3, send_packet(3), UpdateType::Mandatory),
+		];
+		for (height, event, update_type) in rounds {
+			let update = client_update(height as u8);
+			let height = Height::new(0, height);
+			source.state().updates.insert(height, (update, vec![event], update_type));
+			process_finality_event(
+				&mut source,
+				&mut sink,
+				&mut None,
+				None,
+				None,
+				None,
+				Some(height),
+				&mut finality,
+			)
+			.await
+			.unwrap();
+		}
+
+		// the evidence is submitted, and only the mandatory client update is relayed to the
+		// misbehaving chain afterwards, without the packets
+		assert!(is_relaying_paused(&source, &sink));
+		assert_eq!(source.submitted(), vec![vec![evidence]]);
+		assert_eq!(sink.submitted(), vec![vec![client_update(3)]]);
+	}
+}
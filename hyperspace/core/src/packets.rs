@@ -14,41 +14,69 @@
 
 #[cfg(feature = "testing")]
 use crate::send_packet_relay::packet_relay_status;
+use futures::{stream, Future, StreamExt};
 use rand::Rng;
 use sp_runtime::Either::{Left, Right};
 use std::{
+	collections::HashSet,
 	sync::{
 		atomic::{AtomicUsize, Ordering},
-		Arc,
+		Mutex,
 	},
 	time::Duration,
 };
-use tokio::{task::JoinSet, time::sleep};
+use tokio::time::sleep;
 
 use crate::packets::utils::{
 	construct_ack_message, construct_recv_message, construct_timeout_message,
-	get_timeout_proof_height, verify_delay_passed, VerifyDelayOn,
+	contiguous_from_next_sequence, get_timeout_proof_height, is_packet_skipped,
+	report_blocked_ordered_channel, verify_delay_passed, VerifyDelayOn,
 };
 use ibc::{
-	applications::transfer::packet::PacketData,
 	core::{
 		ics02_client::client_state::ClientState as ClientStateT,
 		ics03_connection::connection::ConnectionEnd,
-		ics04_channel::channel::{ChannelEnd, State},
+		ics04_channel::channel::{ChannelEnd, Order, State},
 	},
 	Height,
 };
 use ibc_proto::google::protobuf::Any;
+use metrics::handler::MetricsHandler;
 use pallet_ibc::light_clients::AnyClientState;
 use primitives::{
-	error::Error, find_suitable_proof_height_for_client, packet_info_to_packet,
-	query_undelivered_acks, query_undelivered_sequences, Chain, UndeliveredType,
+	error::Error,
+	find_suitable_proof_height_for_client,
+	in_flight::{InFlightKey, InFlightType},
+	packet_info_to_packet, query_undelivered_acks, query_undelivered_sequences, Chain,
+	UndeliveredType,
 };
 
 pub mod connection_delay;
 pub mod utils;
 
-pub const PROCESS_PACKETS_BATCH_SIZE: usize = 100;
+/// Undelivered packets and acknowledgements [`query_ready_and_timed_out_packets`] builds the
+/// messages of.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum PacketScope<'a> {
+	/// At most `max_packets_to_process` of each channel, as in a relaying round
+	#[default]
+	Round,
+	/// All of them, or only the ones with the given sequences if any are given
+	All(&'a [u64]),
+}
+
+impl PacketScope<'_> {
+	/// Selects the sequences of a channel, before any proof is queried for them.
+	fn select(self, sequences: Vec<u64>, max_packets_to_process: usize) -> Vec<u64> {
+		match self {
+			PacketScope::Round => sequences.into_iter().take(max_packets_to_process).collect(),
+			PacketScope::All(selected) => sequences
+				.into_iter()
+				.filter(|sequence| selected.is_empty() || selected.contains(sequence))
+				.collect(),
+		}
+	}
+}
 
 /// Returns a tuple of messages, with the first item being packets that are ready to be sent to the
 /// sink chain. And the second item being packet timeouts that should be sent to the source.
@@ -59,9 +87,13 @@ pub const PROCESS_PACKETS_BATCH_SIZE: usize = 100;
 /// source -> ack_packet     -> sink   => sink has undelivered acks
 /// source -> timeout_packet -> source => source & sink has undelivered timeouts (since timeouts
 /// need both clients to be up to date)
+///
+/// Only the packets in `scope` are processed, see [`PacketScope`].
 pub async fn query_ready_and_timed_out_packets(
 	source: &impl Chain,
 	sink: &impl Chain,
+	metrics: Option<&MetricsHandler>,
+	scope: PacketScope<'_>,
 ) -> Result<(Vec<Any>, Vec<Any>), anyhow::Error> {
 	let mut messages = vec![];
 	let mut timeout_messages = vec![];
@@ -134,9 +166,10 @@ pub async fn query_ready_and_timed_out_packets(
 			},
 		};
 
-		let next_sequence_recv = sink
+		let next_sequence_receive = sink
 			.query_next_sequence_recv(sink_height, &sink_port_id, &sink_channel_id)
-			.await?;
+			.await?
+			.next_sequence_receive;
 
 		let source_client_state_on_sink =
 			sink.query_client_state(sink_height, source.client_id()).await?;
@@ -179,9 +212,15 @@ pub async fn query_ready_and_timed_out_packets(
 		let latest_source_height_on_sink = source_client_state_on_sink.latest_height();
 
 		let max_packets_to_process = source.common_state().max_packets_to_process;
+		let max_concurrent_packet_proofs = source.common_state().max_concurrent_packet_proofs;
+		let connection_delay = source.common_state().channel_delay(
+			channel_id,
+			port_id.clone(),
+			source_connection_end.delay_period(),
+		);
 
 		// query packets that are waiting for connection delay.
-		let seqs = query_undelivered_sequences(
+		let undelivered_seqs = query_undelivered_sequences(
 			source_height,
 			sink_height,
 			channel_id,
@@ -189,10 +228,9 @@ pub async fn query_ready_and_timed_out_packets(
 			source,
 			sink,
 		)
-		.await?
-		.into_iter()
-		.take(max_packets_to_process)
-		.collect::<Vec<_>>();
+		.await?;
+		let undelivered_seqs_count = undelivered_seqs.len();
+		let seqs = scope.select(undelivered_seqs, max_packets_to_process);
 
 		log::debug!(target: "hyperspace", "Found {} undelivered packets for {:?}/{:?} for {seqs:?}", seqs.len(), channel_id, port_id.clone());
 
@@ -201,27 +239,36 @@ pub async fn query_ready_and_timed_out_packets(
 		send_packets.sort();
 		send_packets.dedup();
 		log::trace!(target: "hyperspace", "SendPackets count after deduplication: {}", send_packets.len());
-		let mut recv_packets_join_set: JoinSet<Result<_, anyhow::Error>> = JoinSet::new();
-		let source = Arc::new(source.clone());
-		let sink = Arc::new(sink.clone());
-		let timeout_packets_count = Arc::new(AtomicUsize::new(0));
-		let send_packets_count = Arc::new(AtomicUsize::new(0));
-		for send_packets in send_packets.chunks(PROCESS_PACKETS_BATCH_SIZE) {
-			for send_packet in send_packets.iter().cloned() {
-				let source_connection_end = source_connection_end.clone();
-				let sink_channel_end = sink_channel_end.clone();
-				let source_connection_end = source_connection_end.clone();
-				let source = source.clone();
-				let sink = sink.clone();
+		if let Some(metrics) = metrics {
+			let oldest_packet_age = send_packets
+				.iter()
+				.filter_map(|send_packet| send_packet.height)
+				.min()
+				.map_or(0, |height| source_height.revision_height.saturating_sub(height));
+			metrics.handle_undelivered_packets(
+				&channel_id,
+				&port_id,
+				undelivered_seqs_count,
+				oldest_packet_age,
+			);
+		}
+		let timeout_packets_count = AtomicUsize::new(0);
+		let send_packets_count = AtomicUsize::new(0);
+		// the packets that are never relayed, an ordered channel can't get past them
+		let filtered_sequences = Mutex::new(HashSet::new());
+		let (timeout_packets_count, recv_packets_count, filtered_sequences) =
+			(&timeout_packets_count, &send_packets_count, &filtered_sequences);
+		let sink_channel_end = &sink_channel_end;
+		let send_packets_msgs = process_packets_concurrently(
+			send_packets,
+			max_concurrent_packet_proofs,
+			|send_packet| send_packet.sequence,
+			move |send_packet| {
 				let duration = Duration::from_millis(
 					rand::thread_rng().gen_range(1..source.rpc_call_delay().as_millis() as u64),
 				);
-				let timeout_packets_count = timeout_packets_count.clone();
-				let recv_packets_count = send_packets_count.clone();
-				recv_packets_join_set.spawn(async move {
+				async move {
 					sleep(duration).await;
-					let source = &source;
-					let sink = &sink;
 					let packet = packet_info_to_packet(&send_packet);
 					// Check if packet has timed out
 					let packet_height = send_packet.height.ok_or_else(|| {
@@ -230,13 +277,16 @@ pub async fn query_ready_and_timed_out_packets(
 
 					if packet.timed_out(&sink_timestamp, sink_height) {
 						timeout_packets_count.fetch_add(1, Ordering::SeqCst);
+						if let Some(metrics) = metrics {
+							metrics.handle_packet_timed_out_while_pending(&packet);
+						}
 						// so we know this packet has timed out on the sink, we need to find the maximum
 						// consensus state height at which we can generate a non-membership proof of the
 						// packet for the sink's client on the source.
 						let proof_height =
 							if let Some(proof_height) = get_timeout_proof_height(
-								&**source,
-								&**sink,
+								source,
+								sink,
 								source_height,
 								sink_height,
 								sink_timestamp,
@@ -254,15 +304,16 @@ pub async fn query_ready_and_timed_out_packets(
 
 						// given this maximum height, has the connection delay been satisfied?
 						if !verify_delay_passed(
-							&**source,
-							&**sink,
+							source,
+							sink,
 							source_timestamp,
 							source_height,
 							sink_timestamp,
 							sink_height,
-							source_connection_end.delay_period(),
+							connection_delay,
 							proof_height,
 							VerifyDelayOn::Source,
+							&packet,
 						)
 							.await?
 						{
@@ -270,13 +321,19 @@ pub async fn query_ready_and_timed_out_packets(
 							return Ok(None)
 						}
 
+						let in_flight_key = InFlightKey::new(&packet, InFlightType::Timeout);
+						if source.common_state().in_flight_packets.contains(&in_flight_key) {
+							log::debug!(target: "hyperspace", "Skipping timeout for packet as it's already in flight: {:?}", packet);
+							return Ok(None)
+						}
+
 						// lets construct the timeout message to be sent to the source
 						let msg = construct_timeout_message(
-							&**source,
-							&**sink,
-							&sink_channel_end,
+							source,
+							sink,
+							sink_channel_end,
 							packet,
-							next_sequence_recv.next_sequence_receive,
+							next_sequence_receive,
 							proof_height,
 						)
 							.await?;
@@ -299,6 +356,12 @@ pub async fn query_ready_and_timed_out_packets(
 						return Ok(None)
 					}
 
+					// Timeouts are handled above, so skipped packets still get refunded
+					if is_packet_skipped(source, metrics, &packet) {
+						filtered_sequences.lock().unwrap().insert(u64::from(packet.sequence));
+						return Ok(None)
+					}
+
 					// Check if packet is ready to be sent to sink
 					// If sink does not have a client height that is equal to or greater than the packet
 					// creation height, we can't send it yet, packet_info.height should represent the packet
@@ -311,8 +374,8 @@ pub async fn query_ready_and_timed_out_packets(
 					}
 
 					let proof_height = if let Some(proof_height) = find_suitable_proof_height_for_client(
-						&**source,
-						&**sink,
+						source,
+						sink,
 						sink_height,
 						source.client_id(),
 						Height::new(latest_source_height_on_sink.revision_number, packet_height),
@@ -328,15 +391,16 @@ pub async fn query_ready_and_timed_out_packets(
 					};
 
 					if !verify_delay_passed(
-						&**source,
-						&**sink,
+						source,
+						sink,
 						source_timestamp,
 						source_height,
 						sink_timestamp,
 						sink_height,
-						source_connection_end.delay_period(),
+						connection_delay,
 						proof_height,
 						VerifyDelayOn::Sink,
+						&packet,
 					)
 						.await?
 					{
@@ -349,33 +413,54 @@ pub async fn query_ready_and_timed_out_packets(
 						return Ok(None)
 					}
 
-					let list = &source.common_state().skip_tokens_list;
-
-					let decoded_dara: PacketData = serde_json::from_str(&String::from_utf8_lossy(packet.data.as_ref())).map_err(|e| {
-						Error::Custom(format!(
-						"Failed to decode packet data for packet {:?}: {:?}",
-						packet, e
-						))
-					})?;
-
-					if list.iter().any(|skiped_denom| decoded_dara.token.denom.base_denom.as_str() == skiped_denom) {
-						log::info!(target: "hyperspace", "Skipping packet with ignored token: {:?}", packet);
+					let in_flight_key = InFlightKey::new(&packet, InFlightType::RecvPacket);
+					if sink.common_state().in_flight_packets.contains(&in_flight_key) {
+						log::debug!(target: "hyperspace", "Skipping packet as it's already in flight: {:?}", packet);
 						return Ok(None)
 					}
 
-					let msg = construct_recv_message(&**source, &**sink, packet, proof_height).await?;
-					Ok(Some(Right(msg)))
-				});
-			}
-		}
+					let sequence = u64::from(packet.sequence);
+					let msg = construct_recv_message(source, sink, packet, proof_height).await?;
+					Ok(Some(Right((sequence, msg))))
+				}
+			},
+		)
+		.await;
 
-		while let Some(result) = recv_packets_join_set.join_next().await {
-			let Some(either) = result?? else { continue };
+		let mut recv_messages = vec![];
+		for either in send_packets_msgs {
 			match either {
 				Left(msg) => timeout_messages.push(msg),
-				Right(msg) => messages.push(msg),
+				Right(msg) => recv_messages.push(msg),
 			}
 		}
+		// ordered channels only accept the packet with the next expected sequence, so the packets
+		// after a missing one have to wait for it
+		if sink_channel_end.ordering == Order::Ordered {
+			let in_flight_packets = &sink.common_state().in_flight_packets;
+			let (contiguous, missing) = contiguous_from_next_sequence(
+				&sink_channel_id,
+				next_sequence_receive,
+				|sequence| {
+					in_flight_packets.contains(&InFlightKey {
+						channel_id,
+						port_id: port_id.clone(),
+						sequence: sequence.into(),
+						kind: InFlightType::RecvPacket,
+					})
+				},
+				recv_messages,
+			);
+			report_blocked_ordered_channel(
+				metrics,
+				&channel_id,
+				&port_id,
+				missing,
+				&filtered_sequences.lock().unwrap(),
+			);
+			recv_messages = contiguous;
+		}
+		messages.extend(recv_messages.into_iter().map(|(_, msg)| msg));
 
 		let timeouts_count = timeout_packets_count.load(Ordering::SeqCst);
 		log::debug!(target: "hyperspace", "Found {timeouts_count} packets that have timed out");
@@ -394,37 +479,39 @@ pub async fn query_ready_and_timed_out_packets(
 		}
 
 		// query acknowledgements that are waiting for connection delay.
-		let acks = query_undelivered_acks(
+		let undelivered_acks = query_undelivered_acks(
 			source_height,
 			sink_height,
 			channel_id,
 			port_id.clone(),
-			&*source,
-			&*sink,
+			source,
+			sink,
 		)
-		.await?
-		.into_iter()
-		.take(max_packets_to_process)
-		.collect::<Vec<_>>();
+		.await?;
+		if let Some(metrics) = metrics {
+			metrics.handle_undelivered_acknowledgements(
+				&channel_id,
+				&port_id,
+				undelivered_acks.len(),
+			);
+		}
+		let acks = scope.select(undelivered_acks, max_packets_to_process);
 
 		let acknowledgements =
 			source.query_received_packets(channel_id, port_id.clone(), acks).await?;
 		log::trace!(target: "hyperspace", "Got acknowledgements for channel {:?}: {:?}", channel_id, acknowledgements);
-		let mut acknowledgements_join_set: JoinSet<Result<_, anyhow::Error>> = JoinSet::new();
 		sink.on_undelivered_sequences(!acknowledgements.is_empty(), UndeliveredType::Acks)
 			.await;
-		for acknowledgements in acknowledgements.chunks(PROCESS_PACKETS_BATCH_SIZE) {
-			for acknowledgement in acknowledgements.iter().cloned() {
-				let source_connection_end = source_connection_end.clone();
-				let source = source.clone();
-				let sink = sink.clone();
-				let duration1 = Duration::from_millis(
+		let ack_msgs = process_packets_concurrently(
+			acknowledgements,
+			max_concurrent_packet_proofs,
+			|acknowledgement| acknowledgement.sequence,
+			move |acknowledgement| {
+				let duration = Duration::from_millis(
 					rand::thread_rng().gen_range(1..source.rpc_call_delay().as_millis() as u64),
 				);
-				acknowledgements_join_set.spawn(async move {
-					sleep(duration1).await;
-					let source = &source;
-					let sink = &sink;
+				async move {
+					sleep(duration).await;
 					let packet = packet_info_to_packet(&acknowledgement);
 					let ack = if let Some(ack) = acknowledgement.ack {
 						ack
@@ -450,8 +537,8 @@ pub async fn query_ready_and_timed_out_packets(
 					log::trace!(target: "hyperspace", "sink_height: {:?}, latest_source_height_on_sink: {:?}, acknowledgement.height: {}", sink_height, latest_source_height_on_sink, ack_height);
 
 					let proof_height = if let Some(proof_height) = find_suitable_proof_height_for_client(
-						&**source,
-						&**sink,
+						source,
+						sink,
 						sink_height,
 						source.client_id(),
 						Height::new(latest_source_height_on_sink.revision_number, ack_height),
@@ -468,15 +555,16 @@ pub async fn query_ready_and_timed_out_packets(
 					};
 
 					if !verify_delay_passed(
-						&**source,
-						&**sink,
+						source,
+						sink,
 						source_timestamp,
 						source_height,
 						sink_timestamp,
 						sink_height,
-						source_connection_end.delay_period(),
+						connection_delay,
 						proof_height,
 						VerifyDelayOn::Sink,
+						&packet,
 					)
 						.await?
 					{
@@ -484,17 +572,97 @@ pub async fn query_ready_and_timed_out_packets(
 						return Ok(None)
 					}
 
-					let msg = construct_ack_message(&**source, &**sink, packet, ack, proof_height).await?;
+					let in_flight_key = InFlightKey::new(&packet, InFlightType::Acknowledgement);
+					if sink.common_state().in_flight_packets.contains(&in_flight_key) {
+						log::debug!(target: "hyperspace", "Skipping acknowledgement for packet as it's already in flight: {:?}", packet);
+						return Ok(None)
+					}
+
+					let msg = construct_ack_message(source, sink, packet, ack, proof_height).await?;
 					Ok(Some(msg))
-				});
-			}
-		}
+				}
+			},
+		)
+		.await;
+		messages.extend(ack_msgs);
+	}
+
+	Ok((messages, timeout_messages))
+}
 
-		while let Some(result) = acknowledgements_join_set.join_next().await {
-			let Some(msg) = result?? else { continue };
-			messages.push(msg)
+/// Builds messages for the packets running at most `concurrency` futures at a time. Packets that
+/// failed to be processed are logged and skipped, so that a single failing proof query doesn't
+/// drop the whole batch. The returned messages are sorted by the packet sequence regardless of the
+/// order the futures completed in, which is required for ordered channels.
+pub async fn process_packets_concurrently<P, T, F, Fut>(
+	packets: Vec<P>,
+	concurrency: usize,
+	sequence: impl Fn(&P) -> u64,
+	f: F,
+) -> Vec<T>
+where
+	F: Fn(P) -> Fut,
+	Fut: Future<Output = Result<Option<T>, anyhow::Error>>,
+{
+	let mut msgs = stream::iter(packets.into_iter().map(|packet| {
+		let sequence = sequence(&packet);
+		let fut = f(packet);
+		async move { (sequence, fut.await) }
+	}))
+	.buffer_unordered(concurrency.max(1))
+	.filter_map(|(sequence, result)| async move {
+		match result {
+			Ok(msg) => msg.map(|msg| (sequence, msg)),
+			Err(e) => {
+				log::error!(target: "hyperspace", "Failed to process packet with sequence {sequence}: {e:?}");
+				None
+			},
 		}
+	})
+	.collect::<Vec<_>>()
+	.await;
+	msgs.sort_by_key(|(sequence, _)| *sequence);
+	msgs.into_iter().map(|(_, msg)| msg).collect()
+}
+
+#[cfg(test)]
+pub mod tests {
+	use super::{process_packets_concurrently, PacketScope};
+	use std::time::{Duration, Instant};
+	use tokio::time::sleep;
+
+	#[tokio::test]
+	async fn process_packets_concurrently_preserves_order() {
+		let packets = (1..=50u64).collect::<Vec<_>>();
+		let started = Instant::now();
+		let msgs = process_packets_concurrently(
+			packets,
+			10,
+			|sequence| *sequence,
+			|sequence| async move {
+				// later sequences complete first, as if their proofs were fetched faster
+				sleep(Duration::from_millis(100 - sequence % 10 * 5)).await;
+				match sequence {
+					13 => Err(anyhow::anyhow!("query_proof failed")),
+					17 => Ok(None),
+					_ => Ok(Some(sequence)),
+				}
+			},
+		)
+		.await;
+		// 50 serial queries would take at least 2.5 seconds
+		assert!(started.elapsed() < Duration::from_secs(1));
+		let expected =
+			(1..=50u64).filter(|sequence| ![13, 17].contains(sequence)).collect::<Vec<_>>();
+		assert_eq!(msgs, expected);
 	}
 
-	Ok((messages, timeout_messages))
+	#[test]
+	fn sequences_are_selected_before_the_cap() {
+		let undelivered = (1..=10u64).collect::<Vec<_>>();
+		assert_eq!(PacketScope::Round.select(undelivered.clone(), 3), vec![1, 2, 3]);
+		// clearing a channel isn't capped, and only the selected sequences are proven
+		assert_eq!(PacketScope::All(&[]).select(undelivered.clone(), 3), undelivered);
+		assert_eq!(PacketScope::All(&[2, 9, 11]).select(undelivered, 1), vec![2, 9]);
+	}
 }
@@ -42,3 +42,50 @@ pub fn has_delay_elapsed(
 
 	Ok(true)
 }
+
+#[cfg(test)]
+pub mod tests {
+	use super::has_delay_elapsed;
+	use ibc::{
+		core::ics24_host::identifier::{ChannelId, PortId},
+		timestamp::Timestamp,
+		Height,
+	};
+	use primitives::CommonClientState;
+	use std::time::Duration;
+
+	#[test]
+	fn channel_delay_override_defers_packets() {
+		let channel_id = ChannelId::new(0);
+		let port_id = PortId::transfer();
+		let mut state = CommonClientState::default();
+		state
+			.channel_delay_overrides
+			.insert((channel_id, port_id.clone()), Duration::from_secs(600));
+
+		let connection_delay = Duration::from_secs(60);
+		let delay = state.channel_delay(channel_id, port_id.clone(), connection_delay);
+		assert_eq!(delay, Duration::from_secs(600));
+		// other channels and shorter overrides keep the connection delay
+		assert_eq!(
+			state.channel_delay(ChannelId::new(1), port_id.clone(), connection_delay),
+			connection_delay
+		);
+		assert_eq!(
+			state.channel_delay(channel_id, port_id, Duration::from_secs(3600)),
+			Duration::from_secs(3600)
+		);
+
+		let update_time = Timestamp::from_nanoseconds(1_000_000_000_000).unwrap();
+		let update_height = Height::new(0, 100);
+		let elapsed = |secs: u64, blocks: u64, delay: Duration| {
+			let now = (update_time + Duration::from_secs(secs)).unwrap();
+			has_delay_elapsed(now, update_height.add(blocks), update_time, update_height, delay, 0)
+				.unwrap()
+		};
+		// the connection delay has passed, but the overridden one hasn't
+		assert!(elapsed(120, 20, connection_delay));
+		assert!(!elapsed(120, 20, delay));
+		assert!(elapsed(600, 100, delay));
+	}
+}
@@ -14,6 +14,7 @@
 
 use crate::packets::connection_delay::has_delay_elapsed;
 use ibc::{
+	applications::transfer::packet::PacketData,
 	core::{
 		ics02_client::client_state::ClientState as ClientStateT,
 		ics04_channel::{
@@ -26,9 +27,7 @@ use ibc::{
 			packet::{Packet, TimeoutVariant},
 		},
 		ics23_commitment::commitment::CommitmentProofBytes,
-		ics24_host::path::{
-			AcksPath, ChannelEndsPath, CommitmentsPath, ReceiptsPath, SeqRecvsPath,
-		},
+		ics24_host::identifier::{ChannelId, PortId},
 	},
 	proofs::Proofs,
 	timestamp::Timestamp,
@@ -36,11 +35,172 @@ use ibc::{
 	Height,
 };
 use ibc_proto::google::protobuf::Any;
-use pallet_ibc::light_clients::AnyClientState;
-use primitives::{find_suitable_proof_height_for_client, Chain};
-use std::time::Duration;
+use metrics::handler::MetricsHandler;
+use pallet_ibc::light_clients::{AnyClientState, AnyConsensusState};
+pub use primitives::key_path::{get_key_path, KeyPathType};
+use primitives::{find_suitable_proof_height_for_client, packet_filter::FilterDecision, Chain};
+use std::{collections::HashSet, time::Duration};
 use tendermint_proto::Protobuf;
 
+/// Returns an identifier of the packet that stays the same across all the stages of its lifecycle
+/// (recv, ack and timeout) on both chains, so that the logs of a single packet can be followed
+/// end to end.
+pub fn packet_correlation_id(packet: &Packet) -> String {
+	format!("{}/{}/{}", packet.source_port, packet.source_channel, packet.sequence)
+}
+
+/// Returns `true` if the packet shouldn't be relayed from `source` because it doesn't match the
+/// packet filters, or a packet filter hook denies it. Each skipped packet is only logged and
+/// counted in the metrics once.
+pub fn is_packet_filtered(
+	source: &impl Chain,
+	metrics: Option<&MetricsHandler>,
+	packet: &Packet,
+) -> bool {
+	let common_state = source.common_state();
+	if common_state.should_relay_packet(packet) {
+		let reason = match common_state.packet_filter_hooks_decision(packet) {
+			FilterDecision::Allow => return false,
+			FilterDecision::Deny(reason) => reason,
+		};
+		if common_state.record_skipped_packet(packet) {
+			log::info!(
+				target: "hyperspace",
+				"Skipping packet {} (sequence {}) as it's denied by a packet filter hook: {reason}",
+				packet_correlation_id(packet), packet.sequence
+			);
+			if let Some(metrics) = metrics {
+				metrics.handle_denied_packet(reason);
+			}
+		}
+		return true
+	}
+	if common_state.record_skipped_packet(packet) {
+		log::debug!(
+			target: "hyperspace",
+			"Skipping packet {} (sequence {}) as it doesn't match the packet filters",
+			packet_correlation_id(packet), packet.sequence
+		);
+		if let Some(metrics) = metrics {
+			metrics.handle_filtered_packet(packet);
+		}
+	}
+	true
+}
+
+/// Returns `true` if the packet is never relayed from `source`, because it's filtered, see
+/// [`is_packet_filtered`], or it transfers a token of the source's `skip_tokens_list`. Packets
+/// whose data isn't ICS-20 don't transfer any token.
+pub fn is_packet_skipped(
+	source: &impl Chain,
+	metrics: Option<&MetricsHandler>,
+	packet: &Packet,
+) -> bool {
+	if is_packet_filtered(source, metrics, packet) {
+		return true
+	}
+	let common_state = source.common_state();
+	let has_skipped_token = !common_state.skip_tokens_list.is_empty() &&
+		serde_json::from_slice::<PacketData>(packet.data.as_ref()).map_or(false, |data| {
+			common_state
+				.skip_tokens_list
+				.iter()
+				.any(|denom| data.token.denom.base_denom.as_str() == denom)
+		});
+	if has_skipped_token && common_state.record_skipped_packet(packet) {
+		log::info!(
+			target: "hyperspace",
+			"Skipping packet {} (sequence {}) with an ignored token",
+			packet_correlation_id(packet), packet.sequence
+		);
+	}
+	has_skipped_token
+}
+
+/// Block times below this are considered misreported, since the approximate number of blocks
+/// produced during the packet lifetime would overshoot the actual timeout height by far.
+const MIN_EXPECTED_BLOCK_TIME: Duration = Duration::from_millis(1);
+
+/// Returns the sink height from which the search for the timeout proof height should start. That
+/// is the sink client height at packet creation offset by the approximate number of blocks
+/// produced during the packet lifetime, but never above the current sink height. If the sink
+/// reports a zero or sub-millisecond block time, the search starts from the client height itself.
+pub fn timeout_search_start_height(
+	client_height: u64,
+	packet_lifetime: Duration,
+	expected_block_time: Duration,
+	sink_height: u64,
+) -> u64 {
+	if expected_block_time < MIN_EXPECTED_BLOCK_TIME {
+		log::trace!(target: "hyperspace", "Expected block time {expected_block_time:?} is too small, searching for the timeout proof height from {client_height}");
+		return client_height.min(sink_height)
+	}
+	let packet_lifetime_blocks_on_sink =
+		calculate_block_delay(packet_lifetime, expected_block_time).saturating_sub(1);
+	client_height.saturating_add(packet_lifetime_blocks_on_sink).min(sink_height)
+}
+
+/// Returns the recv messages an ordered channel can accept in this round: the ones with
+/// contiguous sequences starting at the sink's `next_sequence_recv`. The sequences for which
+/// `is_in_flight` returns `true` don't break the run, since their messages were already submitted
+/// and are delivered first. The later messages are held back until the missing sequence is
+/// delivered, which is returned alongside. `msgs` are `(sequence, message)` pairs in any order.
+pub fn contiguous_from_next_sequence<T>(
+	channel_id: &ChannelId,
+	next_sequence_recv: u64,
+	is_in_flight: impl Fn(u64) -> bool,
+	mut msgs: Vec<(u64, T)>,
+) -> (Vec<(u64, T)>, Option<u64>) {
+	msgs.sort_by_key(|(sequence, _)| *sequence);
+	// sequences below the expected one were already received
+	msgs.retain(|(sequence, _)| *sequence >= next_sequence_recv);
+	let mut expected = next_sequence_recv;
+	let mut contiguous = 0;
+	for (sequence, _) in &msgs {
+		while expected < *sequence && is_in_flight(expected) {
+			expected += 1;
+		}
+		if *sequence != expected {
+			break
+		}
+		expected += 1;
+		contiguous += 1;
+	}
+	if contiguous == msgs.len() {
+		return (msgs, None)
+	}
+	log::warn!(
+		target: "hyperspace",
+		"Ordered channel {channel_id} is missing packet with sequence {expected}, holding back {} packets from sequence {}",
+		msgs.len() - contiguous,
+		msgs[contiguous].0
+	);
+	msgs.truncate(contiguous);
+	(msgs, Some(expected))
+}
+
+/// Reports the ordered channel `channel_id`/`port_id` as blocked if its `missing` sequence (see
+/// [`contiguous_from_next_sequence`]) is one of the `filtered` sequences: the packet is never
+/// relayed, so no later packet of the channel can be delivered until the filters change.
+pub fn report_blocked_ordered_channel(
+	metrics: Option<&MetricsHandler>,
+	channel_id: &ChannelId,
+	port_id: &PortId,
+	missing: Option<u64>,
+	filtered: &HashSet<u64>,
+) {
+	let blocked_at = missing.filter(|sequence| filtered.contains(sequence));
+	if let Some(sequence) = blocked_at {
+		log::error!(
+			target: "hyperspace",
+			"Ordered channel {channel_id}/{port_id} is blocked: the packet with sequence {sequence} is filtered out, so none of the following packets can be delivered"
+		);
+	}
+	if let Some(metrics) = metrics {
+		metrics.handle_blocked_ordered_channel(channel_id, port_id, blocked_at.unwrap_or_default());
+	}
+}
+
 #[allow(clippy::too_many_arguments)]
 pub async fn get_timeout_proof_height(
 	source: &impl Chain,
@@ -52,7 +212,11 @@ pub async fn get_timeout_proof_height(
 	packet: &Packet,
 	packet_creation_height: u64,
 ) -> Option<Height> {
-	let timeout_variant = Packet::timeout_variant(packet, &sink_timestamp, sink_height).unwrap();
+	let Some(timeout_variant) = Packet::timeout_variant(packet, &sink_timestamp, sink_height)
+	else {
+		log::trace!(target: "hyperspace", "get_timeout_proof_height: packet {packet:?} has not timed out on {}", sink.name());
+		return None
+	};
 	log::trace!(target: "hyperspace", "get_timeout_proof_height: {}->{}, timeout_variant={:?}, source_height={}, sink_height={}, sink_timestamp={}, latest_client_height_on_source={}, packet_creation_height={}, packet={:?}",
 		source.name(), sink.name(), timeout_variant, source_height, sink_height, sink_timestamp, latest_client_height_on_source, packet_creation_height, packet);
 
@@ -90,8 +254,12 @@ pub async fn get_timeout_proof_height(
 			let period =
 				packet.timeout_timestamp.nanoseconds().saturating_sub(timestamp_at_creation);
 			let period = Duration::from_nanos(period);
-			let start_height = height.revision_height +
-				calculate_block_delay(period, sink.expected_block_time()).saturating_sub(1);
+			let start_height = timeout_search_start_height(
+				height.revision_height,
+				period,
+				sink.expected_block_time(),
+				sink_height.revision_height,
+			);
 			let start_height = Height::new(sink_height.revision_number, start_height);
 			find_suitable_proof_height_for_client(
 				sink,
@@ -124,8 +292,12 @@ pub async fn get_timeout_proof_height(
 			let period =
 				packet.timeout_timestamp.nanoseconds().saturating_sub(timestamp_at_creation);
 			let period = Duration::from_nanos(period);
-			let start_height = height.revision_height +
-				calculate_block_delay(period, sink.expected_block_time()).saturating_sub(1);
+			let start_height = timeout_search_start_height(
+				height.revision_height,
+				period,
+				sink.expected_block_time(),
+				sink_height.revision_height,
+			);
 			let start_height = if start_height < packet.timeout_height.revision_height {
 				packet.timeout_height
 			} else {
@@ -162,8 +334,10 @@ pub async fn verify_delay_passed(
 	connection_delay: Duration,
 	proof_height: Height,
 	verify_delay_on: VerifyDelayOn,
+	packet: &Packet,
 ) -> Result<bool, anyhow::Error> {
-	log::trace!(target: "hyperspace", "Verifying delay passed for source: {source_height}, {source_timestamp}, sink: {sink_height}, {sink_timestamp}, connection delay: {}, proof height: {proof_height}, verify delay on: {verify_delay_on:?}", connection_delay.as_secs());
+	let correlation_id = packet_correlation_id(packet);
+	log::trace!(target: "hyperspace", "[{correlation_id}] Verifying delay passed for source: {source_height}, {source_timestamp}, sink: {sink_height}, {sink_timestamp}, connection delay: {}, proof height: {proof_height}, verify delay on: {verify_delay_on:?}", connection_delay.as_secs());
 	match verify_delay_on {
 		VerifyDelayOn::Source => {
 			let actual_proof_height = sink.get_proof_height(proof_height).await;
@@ -182,30 +356,41 @@ pub async fn verify_delay_passed(
 					block_delay,
 				)
 			} else {
-				log::trace!(target: "hyperspace", "Failed to get client update time and height for source client for height {}", actual_proof_height);
+				log::trace!(target: "hyperspace", "[{correlation_id}] Failed to get client update time and height for source client for height {}", actual_proof_height);
 				Ok(false)
 			}
 		},
 		VerifyDelayOn::Sink => {
 			let actual_proof_height = source.get_proof_height(proof_height).await;
-			log::info!(
-				"Checking proof height on {} as {}:{}",
+			log::trace!(
+				target: "hyperspace",
+				"[{correlation_id}] Checking proof height on {} as {}:{}",
 				sink.name(),
 				proof_height,
 				actual_proof_height
 			);
-			let _cs = sink
+			let consensus_state = match sink
 				.query_client_consensus(sink_height, source.client_id(), actual_proof_height)
 				.await
-				.unwrap()
-				.consensus_state
-				.unwrap_or_else(|| {
-					panic!(
-						"query_client_consensus for {} at height {} is not found",
-						source.client_id(),
-						actual_proof_height
-					)
-				});
+			{
+				Ok(response) => response.consensus_state,
+				Err(e) => {
+					log::warn!(target: "hyperspace", "[{correlation_id}] Failed to query consensus state for {} at height {} on {}: {:?}", source.client_id(), actual_proof_height, sink.name(), e);
+					return Ok(false)
+				},
+			};
+			let Some(consensus_state) = consensus_state else {
+				log::warn!(target: "hyperspace", "[{correlation_id}] Consensus state for {} at height {} is not found on {}", source.client_id(), actual_proof_height, sink.name());
+				return Ok(false)
+			};
+			AnyConsensusState::try_from(consensus_state).map_err(|e| {
+				anyhow::anyhow!(
+					"Failed to decode consensus state for {} at height {}: {:?}",
+					source.client_id(),
+					actual_proof_height,
+					e
+				)
+			})?;
 			if let Ok((sink_client_update_height, sink_client_update_time)) = sink
 				.query_client_update_time_and_height(source.client_id(), actual_proof_height)
 				.await
@@ -221,13 +406,34 @@ pub async fn verify_delay_passed(
 					block_delay,
 				)
 			} else {
-				log::trace!(target: "hyperspace", "Failed to get client update time and height for sink client for height {}", actual_proof_height);
+				log::trace!(target: "hyperspace", "[{correlation_id}] Failed to get client update time and height for sink client for height {}", actual_proof_height);
 				Ok(false)
 			}
 		},
 	}
 }
 
+/// Splits the proofs queried for a timeout on close message into the unreceived proof and the
+/// closed channel proof, in the order the keys were queried in.
+pub fn timeout_on_close_proofs(
+	proofs: Vec<Vec<u8>>,
+) -> Result<(CommitmentProofBytes, CommitmentProofBytes), anyhow::Error> {
+	let [proof_unreceived, proof_closed]: [Vec<u8>; 2] =
+		proofs.try_into().map_err(|proofs: Vec<Vec<u8>>| {
+			anyhow::anyhow!(
+				"Expected two proofs for timeout on close message, got {}",
+				proofs.len()
+			)
+		})?;
+	if proof_unreceived.is_empty() {
+		return Err(anyhow::anyhow!("Packet receipt proof for timeout on close message is empty"))
+	}
+	if proof_closed.is_empty() {
+		return Err(anyhow::anyhow!("Channel proof for timeout on close message is empty"))
+	}
+	Ok((proof_unreceived.try_into()?, proof_closed.try_into()?))
+}
+
 pub async fn construct_timeout_message(
 	source: &impl Chain,
 	sink: &impl Chain,
@@ -236,20 +442,27 @@ pub async fn construct_timeout_message(
 	next_sequence_recv: u64,
 	proof_height: Height,
 ) -> Result<Any, anyhow::Error> {
+	let correlation_id = packet_correlation_id(&packet);
+	log::debug!(target: "hyperspace", "[{correlation_id}] Constructing timeout message for {} at proof height {proof_height}", source.name());
+	let signer = source.validate_signer()?;
 	let path_type = if sink_channel_end.ordering == Order::Ordered {
 		KeyPathType::SeqRecv
 	} else {
 		KeyPathType::ReceiptPath
 	};
-	let key = get_key_path(path_type, &packet).into_bytes();
+	let key = sink.full_key_path(path_type, &packet);
+	// the proofs are queried at `proof_height`, but the height they are verified at may be shifted
+	// by the chain, so it's computed once for all of them
+	let actual_proof_height = sink.get_proof_height(proof_height).await;
+	log::debug!(target: "hyperspace", "[{correlation_id}] actual_proof_height={actual_proof_height}");
 
-	let proof_unreceived = sink.query_proof(proof_height, vec![key]).await?;
-	let proof_unreceived = CommitmentProofBytes::try_from(proof_unreceived)?;
 	let msg = if sink_channel_end.state == State::Closed {
-		let channel_key = get_key_path(KeyPathType::ChannelPath, &packet).into_bytes();
-		let proof_closed = sink.query_proof(proof_height, vec![channel_key]).await?;
-		let proof_closed = CommitmentProofBytes::try_from(proof_closed)?;
-		let actual_proof_height = sink.get_proof_height(proof_height).await;
+		let channel_key = sink.full_key_path(KeyPathType::ChannelPath, &packet);
+		// both proofs are taken at `proof_height`, though not necessarily in a single request, see
+		// [`primitives::IbcProvider::query_proofs`]
+		let proofs = sink.query_proofs(proof_height, vec![key, channel_key]).await?;
+		let (proof_unreceived, proof_closed) = timeout_on_close_proofs(proofs)
+			.map_err(|e| anyhow::anyhow!("[{correlation_id}] {e} at height {proof_height}"))?;
 		let msg = MsgTimeoutOnClose {
 			packet,
 			next_sequence_recv: next_sequence_recv.into(),
@@ -260,18 +473,18 @@ pub async fn construct_timeout_message(
 				Some(proof_closed),
 				actual_proof_height,
 			)?,
-			signer: source.account_id(),
+			signer,
 		};
 		let value = msg.encode_vec()?;
 		Any { value, type_url: msg.type_url() }
 	} else {
-		let actual_proof_height = sink.get_proof_height(proof_height).await;
-		log::debug!(target: "hyperspace", "actual_proof_height={actual_proof_height}");
+		let proof_unreceived = sink.query_proof(proof_height, vec![key]).await?;
+		let proof_unreceived = CommitmentProofBytes::try_from(proof_unreceived)?;
 		let msg = MsgTimeout {
 			packet,
 			next_sequence_recv: next_sequence_recv.into(),
 			proofs: Proofs::new(proof_unreceived, None, None, None, actual_proof_height)?,
-			signer: source.account_id(),
+			signer,
 		};
 		let value = msg.encode_vec()?;
 		Any { value, type_url: msg.type_url() }
@@ -285,14 +498,17 @@ pub async fn construct_recv_message(
 	packet: Packet,
 	proof_height: Height,
 ) -> Result<Any, anyhow::Error> {
-	let key = get_key_path(KeyPathType::CommitmentPath, &packet).into_bytes();
+	let correlation_id = packet_correlation_id(&packet);
+	log::debug!(target: "hyperspace", "[{correlation_id}] Constructing recv message for {} at proof height {proof_height}", sink.name());
+	let signer = sink.validate_signer()?;
+	let key = source.full_key_path(KeyPathType::CommitmentPath, &packet);
 	let proof = source.query_proof(proof_height, vec![key]).await?;
 	let commitment_proof = CommitmentProofBytes::try_from(proof)?;
 	let actual_proof_height = source.get_proof_height(proof_height).await;
 	let msg = MsgRecvPacket {
 		packet,
 		proofs: Proofs::new(commitment_proof, None, None, None, actual_proof_height)?,
-		signer: sink.account_id(),
+		signer,
 	};
 	let value = msg.encode_vec()?;
 	let msg = Any { value, type_url: msg.type_url() };
@@ -306,70 +522,181 @@ pub async fn construct_ack_message(
 	ack: Vec<u8>,
 	proof_height: Height,
 ) -> Result<Any, anyhow::Error> {
-	let key = get_key_path(KeyPathType::AcksPath, &packet);
-	log::debug!(target: "hyperspace", "query proof for acks path: {:?}", key);
-	let proof = source.query_proof(proof_height, vec![key.into_bytes()]).await?;
+	let correlation_id = packet_correlation_id(&packet);
+	log::debug!(target: "hyperspace", "[{correlation_id}] Constructing ack message for {} at proof height {proof_height}", sink.name());
+	let signer = sink.validate_signer()?;
+	let key = source.full_key_path(KeyPathType::AcksPath, &packet);
+	log::debug!(target: "hyperspace", "[{correlation_id}] query proof for acks path: {:?}", String::from_utf8_lossy(&key));
+	let proof = source.query_proof(proof_height, vec![key]).await?;
 	let commitment_proof = CommitmentProofBytes::try_from(proof)?;
 	let actual_proof_height = source.get_proof_height(proof_height).await;
 	let msg = MsgAcknowledgement {
 		packet,
 		proofs: Proofs::new(commitment_proof, None, None, None, actual_proof_height)?,
 		acknowledgement: ack.into(),
-		signer: sink.account_id(),
+		signer,
 	};
 	let value = msg.encode_vec()?;
 	let msg = Any { value, type_url: msg.type_url() };
 	Ok(msg)
 }
 
-pub enum KeyPathType {
-	SeqRecv,
-	ReceiptPath,
-	CommitmentPath,
-	AcksPath,
-	ChannelPath,
-}
-
-pub fn get_key_path(key_path_type: KeyPathType, packet: &Packet) -> String {
-	match key_path_type {
-		KeyPathType::SeqRecv => {
-			format!("{}", SeqRecvsPath(packet.destination_port.clone(), packet.destination_channel))
-		},
-		KeyPathType::ReceiptPath => {
-			format!(
-				"{}",
-				ReceiptsPath {
-					port_id: packet.destination_port.clone(),
-					channel_id: packet.destination_channel,
-					sequence: packet.sequence
-				}
-			)
-		},
-		KeyPathType::CommitmentPath => {
-			format!(
-				"{}",
-				CommitmentsPath {
-					port_id: packet.source_port.clone(),
-					channel_id: packet.source_channel,
-					sequence: packet.sequence
-				}
-			)
-		},
-		KeyPathType::AcksPath => {
-			format!(
-				"{}",
-				AcksPath {
-					port_id: packet.destination_port.clone(),
-					channel_id: packet.destination_channel,
-					sequence: packet.sequence
-				}
-			)
+#[cfg(test)]
+pub mod tests {
+	use super::{
+		construct_timeout_message, contiguous_from_next_sequence, is_packet_skipped,
+		timeout_on_close_proofs, timeout_search_start_height,
+	};
+	use ibc::{
+		core::{
+			ics04_channel::{
+				channel::{ChannelEnd, State},
+				msgs::{
+					timeout::{MsgTimeout, TYPE_URL as TIMEOUT_TYPE_URL},
+					timeout_on_close::{MsgTimeoutOnClose, TYPE_URL as TIMEOUT_ON_CLOSE_TYPE_URL},
+				},
+				packet::Packet,
+			},
+			ics24_host::identifier::{ChannelId, ClientId},
 		},
-		KeyPathType::ChannelPath => {
-			format!(
-				"{}",
-				ChannelEndsPath(packet.destination_port.clone(), packet.destination_channel)
+		Height,
+	};
+	use primitives::mock::MockChain;
+	use std::time::Duration;
+	use tendermint_proto::Protobuf;
+
+	#[test]
+	fn timeout_search_start_height_handles_bad_block_times() {
+		let lifetime = Duration::from_secs(60);
+		// regular block time: client height offset by the number of blocks in the lifetime
+		assert_eq!(timeout_search_start_height(100, lifetime, Duration::from_secs(6), 1000), 109);
+		// zero and sub-millisecond block times fall back to the client height
+		assert_eq!(timeout_search_start_height(100, lifetime, Duration::ZERO, 1000), 100);
+		assert_eq!(timeout_search_start_height(100, lifetime, Duration::from_nanos(10), 1000), 100);
+		// huge block time doesn't move the start height
+		assert_eq!(timeout_search_start_height(100, lifetime, Duration::MAX, 1000), 100);
+		// timeout in the past (relative to the creation timestamp)
+		assert_eq!(
+			timeout_search_start_height(100, Duration::ZERO, Duration::from_secs(6), 1000),
+			100
+		);
+		// never above the current sink height
+		assert_eq!(
+			timeout_search_start_height(100, lifetime * 1000, Duration::from_secs(1), 1000),
+			1000
+		);
+		assert_eq!(timeout_search_start_height(2000, lifetime, Duration::ZERO, 1000), 1000);
+	}
+
+	fn sequences(next_sequence_recv: u64, pending: &[u64]) -> Vec<u64> {
+		sequences_with_in_flight(next_sequence_recv, &[], pending).0
+	}
+
+	fn sequences_with_in_flight(
+		next_sequence_recv: u64,
+		in_flight: &[u64],
+		pending: &[u64],
+	) -> (Vec<u64>, Option<u64>) {
+		let msgs = pending.iter().map(|sequence| (*sequence, ())).collect();
+		let (msgs, missing) = contiguous_from_next_sequence(
+			&ChannelId::new(0),
+			next_sequence_recv,
+			|sequence| in_flight.contains(&sequence),
+			msgs,
+		);
+		(msgs.into_iter().map(|(sequence, _)| sequence).collect(), missing)
+	}
+
+	#[test]
+	fn ordered_channel_relays_contiguous_sequences() {
+		assert_eq!(sequences(3, &[5, 3, 4]), vec![3, 4, 5]);
+		// already received sequences are dropped
+		assert_eq!(sequences(3, &[1, 2, 3, 4]), vec![3, 4]);
+		assert_eq!(sequences(3, &[]), Vec::<u64>::new());
+	}
+
+	#[test]
+	fn ordered_channel_holds_back_sequences_after_gap() {
+		// sequence 5 is missing, so 6 and 7 have to wait
+		assert_eq!(sequences(3, &[3, 4, 6, 7]), vec![3, 4]);
+		// the next expected sequence is missing, nothing can be relayed
+		assert_eq!(sequences(3, &[4, 5]), Vec::<u64>::new());
+		assert_eq!(sequences_with_in_flight(3, &[], &[3, 4, 6]), (vec![3, 4], Some(5)));
+	}
+
+	#[test]
+	fn ordered_channel_continues_past_in_flight_sequences() {
+		// 3 was submitted in an earlier round and is delivered before 4 and 5
+		assert_eq!(sequences_with_in_flight(3, &[3], &[4, 5]), (vec![4, 5], None));
+		assert_eq!(sequences_with_in_flight(3, &[4, 5], &[3, 6]), (vec![3, 6], None));
+		// but a sequence that is neither pending nor in flight still holds back the later ones
+		assert_eq!(sequences_with_in_flight(3, &[3], &[5, 6]), (vec![], Some(4)));
+	}
+
+	#[test]
+	fn timeout_on_close_proofs_are_checked() {
+		let (proof_unreceived, proof_closed) =
+			timeout_on_close_proofs(vec![vec![1], vec![2]]).unwrap();
+		assert_eq!(Vec::<u8>::from(proof_unreceived), vec![1]);
+		assert_eq!(Vec::<u8>::from(proof_closed), vec![2]);
+
+		let err = timeout_on_close_proofs(vec![vec![1], vec![]]).unwrap_err();
+		assert!(err.to_string().contains("Channel proof"));
+		assert!(timeout_on_close_proofs(vec![vec![], vec![2]]).is_err());
+		assert!(timeout_on_close_proofs(vec![vec![1]]).is_err());
+	}
+
+	#[test]
+	fn packets_with_skipped_tokens_are_skipped() {
+		let mut source = MockChain::new("source", ClientId::new("07-tendermint", 0).unwrap());
+		source.common_state.skip_tokens_list = vec!["uatom".to_string()];
+		let packet = |sequence: u64, data: &str| Packet {
+			sequence: sequence.into(),
+			data: data.as_bytes().to_vec(),
+			..Default::default()
+		};
+		let transfer = |denom: &str| {
+			format!(r#"{{"denom":"{denom}","amount":"1","sender":"alice","receiver":"bob"}}"#)
+		};
+
+		assert!(is_packet_skipped(&source, None, &packet(1, &transfer("uatom"))));
+		assert!(is_packet_skipped(
+			&source,
+			None,
+			&packet(2, &transfer("transfer/channel-0/uatom"))
+		));
+		assert!(!is_packet_skipped(&source, None, &packet(3, &transfer("stake"))));
+		// the data of other applications doesn't carry a token
+		assert!(!is_packet_skipped(&source, None, &packet(4, "ping")));
+	}
+
+	#[tokio::test]
+	async fn timeout_proofs_are_verified_at_the_proof_height_of_the_sink() {
+		let source = MockChain::new("source", ClientId::new("07-tendermint", 0).unwrap());
+		let sink = MockChain::new("sink", ClientId::new("07-tendermint", 1).unwrap());
+		// the proofs of the sink are verified at the next height
+		sink.state().proof_height_offset = 1;
+		let packet = Packet { sequence: 1.into(), data: vec![1], ..Default::default() };
+
+		for state in [State::Open, State::Closed] {
+			let sink_channel_end = ChannelEnd { state, ..Default::default() };
+			let msg = construct_timeout_message(
+				&source,
+				&sink,
+				&sink_channel_end,
+				packet.clone(),
+				1,
+				Height::new(0, 10),
 			)
-		},
+			.await
+			.unwrap();
+			let proof_height = match msg.type_url.as_str() {
+				TIMEOUT_TYPE_URL => MsgTimeout::decode_vec(&msg.value).unwrap().proofs.height(),
+				TIMEOUT_ON_CLOSE_TYPE_URL =>
+					MsgTimeoutOnClose::decode_vec(&msg.value).unwrap().proofs.height(),
+				type_url => panic!("Unexpected timeout message {type_url}"),
+			};
+			assert_eq!(proof_height, Height::new(0, 11), "{state:?}");
+		}
 	}
 }
@@ -12,15 +12,207 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use ibc_proto::google::protobuf::Any;
+use anyhow::anyhow;
+use futures::StreamExt;
+use ibc::{
+	core::{
+		ics02_client::msgs::update_client::MsgUpdateAnyClient,
+		ics24_host::identifier::{ChannelId, PortId},
+	},
+	Height,
+};
+use ibc_proto::{google::protobuf::Any, ibc::core::channel::v1::Order};
 use metrics::handler::MetricsHandler;
-use primitives::Chain;
+use primitives::{
+	in_flight::{packet_of_message, InFlightType},
+	mock::LocalClientTypes,
+	Chain,
+};
+use std::{collections::HashSet, future::Future, time::Duration};
+use tendermint_proto::Protobuf;
 
-/// This sends messages to the sink chain in a gas-aware manner.
+const UPDATE_CLIENT_TYPE_URL: &str = "/ibc.core.client.v1.MsgUpdateClient";
+
+/// Delay before the first retry of a failed submission, doubled after every retry.
+const SUBMISSION_RETRY_BACKOFF: Duration = Duration::from_secs(1);
+
+/// This sends messages to the sink chain in a gas-aware manner. Batches exceeding the sink's
+/// `max_messages_per_batch` or `max_batch_bytes` are split into sub-batches first, which are
+/// submitted sequentially, at most `max_txs_per_block` of them per sink block. A failed sub-batch
+/// doesn't prevent the following ones from being submitted, except for the messages of the ordered
+/// channels it had messages of, which would fail out of order. If the failed sub-batch carried the
+/// client update, the following ones are skipped, since their proofs can't be verified without it.
+/// The first error is returned after the sub-batches were tried. The messages of the submitted
+/// sub-batches are marked as in flight.
+///
+/// In dry-run mode the batch is recorded instead of being submitted, see
+/// [`primitives::dry_run::DryRun`].
 pub async fn flush_message_batch(
 	msgs: Vec<Any>,
 	metrics: Option<&MetricsHandler>,
 	sink: &impl Chain,
+) -> Result<(), anyhow::Error> {
+	let common_state = sink.common_state();
+	if let Some(dry_run) = &common_state.dry_run {
+		dry_run.record(sink.name(), &msgs)?;
+		// so that the same messages aren't recorded again every round
+		common_state.in_flight_packets.insert_messages(&msgs);
+		return Ok(())
+	}
+	let batches = split_message_batch(
+		msgs,
+		common_state.max_messages_per_batch,
+		common_state.max_batch_bytes,
+	);
+	if batches.len() > 1 {
+		log::info!(target: "hyperspace", "Split outgoing messages for {} into {} sub-batches", sink.name(), batches.len());
+	}
+	let batch_count = batches.len();
+	let mut result = Ok(());
+	let mut blocked_channels = HashSet::new();
+	for (i, mut batch) in batches.into_iter().enumerate() {
+		if !blocked_channels.is_empty() {
+			let len = batch.len();
+			batch.retain(|msg| {
+				sink_channel(msg).map_or(true, |channel| !blocked_channels.contains(&channel))
+			});
+			if batch.len() < len {
+				log::warn!(target: "hyperspace", "Skipping {} messages to {} of ordered channels with a failed sub-batch", len - batch.len(), sink.name());
+				if batch.iter().all(|msg| msg.type_url == UPDATE_CLIENT_TYPE_URL) {
+					continue
+				}
+			}
+		}
+		if is_block_full(i, common_state.max_txs_per_block) {
+			log::debug!(target: "hyperspace", "Submitted {i} sub-batches to {}, waiting for the next block", sink.name());
+			if let Err(e) = wait_for_next_block(sink).await {
+				log::warn!(target: "hyperspace", "Failed to wait for the next block of {}: {e:?}", sink.name());
+			}
+		}
+		let channels = batch.iter().filter_map(sink_channel).collect::<HashSet<_>>();
+		let has_client_update = batch.iter().any(|msg| msg.type_url == UPDATE_CLIENT_TYPE_URL);
+		if let Err(e) = flush_weighted_message_batch(batch, metrics, sink).await {
+			log::error!(target: "hyperspace", "Failed to submit sub-batch to {}: {e:?}", sink.name());
+			if result.is_ok() {
+				result = Err(e);
+			}
+			// the proofs of the following sub-batches can't be verified without the client update,
+			// so submitting them would only burn fees
+			if has_client_update {
+				log::warn!(target: "hyperspace", "Skipping the remaining {} sub-batches to {} after the client update failed", batch_count - i - 1, sink.name());
+				break
+			}
+			blocked_channels.extend(ordered_channels(channels, sink).await);
+		}
+	}
+	result
+}
+
+/// Returns `true` if the sub-batches submitted so far filled the current block.
+fn is_block_full(submitted: usize, max_txs_per_block: Option<usize>) -> bool {
+	match max_txs_per_block {
+		Some(max_txs_per_block) if max_txs_per_block > 0 =>
+			submitted > 0 && submitted % max_txs_per_block == 0,
+		_ => false,
+	}
+}
+
+/// Waits until the sink finalizes a block on top of its current latest one.
+async fn wait_for_next_block(sink: &impl Chain) -> Result<(), anyhow::Error> {
+	let (height, _) = sink.latest_height_and_timestamp().await?;
+	let mut finality = sink.finality_notifications().await?;
+	while let Some(finality_event) = finality.next().await {
+		match sink.finality_event_height(&finality_event).await {
+			Some(finalized_height) if finalized_height <= height => continue,
+			// without a height every notification is for a new block
+			_ => return Ok(()),
+		}
+	}
+	Err(anyhow!("Finality notifications of {} ended", sink.name()))
+}
+
+/// Returns the channel of the sink that handles the packet message, or `None` if it's not a
+/// packet message.
+fn sink_channel(msg: &Any) -> Option<(ChannelId, PortId)> {
+	let (packet, kind) = packet_of_message(msg)?;
+	match kind {
+		InFlightType::RecvPacket => Some((packet.destination_channel, packet.destination_port)),
+		InFlightType::Acknowledgement | InFlightType::Timeout =>
+			Some((packet.source_channel, packet.source_port)),
+	}
+}
+
+/// Returns the ordered ones of the sink's channels. Channels whose ordering can't be queried are
+/// considered ordered.
+async fn ordered_channels(
+	channels: HashSet<(ChannelId, PortId)>,
+	sink: &impl Chain,
+) -> HashSet<(ChannelId, PortId)> {
+	let height = match sink.latest_height_and_timestamp().await {
+		Ok((height, _)) => height,
+		Err(e) => {
+			log::warn!(target: "hyperspace", "Failed to query the latest height of {}: {e:?}", sink.name());
+			return channels
+		},
+	};
+	let mut ordered_channels = HashSet::new();
+	for (channel_id, port_id) in channels {
+		let is_ordered = match sink.query_channel_end(height, channel_id, port_id.clone()).await {
+			Ok(response) => response
+				.channel
+				.map_or(true, |channel| channel.ordering == Order::Ordered as i32),
+			Err(e) => {
+				log::warn!(target: "hyperspace", "Failed to query channel {channel_id}/{port_id} on {}: {e:?}", sink.name());
+				true
+			},
+		};
+		if is_ordered {
+			ordered_channels.insert((channel_id, port_id));
+		}
+	}
+	ordered_channels
+}
+
+/// Splits the messages into sub-batches with at most `max_messages` messages and `max_bytes`
+/// total size each. Client update messages are moved to the start of the first sub-batch and count
+/// toward its limits. The following sub-batches are submitted after it, so their proofs are
+/// verified against the consensus states it creates. The order of the other messages is preserved.
+pub fn split_message_batch(
+	msgs: Vec<Any>,
+	max_messages: Option<usize>,
+	max_bytes: Option<usize>,
+) -> Vec<Vec<Any>> {
+	let max_messages = max_messages.unwrap_or(usize::MAX);
+	let max_bytes = max_bytes.unwrap_or(usize::MAX);
+	let total_bytes = msgs.iter().map(|msg| msg.value.len()).sum::<usize>();
+	if msgs.len() <= max_messages && total_bytes <= max_bytes {
+		return vec![msgs]
+	}
+
+	let (updates, other_msgs): (Vec<_>, Vec<_>) =
+		msgs.into_iter().partition(|msg| msg.type_url == UPDATE_CLIENT_TYPE_URL);
+
+	let mut batches = vec![];
+	let mut batch = vec![];
+	let mut batch_bytes = 0;
+	for msg in updates.into_iter().chain(other_msgs) {
+		if !batch.is_empty() &&
+			(batch.len() + 1 > max_messages || batch_bytes + msg.value.len() > max_bytes)
+		{
+			batches.push(std::mem::take(&mut batch));
+			batch_bytes = 0;
+		}
+		batch_bytes += msg.value.len();
+		batch.push(msg);
+	}
+	batches.push(batch);
+	batches
+}
+
+async fn flush_weighted_message_batch(
+	msgs: Vec<Any>,
+	metrics: Option<&MetricsHandler>,
+	sink: &impl Chain,
 ) -> Result<(), anyhow::Error> {
 	let block_max_weight = sink.block_max_weight();
 	let batch_weight = sink.estimate_weight(msgs.clone()).await?;
@@ -32,7 +224,7 @@ pub async fn flush_message_batch(
 	log::debug!(target: "hyperspace", "Outgoing messages weight: {} block max weight: {}", batch_weight, block_max_weight);
 	let ratio = (batch_weight / block_max_weight) as usize;
 	if ratio == 0 {
-		sink.submit(msgs).await?;
+		submit_with_retries(msgs, metrics, sink).await?;
 		return Ok(())
 	}
 
@@ -53,8 +245,436 @@ pub async fn flush_message_batch(
 	// TODO: return number of failed messages and record it to metrics
 	for batch in msgs.chunks(chunk_size) {
 		// send out batches.
-		sink.submit(batch.to_vec()).await?;
+		submit_with_retries(batch.to_vec(), metrics, sink).await?;
 	}
 
 	Ok(())
 }
+
+/// Submits the messages to the sink, retrying the errors the sink classifies as retryable (see
+/// [`Chain::submission_error_class`]) at most `max_submission_retries` times. Client updates that
+/// became stale are removed from the last attempt, see [`without_stale_client_updates`]. Only one
+/// submission to the sink is in progress at a time, see `submission_lock`. The messages are skipped
+/// if their estimated fee exceeds the sink's `max_fee_per_batch`, so they're submitted again in a
+/// later round. Failed submissions are recorded in the metrics by their class.
+async fn submit_with_retries(
+	msgs: Vec<Any>,
+	metrics: Option<&MetricsHandler>,
+	sink: &impl Chain,
+) -> Result<(), anyhow::Error> {
+	if !is_fee_acceptable(&msgs, sink).await {
+		return Ok(())
+	}
+	let _submission = sink.common_state().submission_lock.lock().await;
+	let max_retries = sink.common_state().max_submission_retries;
+	retry_with_backoff(
+		max_retries,
+		SUBMISSION_RETRY_BACKOFF,
+		|e| sink.submission_error_class(e).is_retryable(),
+		|e| {
+			log::warn!(target: "hyperspace", "Failed to submit messages to {}, retrying: {e:?}", sink.name());
+			if let Some(metrics) = metrics {
+				metrics.handle_submission_retry(sink.name());
+			}
+		},
+		|retry| {
+			let msgs = msgs.clone();
+			async move {
+				if retry > 0 && retry == max_retries {
+					sink.submit(without_stale_client_updates(msgs, sink).await).await
+				} else {
+					sink.submit(msgs).await
+				}
+			}
+		},
+	)
+	.await
+	.map_err(|e| {
+		if let Some(metrics) = metrics {
+			metrics.handle_failed_submission(sink.name(), sink.submission_error_class(&e).as_str());
+		}
+		e
+	})?;
+	sink.common_state().in_flight_packets.insert_messages(&msgs);
+	Ok(())
+}
+
+/// Removes the client updates to heights the sink's client already has a consensus state for. Such
+/// an update is stale, e.g. it was included by an attempt that reported a failure or another
+/// relayer submitted it first, and submitting it again fails, while the proofs of the other
+/// messages can be verified against the existing consensus state.
+async fn without_stale_client_updates(msgs: Vec<Any>, sink: &impl Chain) -> Vec<Any> {
+	let sink_height = match sink.latest_height_and_timestamp().await {
+		Ok((height, _)) => height,
+		Err(e) => {
+			log::warn!(target: "hyperspace", "Failed to query the latest height of {}: {e:?}", sink.name());
+			return msgs
+		},
+	};
+	let mut fresh_msgs = Vec::with_capacity(msgs.len());
+	for msg in msgs {
+		if msg.type_url == UPDATE_CLIENT_TYPE_URL &&
+			is_stale_client_update(&msg, sink_height, sink).await
+		{
+			log::info!(target: "hyperspace", "Removing a stale client update from the messages to {}", sink.name());
+			continue
+		}
+		fresh_msgs.push(msg);
+	}
+	fresh_msgs
+}
+
+async fn is_stale_client_update(msg: &Any, at: Height, sink: &impl Chain) -> bool {
+	let Ok(msg) = MsgUpdateAnyClient::<LocalClientTypes>::decode_vec(&msg.value) else {
+		return false
+	};
+	let Some(height) = msg.client_message.maybe_header_height() else { return false };
+	sink.query_client_consensus(at, msg.client_id, height)
+		.await
+		.map_or(false, |response| response.consensus_state.is_some())
+}
+
+/// Returns `false` if the estimated fee of submitting the messages to the sink is above the sink's
+/// `max_fee_per_batch`. The fee is only estimated if the max is set, and if it can't be estimated
+/// the messages are submitted anyway.
+async fn is_fee_acceptable(msgs: &[Any], sink: &impl Chain) -> bool {
+	let Some(max_fee) = sink.common_state().max_fee_per_batch else { return true };
+	let estimate = match sink.estimate_fee(msgs).await {
+		Ok(estimate) => estimate,
+		Err(e) => {
+			log::warn!(target: "hyperspace", "Failed to estimate the fee of {} messages to {}: {e:?}", msgs.len(), sink.name());
+			return true
+		},
+	};
+	if estimate.amount > max_fee {
+		log::warn!(target: "hyperspace", "Estimated fee of {} messages to {} is {estimate}, which exceeds the max of {max_fee}, skipping them", msgs.len(), sink.name());
+		return false
+	}
+	log::debug!(target: "hyperspace", "Estimated fee of {} messages to {}: {estimate}", msgs.len(), sink.name());
+	true
+}
+
+/// Calls `f` until it succeeds, fails with an error that is not retryable or `max_retries` retries
+/// were made. `f` is given the number of retries made so far. The delay between the attempts
+/// starts at `backoff` and doubles after every retry.
+pub async fn retry_with_backoff<T, E, Fut>(
+	max_retries: usize,
+	mut backoff: Duration,
+	is_retryable: impl Fn(&E) -> bool,
+	on_retry: impl Fn(&E),
+	f: impl Fn(usize) -> Fut,
+) -> Result<T, E>
+where
+	Fut: Future<Output = Result<T, E>>,
+{
+	let mut retries = 0;
+	loop {
+		match f(retries).await {
+			Err(e) if retries < max_retries && is_retryable(&e) => {
+				on_retry(&e);
+				tokio::time::sleep(backoff).await;
+				backoff *= 2;
+				retries += 1;
+			},
+			result => return result,
+		}
+	}
+}
+
+#[cfg(test)]
+pub mod tests {
+	use super::{
+		flush_message_batch, is_block_full, retry_with_backoff, split_message_batch,
+		UPDATE_CLIENT_TYPE_URL,
+	};
+	use ibc::{
+		core::{
+			ics02_client::msgs::update_client::MsgUpdateAnyClient,
+			ics04_channel::{
+				msgs::recv_packet::MsgRecvPacket,
+				packet::{Packet, Sequence},
+			},
+			ics23_commitment::commitment::CommitmentProofBytes,
+			ics24_host::identifier::{ChannelId, ClientId, PortId},
+		},
+		proofs::Proofs,
+		tx_msg::Msg,
+		Height,
+	};
+	use ibc_proto::{
+		google::protobuf::Any,
+		ibc::core::channel::v1::{Channel, Order},
+	};
+	use ics10_grandpa::{
+		client_message::{ClientMessage, Header},
+		proto::{FinalityProof as RawFinalityProof, Header as RawHeader},
+	};
+	use pallet_ibc::light_clients::AnyClientMessage;
+	use primitives::{
+		dry_run::DryRun,
+		in_flight::{InFlightKey, InFlightType},
+		mock::{LocalClientTypes, MockChain},
+		SubmissionErrorClass,
+	};
+	use std::{
+		sync::atomic::{AtomicUsize, Ordering},
+		time::Duration,
+	};
+
+	fn message(type_url: &str, index: u8) -> Any {
+		Any { type_url: type_url.to_string(), value: vec![index; 10] }
+	}
+
+	fn packet(channel: u64, sequence: u64) -> Packet {
+		Packet {
+			sequence: Sequence::from(sequence),
+			destination_port: PortId::transfer(),
+			destination_channel: ChannelId::new(channel),
+			data: vec![1],
+			..Default::default()
+		}
+	}
+
+	fn recv_packet(channel: u64, sequence: u64) -> Any {
+		let packet = packet(channel, sequence);
+		let proof = CommitmentProofBytes::try_from(vec![1]).unwrap();
+		let proofs = Proofs::new(proof, None, None, None, Height::new(0, 1)).unwrap();
+		MsgRecvPacket::new(packet, proofs, "relayer".parse().unwrap()).to_any()
+	}
+
+	fn client_update(client_id: &ClientId, height: Height) -> Any {
+		let header = Header::try_from(RawHeader {
+			finality_proof: Some(RawFinalityProof {
+				block: vec![0; 32],
+				justification: vec![],
+				unknown_headers: vec![],
+			}),
+			parachain_headers: vec![],
+			para_id: height.revision_number as u32,
+			para_height: height.revision_height as u32,
+		})
+		.unwrap();
+		MsgUpdateAnyClient::<LocalClientTypes> {
+			client_id: client_id.clone(),
+			client_message: AnyClientMessage::Grandpa(ClientMessage::Header(header)),
+			signer: "relayer".parse().unwrap(),
+		}
+		.to_any()
+	}
+
+	#[test]
+	fn split_message_batch_sends_client_updates_once() {
+		let update = message(UPDATE_CLIENT_TYPE_URL, 0);
+		let mut msgs = (0..200)
+			.map(|i| message("/ibc.core.channel.v1.MsgRecvPacket", i as u8))
+			.collect::<Vec<_>>();
+		msgs.insert(100, update.clone());
+		let is_update = |msg: &Any| msg.type_url == UPDATE_CLIENT_TYPE_URL;
+
+		// the update counts toward the limit of the first sub-batch
+		let batches = split_message_batch(msgs.clone(), Some(50), None);
+		assert_eq!(batches.iter().map(Vec::len).collect::<Vec<_>>(), vec![50, 50, 50, 50, 1]);
+		assert_eq!(batches[0][0], update);
+		assert_eq!(batches.iter().flatten().filter(|msg| is_update(msg)).count(), 1);
+		let packets = batches.iter().flatten().filter(|msg| !is_update(msg)).collect::<Vec<_>>();
+		assert_eq!(packets, msgs.iter().filter(|msg| !is_update(msg)).collect::<Vec<_>>());
+
+		let batches = split_message_batch(msgs.clone(), None, Some(1010));
+		assert_eq!(batches.iter().map(Vec::len).collect::<Vec<_>>(), vec![101, 100]);
+		assert_eq!(batches[0][0], update);
+		assert!(batches[1].iter().all(|msg| !is_update(msg)));
+		assert!(batches
+			.iter()
+			.all(|batch| batch.iter().map(|msg| msg.value.len()).sum::<usize>() <= 1010));
+
+		assert_eq!(split_message_batch(msgs.clone(), None, None), vec![msgs]);
+	}
+
+	#[tokio::test]
+	async fn retry_with_backoff_retries_transient_errors() {
+		let attempts = AtomicUsize::new(0);
+		let retries = AtomicUsize::new(0);
+		let submit = |_: usize| async {
+			match attempts.fetch_add(1, Ordering::SeqCst) {
+				0 | 1 => Err("account sequence mismatch"),
+				_ => Ok("tx hash"),
+			}
+		};
+		let on_retry = |_: &&str| {
+			retries.fetch_add(1, Ordering::SeqCst);
+		};
+
+		// fails twice, then succeeds
+		let result =
+			retry_with_backoff(3, Duration::from_millis(1), |_| true, on_retry, submit).await;
+		assert_eq!(result, Ok("tx hash"));
+		assert_eq!(attempts.load(Ordering::SeqCst), 3);
+		assert_eq!(retries.load(Ordering::SeqCst), 2);
+
+		// gives up after the retries cap
+		attempts.store(0, Ordering::SeqCst);
+		let result =
+			retry_with_backoff(1, Duration::from_millis(1), |_| true, on_retry, submit).await;
+		assert_eq!(result, Err("account sequence mismatch"));
+		assert_eq!(attempts.load(Ordering::SeqCst), 2);
+
+		// fatal errors are not retried
+		attempts.store(0, Ordering::SeqCst);
+		let result =
+			retry_with_backoff(3, Duration::from_millis(1), |_| false, on_retry, submit).await;
+		assert_eq!(result, Err("account sequence mismatch"));
+		assert_eq!(attempts.load(Ordering::SeqCst), 1);
+	}
+
+	#[test]
+	fn sub_batches_are_paced_across_blocks() {
+		let update = message(UPDATE_CLIENT_TYPE_URL, 0);
+		let mut msgs = vec![update];
+		msgs.extend((0..10).map(|i| message("/ibc.core.channel.v1.MsgRecvPacket", i as u8)));
+		// 3 sub-batches of at most 4 messages, the first one with the update, 2 per block
+		let batches = split_message_batch(msgs, Some(4), None);
+		assert_eq!(batches.iter().map(Vec::len).collect::<Vec<_>>(), vec![4, 4, 3]);
+		let blocks = (0..batches.len())
+			.filter(|submitted| is_block_full(*submitted, Some(2)))
+			.count();
+		assert_eq!(blocks, 1);
+		// the packets stay in order across the sub-batches, so ordered channels are not affected
+		let sequence = batches
+			.iter()
+			.flatten()
+			.filter(|msg| msg.type_url != UPDATE_CLIENT_TYPE_URL)
+			.map(|msg| msg.value[0])
+			.collect::<Vec<_>>();
+		assert_eq!(sequence, (0..10).collect::<Vec<u8>>());
+
+		assert!(!(0..10).any(|submitted| is_block_full(submitted, None)));
+		assert!(!(0..10).any(|submitted| is_block_full(submitted, Some(0))));
+	}
+
+	#[tokio::test]
+	async fn dry_run_never_submits() {
+		let dir = std::env::temp_dir().join(format!("hyperspace-dry-run-{}", std::process::id()));
+		let mut sink = MockChain::new("sink", ClientId::new("07-tendermint", 0).unwrap());
+		sink.common_state.dry_run = Some(DryRun::new(&dir));
+		sink.common_state.max_messages_per_batch = Some(2);
+		let mut msgs = vec![message(UPDATE_CLIENT_TYPE_URL, 0)];
+		msgs.extend((0..5).map(|i| message("/ibc.core.channel.v1.MsgRecvPacket", i as u8)));
+
+		flush_message_batch(msgs, None, &sink).await.unwrap();
+		assert!(sink.submitted().is_empty());
+		assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[tokio::test]
+	async fn only_retryable_submission_errors_are_retried() {
+		let mut sink = MockChain::new("sink", ClientId::new("07-tendermint", 0).unwrap());
+		sink.common_state.max_submission_retries = 3;
+		sink.state().submit_errors.push_back(SubmissionErrorClass::Other);
+		let recv = message("/ibc.core.channel.v1.MsgRecvPacket", 1);
+
+		assert!(flush_message_batch(vec![recv], None, &sink).await.is_err());
+		assert!(sink.submitted().is_empty());
+		assert!(sink.state().submit_errors.is_empty());
+	}
+
+	#[tokio::test]
+	async fn stale_client_update_is_removed_from_the_last_attempt() {
+		let client_id = ClientId::new("10-grandpa", 0).unwrap();
+		let height = Height::new(2000, 10);
+		let mut sink = MockChain::new("sink", ClientId::new("07-tendermint", 0).unwrap());
+		sink.common_state.max_submission_retries = 1;
+		let update = client_update(&client_id, height);
+		assert_eq!(update.type_url, UPDATE_CLIENT_TYPE_URL);
+		let recv = message("/ibc.core.channel.v1.MsgRecvPacket", 1);
+
+		// the client doesn't have the consensus state yet, so the update is submitted again
+		sink.state().submit_errors.push_back(SubmissionErrorClass::AccountSequence);
+		flush_message_batch(vec![update.clone(), recv.clone()], None, &sink)
+			.await
+			.unwrap();
+		assert_eq!(sink.submitted(), vec![vec![update.clone(), recv.clone()]]);
+
+		// the update was included although the attempt timed out
+		{
+			let mut state = sink.state();
+			state.submitted.clear();
+			state.submit_errors.push_back(SubmissionErrorClass::Timeout);
+			state.consensus_states.insert((client_id, height), Any::default());
+		}
+		flush_message_batch(vec![update, recv.clone()], None, &sink).await.unwrap();
+		assert_eq!(sink.submitted(), vec![vec![recv]]);
+	}
+
+	#[tokio::test]
+	async fn failed_sub_batch_stops_the_messages_of_its_ordered_channels() {
+		let mut sink = MockChain::new("sink", ClientId::new("07-tendermint", 0).unwrap());
+		sink.common_state.max_messages_per_batch = Some(2);
+		sink.common_state.max_submission_retries = 0;
+		{
+			let mut state = sink.state();
+			let channel =
+				|ordering: Order| Channel { ordering: ordering as i32, ..Default::default() };
+			state
+				.channels
+				.insert((ChannelId::new(0), PortId::transfer()), channel(Order::Ordered));
+			state
+				.channels
+				.insert((ChannelId::new(1), PortId::transfer()), channel(Order::Unordered));
+			state.submit_errors.push_back(SubmissionErrorClass::Other);
+		}
+		let msgs = vec![recv_packet(0, 1), recv_packet(1, 1), recv_packet(0, 2), recv_packet(1, 2)];
+
+		assert!(flush_message_batch(msgs, None, &sink).await.is_err());
+		// the second packet of the ordered channel would fail without the first one
+		assert_eq!(sink.submitted(), vec![vec![recv_packet(1, 2)]]);
+	}
+
+	#[tokio::test]
+	async fn failed_client_update_stops_the_following_sub_batches() {
+		let mut sink = MockChain::new("sink", ClientId::new("07-tendermint", 0).unwrap());
+		sink.common_state.max_messages_per_batch = Some(2);
+		sink.common_state.max_submission_retries = 0;
+		sink.state().submit_errors.push_back(SubmissionErrorClass::Other);
+		let update = client_update(&ClientId::new("10-grandpa", 0).unwrap(), Height::new(2000, 10));
+		let msgs = vec![update, recv_packet(1, 1), recv_packet(1, 2), recv_packet(1, 3)];
+
+		// the first sub-batch carries the update, which the proofs of the others depend on
+		assert!(flush_message_batch(msgs, None, &sink).await.is_err());
+		assert!(sink.submitted().is_empty());
+	}
+
+	#[tokio::test]
+	async fn sub_batches_wait_for_the_next_finalized_block() {
+		let mut sink = MockChain::new("sink", ClientId::new("07-tendermint", 0).unwrap());
+		sink.common_state.max_messages_per_batch = Some(1);
+		sink.common_state.max_txs_per_block = Some(1);
+		// the first notification is for the current block
+		sink.state().finality_events = vec![Height::new(0, 1), Height::new(0, 2)];
+		let msgs = vec![recv_packet(0, 1), recv_packet(0, 2)];
+
+		tokio::time::timeout(Duration::from_secs(5), flush_message_batch(msgs, None, &sink))
+			.await
+			.unwrap()
+			.unwrap();
+		assert_eq!(sink.submitted().len(), 2);
+	}
+
+	#[tokio::test]
+	async fn batches_above_the_max_fee_are_skipped() {
+		let mut sink = MockChain::new("sink", ClientId::new("07-tendermint", 0).unwrap());
+		sink.common_state.max_fee_per_batch = Some(100);
+		sink.state().fee = 101;
+		let in_flight_key = InFlightKey::new(&packet(0, 1), InFlightType::RecvPacket);
+
+		// the batch is left for a later round, so it must not be marked as in flight
+		flush_message_batch(vec![recv_packet(0, 1)], None, &sink).await.unwrap();
+		assert!(sink.submitted().is_empty());
+		assert!(!sink.common_state.in_flight_packets.contains(&in_flight_key));
+
+		sink.state().fee = 100;
+		flush_message_batch(vec![recv_packet(0, 1)], None, &sink).await.unwrap();
+		assert_eq!(sink.submitted(), vec![vec![recv_packet(0, 1)]]);
+		assert!(sink.common_state.in_flight_packets.contains(&in_flight_key));
+	}
+}
@@ -0,0 +1,356 @@
+// Copyright 2022 ComposableFi
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ibc::{events::IbcEvent, Height};
+use ibc_proto::google::protobuf::Any;
+use primitives::{Chain, UpdateType};
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::BTreeMap,
+	path::PathBuf,
+	sync::{Arc, Mutex},
+};
+
+/// Version of the state file format. Files with other versions are ignored.
+const STATE_VERSION: u32 = 1;
+
+/// Number of the last processed blocks whose hashes are kept for each direction, to find the
+/// block to resume from if the processed one was reorged out.
+const MAX_PROCESSED_BLOCKS: usize = 16;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct StateFile {
+	version: u32,
+	/// Keyed by `"{source}->{sink}"`
+	processed_heights: BTreeMap<String, Height>,
+	/// The heights and hashes of the last processed blocks, oldest first, keyed like the
+	/// processed heights
+	#[serde(default)]
+	processed_blocks: BTreeMap<String, Vec<(Height, Vec<u8>)>>,
+}
+
+/// Keeps the last source height whose events were fully processed for each relaying direction,
+/// and persists it to a file, so that the events aren't processed again after a restart.
+///
+/// The events are queried starting from the latest height of the source client on the sink.
+/// The source chains are given their processed heights at startup, so that they can skip
+/// querying the events that were already processed, see
+/// [`primitives::CommonClientState::processed_height`]. If the client was updated past the
+/// processed height while the relayer was down, the skipped blocks are backfilled at startup,
+/// see `backfill_from_processed_height`.
+///
+/// The hashes of the processed blocks are kept as well, so that the relayer doesn't resume from
+/// a block that was reorged out while it was down, see [`RelayerState::verify_processed_height`].
+#[derive(Debug, Clone)]
+pub struct RelayerState {
+	path: PathBuf,
+	file: Arc<Mutex<StateFile>>,
+	/// Serializes the writes of the file, both directions share it
+	write_lock: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl RelayerState {
+	/// Loads the state from the file. If the file is missing, corrupted or has an unsupported
+	/// version, the relayer starts without a processed height.
+	pub fn load(path: impl Into<PathBuf>) -> Self {
+		let path = path.into();
+		let file = match std::fs::read(&path) {
+			Ok(bytes) => match serde_json::from_slice::<StateFile>(&bytes) {
+				Ok(file) if file.version == STATE_VERSION => file,
+				Ok(file) => {
+					log::warn!(target: "hyperspace", "Ignoring relayer state {} with unsupported version {}", path.display(), file.version);
+					Default::default()
+				},
+				Err(e) => {
+					log::warn!(target: "hyperspace", "Ignoring corrupted relayer state {}: {e}", path.display());
+					Default::default()
+				},
+			},
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => Default::default(),
+			Err(e) => {
+				log::warn!(target: "hyperspace", "Failed to read relayer state {}: {e}", path.display());
+				Default::default()
+			},
+		};
+		let file = StateFile { version: STATE_VERSION, ..file };
+		Self { path, file: Arc::new(Mutex::new(file)), write_lock: Default::default() }
+	}
+
+	/// Returns the last height of `source` whose events were relayed to `sink`.
+	pub fn processed_height(&self, source: &str, sink: &str) -> Option<Height> {
+		self.file
+			.lock()
+			.unwrap()
+			.processed_heights
+			.get(&direction(source, sink))
+			.copied()
+	}
+
+	/// Records the processed height with the hash of its block, if known, and persists the state.
+	/// Heights lower than the recorded one are ignored.
+	pub async fn set_processed_height(
+		&self,
+		source: &str,
+		sink: &str,
+		height: Height,
+		block_hash: Option<Vec<u8>>,
+	) -> Result<(), anyhow::Error> {
+		// the state is read under the write lock, so that an older state never overwrites a newer
+		let _guard = self.write_lock.lock().await;
+		let file = {
+			let mut file = self.file.lock().unwrap();
+			let key = direction(source, sink);
+			let processed_height = file.processed_heights.entry(key.clone()).or_default();
+			if height <= *processed_height {
+				return Ok(())
+			}
+			*processed_height = height;
+			if let Some(block_hash) = block_hash {
+				let blocks = file.processed_blocks.entry(key).or_default();
+				blocks.push((height, block_hash));
+				if blocks.len() > MAX_PROCESSED_BLOCKS {
+					blocks.remove(0);
+				}
+			}
+			file.clone()
+		};
+		self.write(&file).await
+	}
+
+	/// Checks that the block of the processed height of `source` wasn't reorged out while the
+	/// relayer was down. Otherwise walks back the recorded blocks to the last one `source` still
+	/// has, and rewinds the processed height to it, so that the events of the blocks that replaced
+	/// the orphaned ones are processed. If none of the recorded blocks is left, the processed
+	/// height is dropped.
+	///
+	/// Returns the height to resume from. The processed height is kept as is if the blocks can't
+	/// be queried.
+	pub async fn verify_processed_height(&self, source: &impl Chain, sink: &str) -> Option<Height> {
+		let key = direction(source.name(), sink);
+		let (processed_height, blocks) = {
+			let file = self.file.lock().unwrap();
+			let processed_height = file.processed_heights.get(&key).copied()?;
+			(processed_height, file.processed_blocks.get(&key).cloned().unwrap_or_default())
+		};
+		if blocks.is_empty() {
+			return Some(processed_height)
+		}
+		let mut ancestor = None;
+		for (i, (height, block_hash)) in blocks.iter().enumerate().rev() {
+			match source.get_block(*height).await {
+				Ok(Some(hash)) if hash == *block_hash => {
+					ancestor = Some((i, *height));
+					break
+				},
+				Ok(_) => {
+					log::warn!(target: "hyperspace", "Processed block of {} at {height} was reorged out", source.name());
+				},
+				Err(e) => {
+					log::warn!(target: "hyperspace", "Failed to verify the processed block of {} at {height}: {e:?}", source.name());
+					return Some(processed_height)
+				},
+			}
+		}
+		let resume_height = match ancestor {
+			// the latest recorded block is still there
+			Some((i, _)) if i == blocks.len() - 1 => return Some(processed_height),
+			Some((_, height)) => Some(height),
+			None => None,
+		};
+		log::warn!(target: "hyperspace", "Rewinding the processed height of {} from {processed_height} to {resume_height:?}", source.name());
+		if let Err(e) = self.rewind(&key, resume_height).await {
+			log::warn!(target: "hyperspace", "Failed to persist the processed height of {}: {e:?}", source.name());
+		}
+		resume_height
+	}
+
+	/// Sets the processed height back to `height` after a finality regression of `source`, and
+	/// persists the state, so that the blocks above it are processed again after a restart as well.
+	/// Heights higher than the recorded one are ignored.
+	pub async fn rewind_processed_height(
+		&self,
+		source: &str,
+		sink: &str,
+		height: Height,
+	) -> Result<(), anyhow::Error> {
+		match self.processed_height(source, sink) {
+			Some(processed_height) if height < processed_height =>
+				self.rewind(&direction(source, sink), Some(height)).await,
+			_ => Ok(()),
+		}
+	}
+
+	/// Sets the processed height of the direction back to `height`, forgetting the blocks above
+	/// it, and persists the state.
+	async fn rewind(&self, key: &str, height: Option<Height>) -> Result<(), anyhow::Error> {
+		let _guard = self.write_lock.lock().await;
+		let file = {
+			let mut file = self.file.lock().unwrap();
+			match height {
+				Some(height) => {
+					file.processed_heights.insert(key.to_string(), height);
+					if let Some(blocks) = file.processed_blocks.get_mut(key) {
+						blocks.retain(|(block_height, _)| *block_height <= height);
+					}
+				},
+				None => {
+					file.processed_heights.remove(key);
+					file.processed_blocks.remove(key);
+				},
+			}
+			file.clone()
+		};
+		self.write(&file).await
+	}
+
+	/// Writes the state to the file. Must be called under the write lock.
+	async fn write(&self, file: &StateFile) -> Result<(), anyhow::Error> {
+		// write to a temporary file first, so that the state is never partially written
+		let tmp_path = self.path.with_extension("tmp");
+		tokio::fs::write(&tmp_path, serde_json::to_vec(file)?).await?;
+		tokio::fs::rename(&tmp_path, &self.path).await?;
+		Ok(())
+	}
+
+	/// Drops the events of the updates at heights that were already processed. The updates
+	/// themselves are kept, since the client may still need them.
+	pub fn skip_processed_events(
+		&self,
+		source: &str,
+		sink: &str,
+		updates: &mut [(Any, Height, Vec<IbcEvent>, UpdateType)],
+	) {
+		let processed_height = match self.processed_height(source, sink) {
+			Some(height) => height,
+			None => return,
+		};
+		for (_, height, events, _) in updates.iter_mut() {
+			if *height <= processed_height && !events.is_empty() {
+				log::debug!(target: "hyperspace", "Skipping {} events of {source} at {height}, already processed up to {processed_height}", events.len());
+				events.clear();
+			}
+		}
+	}
+}
+
+fn direction(source: &str, sink: &str) -> String {
+	format!("{source}->{sink}")
+}
+
+#[cfg(test)]
+pub mod tests {
+	use super::RelayerState;
+	use ibc::{
+		core::{ics02_client::events::NewBlock, ics24_host::identifier::ClientId},
+		events::IbcEvent,
+		Height,
+	};
+	use ibc_proto::google::protobuf::Any;
+	use primitives::{mock::MockChain, UpdateType};
+	use std::path::PathBuf;
+
+	fn state_path(name: &str) -> PathBuf {
+		let path = std::env::temp_dir()
+			.join(format!("hyperspace-state-{name}-{}.json", std::process::id()));
+		let _ = std::fs::remove_file(&path);
+		path
+	}
+
+	fn updates(
+		heights: std::ops::RangeInclusive<u64>,
+	) -> Vec<(Any, Height, Vec<IbcEvent>, UpdateType)> {
+		heights
+			.map(|height| {
+				let height = Height::new(0, height);
+				let event = IbcEvent::NewBlock(NewBlock::new(height));
+				(Any::default(), height, vec![event], UpdateType::Mandatory)
+			})
+			.collect()
+	}
+
+	/// Processes the events of the updates that weren't processed yet, returns their heights.
+	async fn process(state: &RelayerState, heights: std::ops::RangeInclusive<u64>) -> Vec<u64> {
+		let mut updates = updates(heights);
+		state.skip_processed_events("a", "b", &mut updates);
+		let processed = updates
+			.iter()
+			.filter(|(.., events, _)| !events.is_empty())
+			.map(|(_, height, ..)| height.revision_height)
+			.collect::<Vec<_>>();
+		let (_, height, ..) = updates.last().unwrap();
+		// the hash of the block of the mock chain
+		let block_hash = height.revision_height.to_be_bytes().to_vec();
+		state.set_processed_height("a", "b", *height, Some(block_hash)).await.unwrap();
+		processed
+	}
+
+	#[tokio::test]
+	async fn restart_resumes_from_processed_height() {
+		let path = state_path("restart");
+		let state = RelayerState::load(&path);
+		assert_eq!(process(&state, 1..=5).await, vec![1, 2, 3, 4, 5]);
+		drop(state);
+
+		// after the restart the events are queried from the client height, which is behind
+		let state = RelayerState::load(&path);
+		assert_eq!(state.processed_height("a", "b"), Some(Height::new(0, 5)));
+		assert_eq!(state.processed_height("b", "a"), None);
+		assert_eq!(process(&state, 3..=8).await, vec![6, 7, 8]);
+		assert_eq!(RelayerState::load(&path).processed_height("a", "b"), Some(Height::new(0, 8)));
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[tokio::test]
+	async fn corrupted_or_unsupported_state_is_ignored() {
+		let path = state_path("corrupted");
+		std::fs::write(&path, b"{ not json").unwrap();
+		let state = RelayerState::load(&path);
+		assert_eq!(state.processed_height("a", "b"), None);
+		assert_eq!(process(&state, 1..=2).await, vec![1, 2]);
+
+		std::fs::write(&path, br#"{"version":0,"processed_heights":{}}"#).unwrap();
+		assert_eq!(RelayerState::load(&path).processed_height("a", "b"), None);
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[tokio::test]
+	async fn reorged_processed_blocks_are_walked_back() {
+		let path = state_path("reorg");
+		let state = RelayerState::load(&path);
+		for height in 1..=5 {
+			process(&state, height..=height).await;
+		}
+		let source = MockChain::new("a", ClientId::new("07-tendermint", 0).unwrap());
+		assert_eq!(state.verify_processed_height(&source, "b").await, Some(Height::new(0, 5)));
+
+		// the last two blocks are reorged out while the relayer is down
+		for height in 4..=5 {
+			source.state().block_hashes.insert(Height::new(0, height), vec![0xff]);
+		}
+		let state = RelayerState::load(&path);
+		assert_eq!(state.verify_processed_height(&source, "b").await, Some(Height::new(0, 3)));
+		assert_eq!(RelayerState::load(&path).processed_height("a", "b"), Some(Height::new(0, 3)));
+		// the events of the blocks that replaced them are processed again
+		assert_eq!(process(&state, 2..=6).await, vec![4, 5, 6]);
+
+		// none of the recorded blocks is left
+		for height in 1..=6 {
+			source.state().block_hashes.insert(Height::new(0, height), vec![0xff]);
+		}
+		let state = RelayerState::load(&path);
+		assert_eq!(state.verify_processed_height(&source, "b").await, None);
+		assert_eq!(RelayerState::load(&path).processed_height("a", "b"), None);
+		std::fs::remove_file(&path).unwrap();
+	}
+}
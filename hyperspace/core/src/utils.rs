@@ -1,9 +1,11 @@
-use futures::{Stream, StreamExt};
+use futures::{Future, Stream, StreamExt};
 use std::{
 	pin::Pin,
 	sync::{Arc, Mutex},
 	task::Poll,
+	time::{Duration, Instant},
 };
+use tokio::task::JoinHandle;
 
 /// Keeps the most recent value of a stream and acts as stream itself.
 pub struct RecentStream<T: Send + 'static> {
@@ -45,3 +47,105 @@ impl<T: Send> Stream for RecentStream<T> {
 		}
 	}
 }
+
+/// Aborts the task when dropped, so that it doesn't outlive its owner.
+struct AbortOnDrop<T>(JoinHandle<T>);
+
+impl<T> Drop for AbortOnDrop<T> {
+	fn drop(&mut self) {
+		self.0.abort();
+	}
+}
+
+/// Runs the task returned by `spawn_task` in its own tokio task until it succeeds. If it fails or
+/// panics, it's spawned again after a delay that starts at `backoff` and doubles after every
+/// consecutive failure, up to `max_backoff`. The running task is aborted when the returned future
+/// is dropped.
+pub async fn supervise<F, Fut>(
+	name: String,
+	backoff: Duration,
+	max_backoff: Duration,
+	mut spawn_task: F,
+) where
+	F: FnMut() -> Fut,
+	Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+{
+	let mut delay = backoff;
+	loop {
+		let started_at = Instant::now();
+		let mut task = AbortOnDrop(tokio::spawn(spawn_task()));
+		match (&mut task.0).await {
+			Ok(Ok(())) => return,
+			Ok(Err(e)) => log::error!(target: "hyperspace", "Relaying {name} failed: {e:?}"),
+			Err(e) if e.is_panic() => log::error!(target: "hyperspace", "Relaying {name} panicked"),
+			Err(_) => return,
+		}
+		// a task that was running for a while before failing isn't failing consecutively
+		if started_at.elapsed() > max_backoff {
+			delay = backoff;
+		}
+		log::info!(target: "hyperspace", "Restarting relaying {name} in {delay:?}");
+		tokio::time::sleep(delay).await;
+		delay = (delay * 2).min(max_backoff);
+	}
+}
+
+#[cfg(test)]
+pub mod tests {
+	use super::supervise;
+	use std::{
+		sync::{
+			atomic::{AtomicUsize, Ordering},
+			Arc,
+		},
+		time::Duration,
+	};
+
+	#[tokio::test]
+	async fn failing_direction_does_not_stop_the_other() {
+		let failures = Arc::new(AtomicUsize::new(0));
+		let relayed = Arc::new(AtomicUsize::new(0));
+		let backoff = Duration::from_millis(5);
+
+		// the finality stream of `a` keeps failing, either with an error or a panic
+		let a_to_b = {
+			let failures = failures.clone();
+			supervise("a -> b".to_string(), backoff, backoff * 4, move || {
+				let failures = failures.clone();
+				async move {
+					if failures.fetch_add(1, Ordering::SeqCst) % 2 == 0 {
+						Err(anyhow::anyhow!("finality stream of a closed"))
+					} else {
+						panic!("finality stream of a panicked")
+					}
+				}
+			})
+		};
+		let b_to_a = {
+			let relayed = relayed.clone();
+			supervise("b -> a".to_string(), backoff, backoff * 4, move || {
+				let relayed = relayed.clone();
+				async move {
+					loop {
+						relayed.fetch_add(1, Ordering::SeqCst);
+						tokio::time::sleep(Duration::from_millis(1)).await;
+					}
+				}
+			})
+		};
+		let relayer = tokio::spawn(futures::future::join(a_to_b, b_to_a));
+
+		tokio::time::sleep(Duration::from_millis(200)).await;
+		assert!(failures.load(Ordering::SeqCst) >= 3);
+		assert!(relayed.load(Ordering::SeqCst) >= 10);
+
+		// stopping the relayer stops both directions
+		relayer.abort();
+		assert!(relayer.await.unwrap_err().is_cancelled());
+		let relayed_before = relayed.load(Ordering::SeqCst);
+		let failures_before = failures.load(Ordering::SeqCst);
+		tokio::time::sleep(Duration::from_millis(50)).await;
+		assert_eq!(relayed.load(Ordering::SeqCst), relayed_before);
+		assert_eq!(failures.load(Ordering::SeqCst), failures_before);
+	}
+}
@@ -8,6 +8,7 @@ use ibc::{
 		ics26_routing::msgs::Ics26Envelope,
 	},
 	events::IbcEvent,
+	signer::Signer,
 	Height,
 };
 use ibc_proto::{
@@ -19,17 +20,41 @@ use ibc_proto::{
 };
 use pallet_ibc::light_clients::AnyClientMessage;
 use primitives::{
-	mock::LocalClientTypes, Chain, CommonClientState, IbcProvider, LightClientSync,
-	MisbehaviourHandler,
+	check_relayed_path, mock::LocalClientTypes, Chain, CommonClientState, FeeEstimate, IbcProvider,
+	KeyProvider, LightClientSync, MisbehaviourHandler, SubmissionErrorClass,
 };
 use prost::Message;
 use std::{pin::Pin, time::Duration};
 use tendermint_rpc::{
 	event::{Event, EventData},
 	query::{EventType, Query},
-	SubscriptionClient, WebSocketClient,
+	Client, SubscriptionClient, WebSocketClient,
 };
 
+/// Codespace of the cosmos-sdk errors.
+const SDK_CODESPACE: &str = "sdk";
+const ERR_OUT_OF_GAS: u32 = 11;
+const ERR_TX_IN_MEMPOOL_CACHE: u32 = 19;
+const ERR_MEMPOOL_IS_FULL: u32 = 20;
+const ERR_WRONG_SEQUENCE: u32 = 32;
+
+/// Codes and registered descriptions of the cosmos-sdk errors a submission can be classified by.
+const SDK_ERRORS: [(u32, &str); 4] = [
+	(ERR_OUT_OF_GAS, "out of gas"),
+	(ERR_TX_IN_MEMPOOL_CACHE, "tx already in mempool"),
+	(ERR_MEMPOOL_IS_FULL, "mempool is full"),
+	(ERR_WRONG_SEQUENCE, "incorrect account sequence"),
+];
+
+fn sdk_error_class(code: u32) -> SubmissionErrorClass {
+	match code {
+		ERR_WRONG_SEQUENCE => SubmissionErrorClass::AccountSequence,
+		ERR_TX_IN_MEMPOOL_CACHE | ERR_MEMPOOL_IS_FULL => SubmissionErrorClass::Mempool,
+		ERR_OUT_OF_GAS => SubmissionErrorClass::OutOfGas,
+		_ => SubmissionErrorClass::Other,
+	}
+}
+
 #[async_trait::async_trait]
 impl<H> LightClientSync for CosmosClient<H>
 where
@@ -90,6 +115,15 @@ where
 		Ok(current_len as u64)
 	}
 
+	async fn estimate_fee(&self, _msgs: &[Any]) -> Result<FeeEstimate, Self::Error> {
+		// the configured fee is paid for every transaction, regardless of its messages
+		let amount = self
+			.fee_amount
+			.parse()
+			.map_err(|e| Error::from(format!("Invalid fee amount {}: {e}", self.fee_amount)))?;
+		Ok(FeeEstimate { amount, denom: Some(self.fee_denom.clone()) })
+	}
+
 	async fn finality_notifications(
 		&self,
 	) -> Result<Pin<Box<dyn Stream<Item = <Self as IbcProvider>::FinalityEvent> + Send + Sync>>, Error> {
@@ -221,6 +255,25 @@ where
 		block_height.increment()
 	}
 
+	fn submission_error_class(&self, error: &Error) -> SubmissionErrorClass {
+		match error {
+			Error::RpcError(_) => SubmissionErrorClass::Rpc,
+			Error::TxNotFound(..) => SubmissionErrorClass::Timeout,
+			Error::TxFailed { codespace, code, .. } if codespace == SDK_CODESPACE =>
+				sdk_error_class(*code),
+			Error::SimulationFailed(status) => match status.code() {
+				tonic::Code::Unavailable | tonic::Code::DeadlineExceeded =>
+					SubmissionErrorClass::Rpc,
+				// the simulation reports the registered description of the sdk error
+				_ => SDK_ERRORS
+					.iter()
+					.find(|(_, description)| status.message().contains(&format!(": {description}")))
+					.map_or(SubmissionErrorClass::Other, |(code, _)| sdk_error_class(*code)),
+			},
+			_ => SubmissionErrorClass::Other,
+		}
+	}
+
 	async fn handle_error(&mut self, error: &anyhow::Error) -> Result<(), anyhow::Error> {
 		let err_str = if let Some(rpc_err) = error.downcast_ref::<Error>() {
 			match rpc_err {
@@ -267,6 +320,35 @@ where
 		log::info!(target: "hyperspace_cosmos", "Reconnected to cosmos chain");
 		Ok(())
 	}
+
+	fn validate_signer(&self) -> Result<Signer, Self::Error> {
+		let signer = self.account_id();
+		let (prefix, ..) = bech32::decode(signer.as_ref()).map_err(|e| {
+			Error::from(format!("Invalid signer {} for {}: {e}", signer, self.name))
+		})?;
+		if prefix != self.account_prefix {
+			return Err(Error::from(format!(
+				"Invalid signer {} for {}: expected account prefix {}, found {prefix}",
+				signer, self.name, self.account_prefix
+			)))
+		}
+		Ok(signer)
+	}
+
+	async fn health_check<C: Chain>(&self, counterparty: &C) -> Result<(), anyhow::Error> {
+		let status = self.rpc_http_client.status().await.map_err(|e| {
+			anyhow::anyhow!("Failed to query the status of {}, check the rpc_url: {e}", self.name)
+		})?;
+		let network = status.node_info.network.to_string();
+		if network != self.chain_id.to_string() {
+			return Err(anyhow::anyhow!(
+				"Chain id of {} is {network}, but {} is configured",
+				self.name,
+				self.chain_id
+			))
+		}
+		check_relayed_path(self, counterparty).await
+	}
 }
 
 impl<H> CosmosClient<H>
@@ -296,7 +378,7 @@ where
 		&self,
 		_counterparty: &C,
 		_client_message: AnyClientMessage,
-	) -> Result<(), anyhow::Error> {
-		Ok(())
+	) -> Result<Option<Any>, anyhow::Error> {
+		Ok(None)
 	}
 }
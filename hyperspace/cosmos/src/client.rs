@@ -27,6 +27,8 @@ use ics07_tendermint::{
 };
 use pallet_ibc::light_clients::{AnyClientState, AnyConsensusState, HostFunctionsManager};
 use primitives::{
+	channel_filter::{ChannelFilter, ChannelFilterEntry},
+	in_flight::InFlightPackets,
 	Chain, CommonClientConfig, CommonClientState, IbcProvider, KeyProvider, UpdateType,
 };
 use prost::Message;
@@ -233,8 +235,9 @@ pub struct CosmosClientConfig {
 	pub address_type: AddressType,			    // TODO: Type = cosmos
 	pub extension_options: Vec<ExtensionOption>,// TODO: Could be set to None
 	*/
-	/// Whitelisted channels
-	pub channel_whitelist: Vec<(ChannelId, PortId)>,
+	/// Whitelisted channels, either `[channel, port]` pairs, `["*", port]` for all the channels on
+	/// the port, or `"*"` for all the channels
+	pub channel_whitelist: Vec<ChannelFilterEntry>,
 	/// The key that signs transactions
 	pub mnemonic: String,
 	/// Common client config
@@ -290,6 +293,7 @@ where
 		})
 		.map_err(|e| e.to_string())?;
 
+		let channel_filter = ChannelFilter::new(config.channel_whitelist);
 		let rpc_call_delay = Duration::from_millis(1000);
 		Ok(Self {
 			name: config.name,
@@ -302,7 +306,7 @@ where
 			websocket_url: config.websocket_url,
 			client_id: Arc::new(Mutex::new(config.client_id)),
 			connection_id: Arc::new(Mutex::new(config.connection_id)),
-			channel_whitelist: Arc::new(Mutex::new(config.channel_whitelist.into_iter().collect())),
+			channel_whitelist: Arc::new(Mutex::new(channel_filter.channels())),
 			light_client,
 			account_prefix: config.account_prefix,
 			commitment_prefix,
@@ -322,6 +326,42 @@ where
 				misbehaviour_client_msg_queue: Arc::new(AsyncMutex::new(vec![])),
 				max_packets_to_process: config.common.max_packets_to_process as usize,
 				skip_tokens_list: config.skip_tokens_list.unwrap_or_default(),
+				channel_filter,
+				packet_filter: config.common.packet_filter,
+				channel_packet_filters: config.common.channel_packet_filters,
+				packet_filter_hooks: config
+					.common
+					.transfer_denylist
+					.hooks()
+					.map_err(|e| Error::from(format!("Invalid transfer denylist: {e}")))?,
+				skipped_packets: Default::default(),
+				max_messages_per_batch: config.common.max_messages_per_batch.map(|x| x as usize),
+				max_txs_per_block: config.common.max_txs_per_block.map(|x| x as usize),
+				max_batch_bytes: config.common.max_batch_bytes.map(|x| x as usize),
+				max_concurrent_packet_proofs: config.common.max_concurrent_packet_proofs as usize,
+				channel_delay_overrides: config
+					.common
+					.channel_delay_overrides
+					.into_iter()
+					.map(|(channel_id, port_id, delay)| {
+						((channel_id, port_id), Duration::from_secs(delay))
+					})
+					.collect(),
+				in_flight_packets: InFlightPackets::new(Duration::from_secs(
+					config.common.in_flight_packets_expiry,
+				)),
+				max_submission_retries: config.common.max_submission_retries as usize,
+				submission_lock: Default::default(),
+				eager_flush: config.common.eager_flush,
+				max_client_staleness: config.common.max_client_staleness.map(Duration::from_secs),
+				poll_interval: config.common.poll_interval.map(Duration::from_millis),
+				dry_run: None,
+				pause_flag: Default::default(),
+				shutdown: Default::default(),
+				force_client_update: Default::default(),
+				processed_height: Default::default(),
+				finality_regression_threshold: config.common.finality_regression_threshold,
+				max_fee_per_batch: config.common.max_fee_per_batch.map(Into::into),
 			},
 			join_handles: Arc::new(TokioMutex::new(join_handles)),
 		})
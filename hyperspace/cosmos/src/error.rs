@@ -25,6 +25,15 @@ pub enum Error {
 	/// Tendermint error
 	#[error("Tendermint error: {0}")]
 	TendermintError(#[from] tendermint::Error),
+	/// Transaction simulation failed
+	#[error("Transaction simulation failed: {0}")]
+	SimulationFailed(tonic::Status),
+	/// Transaction was rejected or failed with an ABCI error code
+	#[error("Transaction {hash} failed with code {code} in codespace {codespace:?}: {log}")]
+	TxFailed { hash: String, codespace: String, code: u32, log: String },
+	/// Transaction wasn't included in a block in time
+	#[error("Transaction {0} not found after {1:?}")]
+	TxNotFound(String, std::time::Duration),
 }
 
 impl From<String> for Error {
@@ -17,7 +17,7 @@ use ibc::{
 			client_state::ClientType, events as ClientEvents,
 			msgs::update_client::MsgUpdateAnyClient, trust_threshold::TrustThreshold,
 		},
-		ics04_channel::packet::Sequence,
+		ics04_channel::packet::{Packet, Sequence},
 		ics23_commitment::{commitment::CommitmentPrefix, specs::ProofSpecs},
 		ics24_host::{
 			identifier::{ChainId, ChannelId, ClientId, ConnectionId, PortId},
@@ -63,7 +63,10 @@ use pallet_ibc::light_clients::{
 	AnyClientMessage, AnyClientState, AnyConsensusState, HostFunctionsManager,
 };
 use primitives::{
-	filter_events_by_ids, mock::LocalClientTypes, Chain, IbcProvider, KeyProvider, UpdateType,
+	filter_events_by_ids,
+	key_path::{get_key_path, KeyPathType},
+	mock::LocalClientTypes,
+	Chain, IbcProvider, KeyProvider, UpdateType,
 };
 use prost::Message;
 use rand::Rng;
@@ -146,9 +149,22 @@ where
 		let mut block_events = Vec::new();
 		let mut join_set: JoinSet<Result<_, anyhow::Error>> = JoinSet::new();
 		let range = (from.value()..to.value()).collect::<Vec<_>>();
+		// the client may be behind the processed height if its optional updates were skipped, or
+		// the relayer was restarted, there's no need to parse the events of these blocks again
+		let processed_height = self.common_state().processed_height();
+		let is_processed = |height: u64| {
+			processed_height.map_or(false, |processed_height| {
+				Height::new(latest_revision, height) <= processed_height
+			})
+		};
 		let to = self.rpc_call_delay().as_millis();
 		for heights in range.chunks(100) {
 			for height in heights.iter().copied() {
+				if is_processed(height) {
+					log::trace!(target: "hyperspace_cosmos", "Skipping events at height {:?}, already processed", height);
+					block_events.push((height, vec![]));
+					continue
+				}
 				log::trace!(target: "hyperspace_cosmos", "Parsing events at height {:?}", height);
 				let client = self.clone();
 				let duration = Duration::from_millis(rand::thread_rng().gen_range(0..to) as u64);
@@ -207,6 +223,13 @@ where
 		Ok(updates)
 	}
 
+	async fn finality_event_height(&self, finality_event: &Self::FinalityEvent) -> Option<Height> {
+		match finality_event {
+			FinalityEvent::Tendermint { to, .. } =>
+				Some(Height::new(self.chain_id.version(), to.value())),
+		}
+	}
+
 	// TODO: Changed result: `Item =` from `IbcEvent` to `IbcEventWithHeight` to include the
 	// necessary height field, as `height` is removed from `Attribute` from ibc-rs v0.22.0
 	async fn ibc_events(&self) -> Pin<Box<dyn Stream<Item = IbcEvent> + Send + 'static>> {
@@ -366,6 +389,11 @@ where
 		})
 	}
 
+	/// The IBC store is selected by the ABCI query path, so the keys aren't prefixed.
+	fn full_key_path(&self, key_path_type: KeyPathType, packet: &Packet) -> Vec<u8> {
+		get_key_path(key_path_type, packet).into_bytes()
+	}
+
 	async fn query_proof(&self, at: Height, keys: Vec<Vec<u8>>) -> Result<Vec<u8>, Self::Error> {
 		let (_, proof) = self.query_path(keys[0].clone(), at, true).await?;
 		Ok(proof)
@@ -964,6 +992,17 @@ where
 		Ok(time.nanoseconds())
 	}
 
+	async fn get_block(&self, height: Height) -> Result<Option<Vec<u8>>, Self::Error> {
+		let height = TmHeight::try_from(height.revision_height)
+			.map_err(|e| Error::from(format!("Invalid block number: {e}")))?;
+		let response = self
+			.rpc_ws_client()
+			.block(height)
+			.await
+			.map_err(|e| Error::RpcError(e.to_string()))?;
+		Ok(Some(response.block_id.hash.as_bytes().to_vec()))
+	}
+
 	async fn query_clients(&self) -> Result<Vec<ClientId>, Self::Error> {
 		let request = tonic::Request::new(QueryClientStatesRequest {
 			pagination: Some(PageRequest { limit: u32::MAX as _, ..Default::default() }),
@@ -1331,6 +1370,11 @@ where
 
 		Ok(code_id)
 	}
+
+	fn requires_gzipped_wasm(&self) -> bool {
+		// the whole code is pushed in a single transaction, which must fit in a block
+		true
+	}
 }
 
 impl<H> CosmosClient<H>
@@ -1366,6 +1410,8 @@ where
 		for event in events {
 			let mut channel_and_port_ids = self.channel_whitelist();
 			channel_and_port_ids.extend(counterparty.channel_whitelist());
+			let mut channel_filter = self.common_state().channel_filter.clone();
+			channel_filter.extend(&counterparty.common_state().channel_filter);
 
 			let ibc_event = ibc_event_try_from_abci_event(&event, ibc_height).ok();
 			match ibc_event {
@@ -1378,6 +1424,7 @@ where
 							.flatten()
 							.collect::<Vec<_>>(),
 						&channel_and_port_ids,
+						&channel_filter,
 					);
 
 					if is_filtered {
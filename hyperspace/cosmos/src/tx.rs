@@ -74,10 +74,10 @@ pub async fn simulate_tx(
 
 	let response = tokio::time::timeout(
 		Duration::from_secs(15),
-		client.simulate(request).map_err(|e| Error::from(e.to_string())),
+		client.simulate(request).map_err(Error::SimulationFailed),
 	)
 	.await
-	.map_err(|_| Error::from("simulation timeout".to_string()))??
+	.map_err(|_| Error::RpcError("simulation timeout".to_string()))??
 	.into_inner();
 	Ok(response)
 }
@@ -86,7 +86,16 @@ pub async fn broadcast_tx(rpc_client: &WebSocketClient, tx_bytes: Vec<u8>) -> Re
 	let response = rpc_client
 		.broadcast_tx_sync(tx_bytes)
 		.await
-		.map_err(|e| Error::from(format!("failed to broadcast transaction {e:?}")))?;
+		.map_err(|e| Error::RpcError(format!("failed to broadcast transaction {e:?}")))?;
+	// the transaction was rejected by the mempool
+	if response.code.is_err() {
+		return Err(Error::TxFailed {
+			hash: response.hash.to_string(),
+			codespace: response.codespace,
+			code: response.code.value(),
+			log: response.log,
+		})
+	}
 	Ok(response.hash)
 }
 
@@ -104,16 +113,12 @@ pub async fn confirm_tx(rpc_client: &WebSocketClient, tx_hash: Hash) -> Result<H
 				Order::Ascending,
 			)
 			.await
-			.map_err(|e| Error::from(format!("failed to search for transaction {e:?}")))?;
+			.map_err(|e| Error::RpcError(format!("failed to search for transaction {e:?}")))?;
 		match response.txs.into_iter().next() {
 			None => {
 				let elapsed = start_time.elapsed();
 				if elapsed > timeout {
-					return Err(Error::from(format!(
-						"transaction {} not found after {} seconds",
-						tx_hash,
-						elapsed.as_secs()
-					)))
+					return Err(Error::TxNotFound(tx_hash.to_string(), elapsed))
 				} else {
 					tokio::time::sleep(WAIT_BACKOFF).await;
 				}
@@ -122,9 +127,14 @@ pub async fn confirm_tx(rpc_client: &WebSocketClient, tx_hash: Hash) -> Result<H
 		}
 	};
 
-	let response_code = response.tx_result.code;
-	if response_code.is_err() {
-		return Err(Error::from(format!("transaction {tx_hash} failed with code {response_code:?}")))
+	let tx_result = response.tx_result;
+	if tx_result.code.is_err() {
+		return Err(Error::TxFailed {
+			hash: tx_hash.to_string(),
+			codespace: tx_result.codespace,
+			code: tx_result.code.value(),
+			log: tx_result.log,
+		})
 	}
 	Ok(response.hash)
 }
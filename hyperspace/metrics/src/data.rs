@@ -141,6 +141,38 @@ pub struct Metrics {
 	/// Average time between client updates.
 	pub sent_update_client_time: Histogram,
 
+	/// Time between observing a "send packet" event and submitting a receive message for it.
+	pub packet_send_to_recv_latency: HistogramVec,
+	/// Time between observing a "write acknowledgement" event and submitting an acknowledgement
+	/// message for it.
+	pub packet_recv_to_ack_latency: HistogramVec,
+	/// Total number of submitted timeout messages per channel.
+	pub number_of_submitted_timeouts: CounterVec<U64>,
+	/// Total number of failed message submissions per error class.
+	pub number_of_failed_submissions: CounterVec<U64>,
+	/// Total number of retried message submissions.
+	pub number_of_submission_retries: CounterVec<U64>,
+	/// Total number of packets skipped because of the packet filters.
+	pub number_of_filtered_packets: CounterVec<U64>,
+	/// Total number of packets denied by the packet filter hooks per reason.
+	pub number_of_denied_packets: CounterVec<U64>,
+	/// Number of undelivered packets per channel.
+	pub undelivered_packets_per_channel: GaugeVec<U64>,
+	/// Number of undelivered acknowledgements per channel.
+	pub undelivered_acknowledgements_per_channel: GaugeVec<U64>,
+	/// Age (in source blocks) of the oldest undelivered packet per channel.
+	pub oldest_undelivered_packet_age: GaugeVec<U64>,
+	/// Sequence of the filtered packet an ordered channel is blocked at, 0 if it's not blocked.
+	pub blocked_ordered_channel_sequence: GaugeVec<U64>,
+	/// Total number of packets that timed out before they were delivered per channel.
+	pub number_of_packets_timed_out_while_pending: CounterVec<U64>,
+	/// Total number of optional client updates that weren't sent because there were no messages.
+	pub number_of_skipped_client_updates: CounterVec<U64>,
+	/// Total number of detected misbehaviours of the counterparty.
+	pub number_of_detected_misbehaviours: CounterVec<U64>,
+	/// Seconds left until the counterparty client expires.
+	pub client_seconds_to_expiry: GaugeVec<U64>,
+
 	/// Latest processed height - helpful to prevent pushing the same event twice
 	pub latest_processed_height: Gauge<U64>,
 
@@ -343,6 +375,173 @@ impl Metrics {
 				)?,
 				registry,
 			)?,
+			packet_send_to_recv_latency: register(
+				HistogramVec::new(
+					HistogramOpts::new(
+						"hyperspace_packet_send_to_recv_latency".to_string(),
+						"Time between a 'send packet' event and the submission of the receive message",
+					)
+					.buckets(vec![1.0, 10.0, 100.0, 1000.0, 10000.0, 100000.0, 1000000.0])
+					.const_label("name", prefix.to_string()),
+					&["source", "destination", "channel"],
+				)?,
+				registry,
+			)?,
+			packet_recv_to_ack_latency: register(
+				HistogramVec::new(
+					HistogramOpts::new(
+						"hyperspace_packet_recv_to_ack_latency".to_string(),
+						"Time between a 'write acknowledgement' event and the submission of the acknowledgement message",
+					)
+					.buckets(vec![1.0, 10.0, 100.0, 1000.0, 10000.0, 100000.0, 1000000.0])
+					.const_label("name", prefix.to_string()),
+					&["source", "destination", "channel"],
+				)?,
+				registry,
+			)?,
+			number_of_submitted_timeouts: register(
+				CounterVec::new(
+					Opts::new(
+						"hyperspace_number_of_submitted_timeouts".to_string(),
+						"Total number of submitted timeout messages",
+					)
+					.const_label("name", prefix.to_string()),
+					&["source", "destination", "channel"],
+				)?,
+				registry,
+			)?,
+			number_of_failed_submissions: register(
+				CounterVec::new(
+					Opts::new(
+						"hyperspace_number_of_failed_submissions".to_string(),
+						"Total number of failed message submissions",
+					)
+					.const_label("name", prefix.to_string()),
+					&["destination", "error"],
+				)?,
+				registry,
+			)?,
+			number_of_submission_retries: register(
+				CounterVec::new(
+					Opts::new(
+						"hyperspace_number_of_submission_retries".to_string(),
+						"Total number of retried message submissions",
+					)
+					.const_label("name", prefix.to_string()),
+					&["destination"],
+				)?,
+				registry,
+			)?,
+			number_of_filtered_packets: register(
+				CounterVec::new(
+					Opts::new(
+						"hyperspace_number_of_filtered_packets".to_string(),
+						"Total number of packets skipped because of the packet filters",
+					)
+					.const_label("name", prefix.to_string()),
+					&["source", "destination", "channel"],
+				)?,
+				registry,
+			)?,
+			number_of_denied_packets: register(
+				CounterVec::new(
+					Opts::new(
+						"hyperspace_number_of_denied_packets".to_string(),
+						"Total number of packets denied by the packet filter hooks",
+					)
+					.const_label("name", prefix.to_string()),
+					&["source", "destination", "reason"],
+				)?,
+				registry,
+			)?,
+			undelivered_packets_per_channel: register(
+				GaugeVec::new(
+					Opts::new(
+						"hyperspace_undelivered_packets_per_channel".to_string(),
+						"Number of undelivered packets per channel",
+					)
+					.const_label("name", prefix.to_string()),
+					&["source", "destination", "channel", "port"],
+				)?,
+				registry,
+			)?,
+			undelivered_acknowledgements_per_channel: register(
+				GaugeVec::new(
+					Opts::new(
+						"hyperspace_undelivered_acknowledgements_per_channel".to_string(),
+						"Number of undelivered acknowledgements per channel",
+					)
+					.const_label("name", prefix.to_string()),
+					&["source", "destination", "channel", "port"],
+				)?,
+				registry,
+			)?,
+			oldest_undelivered_packet_age: register(
+				GaugeVec::new(
+					Opts::new(
+						"hyperspace_oldest_undelivered_packet_age_blocks".to_string(),
+						"Age (in source blocks) of the oldest undelivered packet per channel",
+					)
+					.const_label("name", prefix.to_string()),
+					&["source", "destination", "channel", "port"],
+				)?,
+				registry,
+			)?,
+			blocked_ordered_channel_sequence: register(
+				GaugeVec::new(
+					Opts::new(
+						"hyperspace_blocked_ordered_channel_sequence".to_string(),
+						"Sequence of the filtered packet an ordered channel is blocked at",
+					)
+					.const_label("name", prefix.to_string()),
+					&["source", "destination", "channel", "port"],
+				)?,
+				registry,
+			)?,
+			number_of_packets_timed_out_while_pending: register(
+				CounterVec::new(
+					Opts::new(
+						"hyperspace_number_of_packets_timed_out_while_pending".to_string(),
+						"Total number of packets that timed out before they were delivered",
+					)
+					.const_label("name", prefix.to_string()),
+					&["source", "destination", "channel", "port"],
+				)?,
+				registry,
+			)?,
+			number_of_skipped_client_updates: register(
+				CounterVec::new(
+					Opts::new(
+						"hyperspace_number_of_skipped_client_updates".to_string(),
+						"Total number of optional client updates that weren't sent",
+					)
+					.const_label("name", prefix.to_string()),
+					&["source", "destination"],
+				)?,
+				registry,
+			)?,
+			number_of_detected_misbehaviours: register(
+				CounterVec::new(
+					Opts::new(
+						"hyperspace_number_of_detected_misbehaviours".to_string(),
+						"Total number of detected misbehaviours of the counterparty",
+					)
+					.const_label("name", prefix.to_string()),
+					&["source", "destination"],
+				)?,
+				registry,
+			)?,
+			client_seconds_to_expiry: register(
+				GaugeVec::new(
+					Opts::new(
+						"hyperspace_client_seconds_to_expiry".to_string(),
+						"Seconds left until the counterparty client expires",
+					)
+					.const_label("name", prefix.to_string()),
+					&["source", "destination", "client_id"],
+				)?,
+				registry,
+			)?,
 			latest_processed_height: register(
 				Gauge::with_opts(
 					Opts::new(
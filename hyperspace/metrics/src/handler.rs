@@ -17,6 +17,10 @@ use ibc::{
 	core::{
 		ics04_channel::{
 			events::{TimeoutOnClosePacket, TimeoutPacket},
+			msgs::{
+				acknowledgement::MsgAcknowledgement, recv_packet::MsgRecvPacket,
+				timeout::MsgTimeout, timeout_on_close::MsgTimeoutOnClose,
+			},
 			packet::{Packet, Sequence},
 		},
 		ics24_host::identifier::{ChannelId, PortId},
@@ -24,15 +28,16 @@ use ibc::{
 	events::IbcEvent,
 };
 use ibc_proto::google::protobuf::Any;
-use prometheus::{Histogram, Registry};
+use prometheus::{Histogram, HistogramVec, Registry};
 use std::{
-	collections::HashMap,
+	collections::{HashMap, HashSet, VecDeque},
 	ops::DerefMut,
 	sync::{Arc, Mutex},
-	time::Instant,
+	time::{Duration, Instant},
 };
+use tendermint_proto::Protobuf;
 
-#[derive(Eq, PartialEq, Hash)]
+#[derive(Clone, Eq, PartialEq, Hash)]
 pub struct PacketId {
 	pub sequence: Sequence,
 	pub destination_channel: ChannelId,
@@ -51,6 +56,41 @@ impl From<Packet> for PacketId {
 
 pub type PacketMap = Arc<Mutex<HashMap<PacketId, Instant>>>;
 
+/// Maximum number of packets remembered by [`TimedOutPendingPackets`].
+const MAX_TIMED_OUT_PENDING_PACKETS: usize = 10_000;
+
+/// Packets counted as timed out while pending, until their timeout is observed. At most
+/// [`MAX_TIMED_OUT_PENDING_PACKETS`] are remembered, the oldest ones are forgotten first, so a
+/// packet whose timeout is never observed may be counted again.
+#[derive(Default)]
+struct TimedOutPendingPackets {
+	order: VecDeque<PacketId>,
+	packets: HashSet<PacketId>,
+}
+
+impl TimedOutPendingPackets {
+	/// Returns `false` if the packet was already counted.
+	fn insert(&mut self, packet: PacketId) -> bool {
+		if !self.packets.insert(packet.clone()) {
+			return false
+		}
+		self.order.push_back(packet);
+		while self.order.len() > MAX_TIMED_OUT_PENDING_PACKETS {
+			if let Some(oldest) = self.order.pop_front() {
+				self.packets.remove(&oldest);
+			}
+		}
+		true
+	}
+
+	fn remove(&mut self, packet: &PacketId) {
+		if self.packets.remove(packet) {
+			self.order.retain(|pending| pending != packet);
+		}
+	}
+}
+
+#[derive(Clone)]
 pub struct MetricsHandler {
 	registry: Registry,
 	metrics: Metrics,
@@ -59,10 +99,13 @@ pub struct MetricsHandler {
 	last_sent_acknowledgment_time: PacketMap,
 	last_sent_timeout_packet_time: PacketMap,
 	last_update_client_time: Arc<Mutex<Option<Instant>>>,
+	/// Packets counted as timed out while pending, until their timeout is observed
+	timed_out_pending_packets: Arc<Mutex<TimedOutPendingPackets>>,
 
 	counterparty_last_sent_packet_time: Option<PacketMap>,
 	counterparty_last_sent_acknowledgment_time: Option<PacketMap>,
 	counterparty_last_sent_timeout_packet_time: Option<PacketMap>,
+	counterparty_prefix: Option<String>,
 }
 
 impl MetricsHandler {
@@ -74,9 +117,11 @@ impl MetricsHandler {
 			last_sent_acknowledgment_time: Arc::new(Mutex::new(HashMap::new())),
 			last_sent_timeout_packet_time: Arc::new(Mutex::new(HashMap::new())),
 			last_update_client_time: Arc::new(Mutex::new(None)),
+			timed_out_pending_packets: Default::default(),
 			counterparty_last_sent_packet_time: None,
 			counterparty_last_sent_acknowledgment_time: None,
 			counterparty_last_sent_timeout_packet_time: None,
+			counterparty_prefix: None,
 		}
 	}
 
@@ -140,6 +185,7 @@ impl MetricsHandler {
 				IbcEvent::TimeoutPacket(TimeoutPacket { packet, .. }) |
 				IbcEvent::TimeoutOnClosePacket(TimeoutOnClosePacket { packet, .. }) => {
 					self.metrics.number_of_received_timeouts.inc();
+					self.timed_out_pending_packets.lock().unwrap().remove(&packet.clone().into());
 					self.observe_last_packet_time(
 						packet,
 						&self.counterparty_last_sent_timeout_packet_time,
@@ -170,6 +216,13 @@ impl MetricsHandler {
 		for message in messages {
 			match message.type_url.as_str() {
 				"/ibc.core.channel.v1.MsgAcknowledgement" => {
+					if let Ok(msg) = MsgAcknowledgement::decode_vec(&message.value) {
+						self.observe_packet_latency(
+							&msg.packet,
+							&self.last_sent_acknowledgment_time,
+							&self.metrics.packet_recv_to_ack_latency,
+						);
+					}
 					self.metrics.number_of_sent_acknowledgments.inc();
 					// The counters may be out of sync (e.g. when relayer was restarted), so we use
 					// saturating sub
@@ -182,6 +235,13 @@ impl MetricsHandler {
 						.set(number_of_undelivered_acknowledgements);
 				},
 				"/ibc.core.channel.v1.MsgRecvPacket" => {
+					if let Ok(msg) = MsgRecvPacket::decode_vec(&message.value) {
+						self.observe_packet_latency(
+							&msg.packet,
+							&self.last_sent_packet_time,
+							&self.metrics.packet_send_to_recv_latency,
+						);
+					}
 					self.metrics.number_of_undelivered_packets.set(
 						self.metrics.number_of_sent_packets.get().saturating_sub(
 							self.metrics.counterparty_number_of_received_packets().get(),
@@ -208,6 +268,9 @@ impl MetricsHandler {
 			Some(self.last_sent_acknowledgment_time.clone());
 		counterparty.counterparty_last_sent_timeout_packet_time =
 			Some(self.last_sent_timeout_packet_time.clone());
+
+		self.counterparty_prefix = Some(counterparty.metrics.prefix.clone());
+		counterparty.counterparty_prefix = Some(self.metrics.prefix.clone());
 	}
 
 	pub async fn handle_timeouts(&self, timeouts: &[Any]) {
@@ -215,12 +278,153 @@ impl MetricsHandler {
 			match message.type_url.as_str() {
 				"/ibc.core.channel.v1.MsgTimeout" | "/ibc.core.channel.v1.MsgTimeoutOnClose" => {
 					self.metrics.number_of_sent_timeout_packets.inc();
+					let packet = match message.type_url.as_str() {
+						"/ibc.core.channel.v1.MsgTimeout" =>
+							MsgTimeout::decode_vec(&message.value).ok().map(|msg| msg.packet),
+						_ =>
+							MsgTimeoutOnClose::decode_vec(&message.value).ok().map(|msg| msg.packet),
+					};
+					if let Some(packet) = packet {
+						let channel = packet.source_channel.to_string();
+						self.metrics
+							.number_of_submitted_timeouts
+							.with_label_values(&[
+								&self.metrics.prefix,
+								self.counterparty_prefix(),
+								&channel,
+							])
+							.inc();
+					}
 				},
 				_ => (),
 			}
 		}
 	}
 
+	/// Records a failed submission of messages to `destination`. The error class is used as a
+	/// label, so it should have a small number of possible values.
+	pub fn handle_failed_submission(&self, destination: &str, error_class: &str) {
+		self.metrics
+			.number_of_failed_submissions
+			.with_label_values(&[destination, error_class])
+			.inc();
+	}
+
+	/// Records a retry of a failed submission of messages to `destination`.
+	pub fn handle_submission_retry(&self, destination: &str) {
+		self.metrics
+			.number_of_submission_retries
+			.with_label_values(&[destination])
+			.inc();
+	}
+
+	/// Records a packet that wasn't relayed to the counterparty because of the packet filters.
+	pub fn handle_filtered_packet(&self, packet: &Packet) {
+		let channel = packet.source_channel.to_string();
+		self.metrics
+			.number_of_filtered_packets
+			.with_label_values(&[&self.metrics.prefix, self.counterparty_prefix(), &channel])
+			.inc();
+	}
+
+	/// Records a packet that wasn't relayed to the counterparty because a packet filter hook
+	/// denied it.
+	pub fn handle_denied_packet(&self, reason: &str) {
+		self.metrics
+			.number_of_denied_packets
+			.with_label_values(&[&self.metrics.prefix, self.counterparty_prefix(), reason])
+			.inc();
+	}
+
+	/// Records an optional client update that wasn't sent to the counterparty.
+	pub fn handle_skipped_client_update(&self) {
+		self.metrics
+			.number_of_skipped_client_updates
+			.with_label_values(&[&self.metrics.prefix, self.counterparty_prefix()])
+			.inc();
+	}
+
+	/// Records a misbehaviour of the counterparty detected in its client updates on this chain.
+	pub fn handle_misbehaviour(&self) {
+		self.metrics
+			.number_of_detected_misbehaviours
+			.with_label_values(&[&self.metrics.prefix, self.counterparty_prefix()])
+			.inc();
+	}
+
+	/// Records the time left until the client of this chain on the counterparty expires.
+	pub fn handle_client_expiry(&self, client_id: &str, remaining: Duration) {
+		self.metrics
+			.client_seconds_to_expiry
+			.with_label_values(&[&self.metrics.prefix, self.counterparty_prefix(), client_id])
+			.set(remaining.as_secs());
+	}
+
+	/// Records the undelivered packets of a channel, and the age (in source blocks) of the oldest
+	/// one.
+	pub fn handle_undelivered_packets(
+		&self,
+		channel_id: &ChannelId,
+		port_id: &PortId,
+		count: usize,
+		oldest_packet_age: u64,
+	) {
+		let labels = self.channel_labels(channel_id, port_id);
+		let labels = labels.iter().map(String::as_str).collect::<Vec<_>>();
+		self.metrics
+			.undelivered_packets_per_channel
+			.with_label_values(&labels)
+			.set(count as u64);
+		self.metrics
+			.oldest_undelivered_packet_age
+			.with_label_values(&labels)
+			.set(oldest_packet_age);
+	}
+
+	/// Records the undelivered acknowledgements of a channel.
+	pub fn handle_undelivered_acknowledgements(
+		&self,
+		channel_id: &ChannelId,
+		port_id: &PortId,
+		count: usize,
+	) {
+		let labels = self.channel_labels(channel_id, port_id);
+		let labels = labels.iter().map(String::as_str).collect::<Vec<_>>();
+		self.metrics
+			.undelivered_acknowledgements_per_channel
+			.with_label_values(&labels)
+			.set(count as u64);
+	}
+
+	/// Records the sequence of the filtered packet an ordered channel is blocked at, or 0 if the
+	/// channel isn't blocked.
+	pub fn handle_blocked_ordered_channel(
+		&self,
+		channel_id: &ChannelId,
+		port_id: &PortId,
+		sequence: u64,
+	) {
+		let labels = self.channel_labels(channel_id, port_id);
+		let labels = labels.iter().map(String::as_str).collect::<Vec<_>>();
+		self.metrics
+			.blocked_ordered_channel_sequence
+			.with_label_values(&labels)
+			.set(sequence);
+	}
+
+	/// Records a packet that timed out before it was delivered. Each packet is only counted once.
+	pub fn handle_packet_timed_out_while_pending(&self, packet: &Packet) {
+		if !self.timed_out_pending_packets.lock().unwrap().insert(packet.clone().into()) {
+			return
+		}
+		let labels = self.channel_labels(&packet.source_channel, &packet.source_port);
+		let labels = labels.iter().map(String::as_str).collect::<Vec<_>>();
+		self.metrics
+			.number_of_packets_timed_out_while_pending
+			.with_label_values(&labels)
+			.inc();
+	}
+
 	pub async fn handle_transaction_costs(&self, batch_weight: u64, messages: &[Any]) {
 		let batch_size = messages.iter().map(|x| x.value.len()).sum::<usize>();
 		self.metrics.gas_cost_for_sent_tx_bundle.observe(batch_weight as f64);
@@ -245,6 +449,38 @@ impl MetricsHandler {
 			log::warn!("No last time found for packet {:?}", packet);
 		}
 	}
+
+	/// Observes the time since the packet was put into the `map` (when the corresponding event was
+	/// received). The metrics are labelled with the source and destination chain names and the
+	/// source channel of the packet.
+	fn observe_packet_latency(
+		&self,
+		packet: &Packet,
+		map: &PacketMap,
+		time_metrics: &HistogramVec,
+	) {
+		let now = Instant::now();
+		if let Some(last_time) = map.lock().unwrap().get(&packet.clone().into()) {
+			let elapsed = now.duration_since(*last_time);
+			let channel = packet.source_channel.to_string();
+			time_metrics
+				.with_label_values(&[&self.metrics.prefix, self.counterparty_prefix(), &channel])
+				.observe(elapsed.as_millis() as f64);
+		}
+	}
+
+	fn channel_labels(&self, channel_id: &ChannelId, port_id: &PortId) -> [String; 4] {
+		[
+			self.metrics.prefix.clone(),
+			self.counterparty_prefix().to_string(),
+			channel_id.to_string(),
+			port_id.to_string(),
+		]
+	}
+
+	fn counterparty_prefix(&self) -> &str {
+		self.counterparty_prefix.as_deref().unwrap_or_default()
+	}
 }
 
 fn observe_delta_time(maybe_time: &mut Option<Instant>, time_metrics: &Histogram) {
@@ -18,7 +18,7 @@ use anyhow::anyhow;
 use codec::{Decode, Encode};
 use finality_grandpa::BlockNumberOps;
 use finality_grandpa_rpc::GrandpaApiClient;
-use futures::{Stream, StreamExt, TryFutureExt};
+use futures::{Stream, StreamExt};
 use grandpa_light_client_primitives::{FinalityProof, ParachainHeaderProofs};
 use ibc::{
 	core::{
@@ -29,6 +29,7 @@ use ibc::{
 		ics26_routing::msgs::Ics26Envelope,
 	},
 	events::IbcEvent,
+	signer::Signer,
 	tx_msg::Msg,
 	Height,
 };
@@ -39,10 +40,14 @@ use jsonrpsee_ws_client::WsClientBuilder;
 use light_client_common::config::{EventRecordT, RuntimeCall, RuntimeTransactions};
 use pallet_ibc::light_clients::AnyClientMessage;
 use primitives::{
-	mock::LocalClientTypes, Chain, CommonClientState, IbcProvider, MisbehaviourHandler,
+	mock::LocalClientTypes, Chain, CommonClientState, FeeEstimate, IbcProvider, KeyProvider,
+	MisbehaviourHandler, SubmissionErrorClass,
 };
 use sc_consensus_beefy_rpc::BeefyApiClient;
-use sp_core::{twox_128, H256};
+use sp_core::{
+	crypto::{AccountId32, Ss58Codec},
+	twox_128, H256,
+};
 use sp_runtime::{
 	traits::{IdentifyAccount, One, Verify},
 	MultiSignature, MultiSigner,
@@ -103,39 +108,15 @@ where
 	}
 
 	async fn estimate_weight(&self, messages: Vec<Any>) -> Result<u64, Self::Error> {
-		let extrinsic = {
-			// todo: put this in utils
-			let signer = ExtrinsicSigner::<T, Self>::new(
-				self.key_store.clone(),
-				self.key_type_id.clone(),
-				self.public_key.clone(),
-			);
-
-			let messages = messages
-				.into_iter()
-				.map(|msg| Any { type_url: msg.type_url.clone(), value: msg.value })
-				.collect::<Vec<_>>();
-
-			let tx_params = BaseExtrinsicParamsBuilder::new()
-				.tip(T::Tip::from(100_000u128))
-				.era(Era::Immortal, self.para_client.genesis_hash());
-			let call = T::Tx::ibc_deliver(messages);
-			self.para_client
-				.tx()
-				.create_signed(&call, &signer, tx_params.into())
-				.await?
-				.encoded()
-				.to_vec()
-		};
-		let dispatch_info = TransactionPaymentApiClient::<
-			H256,
-			RuntimeDispatchInfo<u128, sp_weights::Weight>,
-		>::query_info(&*self.para_ws_client, extrinsic.into(), None)
-		.await
-		.map_err(|e| Error::from(format!("Rpc Error From Estimating weight {:?}", e)))?;
+		let dispatch_info = self.query_deliver_dispatch_info(messages).await?;
 		Ok(dispatch_info.weight.ref_time())
 	}
 
+	async fn estimate_fee(&self, msgs: &[Any]) -> Result<FeeEstimate, Self::Error> {
+		let dispatch_info = self.query_deliver_dispatch_info(msgs.to_vec()).await?;
+		Ok(FeeEstimate { amount: dispatch_info.partial_fee, denom: None })
+	}
+
 	async fn finality_notifications(
 		&self,
 	) -> Result<
@@ -323,6 +304,17 @@ where
 		block_height
 	}
 
+	fn submission_error_class(&self, error: &Error) -> SubmissionErrorClass {
+		match error {
+			Error::RpcError(_) |
+			Error::SubxtRRpc(_) |
+			Error::JosnrpseeError(_) |
+			Error::Subxt(subxt::Error::Rpc(_)) => SubmissionErrorClass::Rpc,
+			Error::InBlockTimeout(_) => SubmissionErrorClass::Timeout,
+			_ => SubmissionErrorClass::Other,
+		}
+	}
+
 	async fn handle_error(&mut self, error: &anyhow::Error) -> Result<(), anyhow::Error> {
 		let err_str = if let Some(rpc_err) = error.downcast_ref::<Error>() {
 			match rpc_err {
@@ -378,6 +370,87 @@ where
 	fn common_state_mut(&mut self) -> &mut CommonClientState {
 		&mut self.common_state
 	}
+
+	fn validate_signer(&self) -> Result<Signer, Self::Error> {
+		let signer = self.account_id();
+		let (_, format) =
+			AccountId32::from_ss58check_with_version(signer.as_ref()).map_err(|e| {
+				Error::from(format!("Invalid signer {} for {}: {e:?}", signer, self.name))
+			})?;
+		if format != self.ss58_version {
+			return Err(Error::from(format!(
+				"Invalid signer {} for {}: expected ss58 prefix {}, found {}",
+				signer,
+				self.name,
+				self.ss58_version.prefix(),
+				format.prefix()
+			)))
+		}
+		Ok(signer)
+	}
+}
+
+impl<T: light_client_common::config::Config + Send + Sync + Clone + 'static> ParachainClient<T>
+where
+	u32: From<<<T as subxt::Config>::Header as HeaderT>::Number>,
+	Self: KeyProvider,
+	u32: From<<<T as subxt::Config>::Header as Header>::Number>,
+	<<T as light_client_common::config::Config>::Signature as Verify>::Signer:
+		From<MultiSigner> + IdentifyAccount<AccountId = T::AccountId>,
+	MultiSigner: From<MultiSigner>,
+	<T as subxt::Config>::Address: From<<T as subxt::Config>::AccountId>,
+	<T as subxt::Config>::Signature: From<MultiSignature> + Send + Sync,
+	<<T as subxt::Config>::Header as Header>::Number:
+		BlockNumberOps + From<u32> + Display + Ord + sp_runtime::traits::Zero + One + Send + Sync,
+	<T as subxt::Config>::Header: Decode + Send + Sync + Clone,
+	T::Hash: From<sp_core::H256> + From<[u8; 32]>,
+	BTreeMap<sp_core::H256, ParachainHeaderProofs>:
+		From<BTreeMap<<T as subxt::Config>::Hash, ParachainHeaderProofs>>,
+	sp_core::H256: From<T::Hash>,
+	<T::ExtrinsicParams as ExtrinsicParams<T::Index, T::Hash>>::OtherParams:
+		From<BaseExtrinsicParamsBuilder<T, T::Tip>> + Send + Sync,
+	<T as subxt::Config>::AccountId: Send + Sync,
+	<T as subxt::Config>::Address: Send + Sync,
+	<T as light_client_common::config::Config>::AssetId: Clone,
+{
+	/// Queries the weight and the fee (excluding the tip) of an `ibc_deliver` extrinsic with the
+	/// messages.
+	async fn query_deliver_dispatch_info(
+		&self,
+		messages: Vec<Any>,
+	) -> Result<RuntimeDispatchInfo<u128, sp_weights::Weight>, Error> {
+		let extrinsic = {
+			// todo: put this in utils
+			let signer = ExtrinsicSigner::<T, Self>::new(
+				self.key_store.clone(),
+				self.key_type_id.clone(),
+				self.public_key.clone(),
+			);
+
+			let messages = messages
+				.into_iter()
+				.map(|msg| Any { type_url: msg.type_url.clone(), value: msg.value })
+				.collect::<Vec<_>>();
+
+			let tx_params = BaseExtrinsicParamsBuilder::new()
+				.tip(T::Tip::from(100_000u128))
+				.era(Era::Immortal, self.para_client.genesis_hash());
+			let call = T::Tx::ibc_deliver(messages);
+			self.para_client
+				.tx()
+				.create_signed(&call, &signer, tx_params.into())
+				.await?
+				.encoded()
+				.to_vec()
+		};
+		let dispatch_info = TransactionPaymentApiClient::<
+			H256,
+			RuntimeDispatchInfo<u128, sp_weights::Weight>,
+		>::query_info(&*self.para_ws_client, extrinsic.into(), None)
+		.await
+		.map_err(|e| Error::from(format!("Rpc Error From Estimating weight {:?}", e)))?;
+		Ok(dispatch_info)
+	}
 }
 
 #[async_trait::async_trait]
@@ -406,7 +479,7 @@ where
 		&self,
 		counterparty: &C,
 		client_message: AnyClientMessage,
-	) -> Result<(), anyhow::Error> {
+	) -> Result<Option<Any>, anyhow::Error> {
 		let client_message = client_message.unpack_recursive_into();
 		match client_message {
 			AnyClientMessage::Grandpa(ClientMessage::Header(header)) => {
@@ -501,19 +574,17 @@ where
 						second_finality_proof: trusted_finality_proof,
 					});
 
-					counterparty
-						.submit(vec![MsgUpdateAnyClient::<LocalClientTypes>::new(
-							self.client_id(),
-							AnyClientMessage::Grandpa(misbehaviour.clone()),
-							counterparty.account_id(),
-						)
-						.to_any()])
-						.map_err(|e| anyhow!("Failed to submit misbehaviour report: {:?}", e))
-						.await?;
+					let msg = MsgUpdateAnyClient::<LocalClientTypes>::new(
+						self.client_id(),
+						AnyClientMessage::Grandpa(misbehaviour),
+						counterparty.account_id(),
+					)
+					.to_any();
+					return Ok(Some(msg))
 				}
 			},
 			_ => {},
 		}
-		Ok(())
+		Ok(None)
 	}
 }
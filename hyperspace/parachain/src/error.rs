@@ -80,6 +80,9 @@ pub enum Error {
 	MetadataError(#[from] MetadataError),
 	#[error("Jsonrpsee error: {0}")]
 	JosnrpseeError(#[from] jsonrpsee::core::Error),
+	/// The extrinsic wasn't included in a block in time
+	#[error("Extrinsic wasn't included in a block after {0:?}")]
+	InBlockTimeout(std::time::Duration),
 }
 
 impl From<String> for Error {
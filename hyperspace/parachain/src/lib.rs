@@ -63,7 +63,10 @@ use jsonrpsee_ws_client::WsClientBuilder;
 use light_client_common::config::{AsInner, RuntimeStorage};
 use pallet_ibc::light_clients::{AnyClientState, AnyConsensusState, HostFunctionsManager};
 use pallet_mmr_primitives::Proof;
-use primitives::{CommonClientState, KeyProvider};
+use primitives::{
+	channel_filter::{ChannelFilter, ChannelFilterEntry},
+	CommonClientState, KeyProvider,
+};
 use sc_keystore::LocalKeystore;
 use sp_core::{ecdsa, ed25519, sr25519, Bytes, Pair, H256};
 use sp_keystore::KeystorePtr;
@@ -178,8 +181,9 @@ pub struct ParachainClientConfig {
 	pub private_key: String,
 	/// used for encoding relayer address.
 	pub ss58_version: u8,
-	/// Channels cleared for packet relay
-	pub channel_whitelist: Vec<(ChannelId, PortId)>,
+	/// Channels cleared for packet relay, either `[channel, port]` pairs, `["*", port]` for all
+	/// the channels on the port, or `"*"` for all the channels
+	pub channel_whitelist: Vec<ChannelFilterEntry>,
 	/// Finality protocol
 	pub finality_protocol: FinalityProtocol,
 	/// Digital signature scheme
@@ -242,6 +246,7 @@ where
 			.unwrap();
 
 		assert!(key_store.has_keys(&[(public_key.as_ref().to_vec(), key_type_id)]));
+		let channel_filter = ChannelFilter::new(config.channel_whitelist);
 		Ok(Self {
 			name: config.name,
 			parachain_rpc_url: config.parachain_rpc_url,
@@ -259,7 +264,7 @@ where
 			para_ws_client,
 			relay_ws_client,
 			ss58_version: Ss58AddressFormat::from(config.ss58_version),
-			channel_whitelist: Arc::new(Mutex::new(config.channel_whitelist.into_iter().collect())),
+			channel_whitelist: Arc::new(Mutex::new(channel_filter.channels())),
 			finality_protocol: config.finality_protocol,
 			common_state: CommonClientState {
 				skip_optional_client_updates: true,
@@ -267,6 +272,7 @@ where
 				rpc_call_delay: DEFAULT_RPC_CALL_DELAY,
 				initial_rpc_call_delay: DEFAULT_RPC_CALL_DELAY,
 				misbehaviour_client_msg_queue: Arc::new(AsyncMutex::new(vec![])),
+				channel_filter,
 				..Default::default()
 			},
 		})
@@ -440,9 +446,7 @@ where
 		let tx_in_block =
 			tokio::time::timeout(WAIT_FOR_IN_BLOCK_TIMEOUT, progress.wait_for_in_block())
 				.await
-				.map_err(|e| {
-					Error::from(format!("[submit_call] Failed to wait for in block due to {:?}", e))
-				})??;
+				.map_err(|_| Error::InBlockTimeout(WAIT_FOR_IN_BLOCK_TIMEOUT))??;
 		tx_in_block.wait_for_success().await?;
 		Ok((tx_in_block.extrinsic_hash(), tx_in_block.block_hash()))
 	}
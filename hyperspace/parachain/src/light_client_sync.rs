@@ -319,6 +319,8 @@ where
 		.filter(|e| {
 			let mut channel_and_port_ids = source.channel_whitelist();
 			channel_and_port_ids.extend(counterparty.channel_whitelist());
+			let mut channel_filter = source.common_state().channel_filter.clone();
+			channel_filter.extend(&counterparty.common_state().channel_filter);
 			filter_events_by_ids(
 				e,
 				&[source.client_id(), counterparty.client_id()],
@@ -327,6 +329,7 @@ where
 					.flatten()
 					.collect::<Vec<_>>(),
 				&channel_and_port_ids,
+				&channel_filter,
 			)
 		})
 		.collect();
@@ -23,6 +23,7 @@ use ibc::{
 	applications::transfer::{Amount, PrefixedCoin, PrefixedDenom},
 	core::{
 		ics02_client::client_state::{ClientState, ClientType},
+		ics04_channel::packet::Packet,
 		ics23_commitment::commitment::CommitmentPrefix,
 		ics24_host::identifier::{ChannelId, ClientId, ConnectionId, PortId},
 	},
@@ -51,7 +52,10 @@ use pallet_ibc::{
 	light_clients::{AnyClientState, AnyConsensusState, HostFunctionsManager},
 	HostConsensusProof,
 };
-use primitives::{apply_prefix, Chain, IbcProvider, KeyProvider, UpdateType};
+use primitives::{
+	key_path::{get_prefixed_key_path, KeyPathType},
+	Chain, IbcProvider, KeyProvider, UpdateType,
+};
 use sp_core::H256;
 use sp_runtime::{
 	traits::{IdentifyAccount, One, Verify},
@@ -126,6 +130,24 @@ where
 			.await
 	}
 
+	async fn finality_event_height(&self, finality_event: &Self::FinalityEvent) -> Option<Height> {
+		match finality_event {
+			FinalityEvent::Grandpa(justification) => {
+				let prover = self.grandpa_prover();
+				let header = prover
+					.query_latest_finalized_parachain_header(justification.commit.target_number)
+					.await
+					.map_err(|e| {
+						log::warn!(target: "hyperspace_parachain", "Failed to query the parachain header finalized at {}: {e:?}", justification.commit.target_number)
+					})
+					.ok()?;
+				Some(Height::new(self.para_id as u64, u32::from(header.number()) as u64))
+			},
+			// the updates of the BEEFY client don't have a height yet
+			FinalityEvent::Beefy(_) => None,
+		}
+	}
+
 	async fn ibc_events(&self) -> Pin<Box<dyn Stream<Item = IbcEvent> + Send + 'static>> {
 		use futures::StreamExt;
 
@@ -247,18 +269,20 @@ where
 		Ok(response)
 	}
 
+	/// The keys of the runtime storage are prefixed with the connection prefix.
+	fn full_key_path(&self, key_path_type: KeyPathType, packet: &Packet) -> Vec<u8> {
+		get_prefixed_key_path(&self.connection_prefix(), key_path_type, packet)
+	}
+
 	/// Query the proof of the given keys at the given height.
 	///
-	/// Note: all the keys will be prefixed with the connection prefix.
+	/// Note: the keys must be prefixed with the connection prefix already, see
+	/// [`IbcProvider::full_key_path`].
 	async fn query_proof(&self, at: Height, keys: Vec<Vec<u8>>) -> Result<Vec<u8>, Self::Error> {
-		let prefix = self.connection_prefix().into_vec();
-		let prefixed_keys =
-			keys.into_iter().map(|path| apply_prefix(prefix.clone(), path)).collect();
-
 		let proof = IbcApiClient::<u32, H256, <T as light_client_common::config::Config>::AssetId>::query_proof(
 			&*self.para_ws_client,
 			at.revision_height as u32,
-			prefixed_keys,
+			keys,
 		)
 		.await
 		.map_err(|e| Error::from(format!("Rpc Error {:?}", e)))?;
@@ -642,6 +666,12 @@ where
 		Ok(timestamp_nanos)
 	}
 
+	async fn get_block(&self, height: Height) -> Result<Option<Vec<u8>>, Self::Error> {
+		let subxt_block_number: subxt::rpc::types::BlockNumber = height.revision_height.into();
+		let block_hash = self.para_client.rpc().block_hash(Some(subxt_block_number)).await?;
+		Ok(block_hash.map(|block_hash| block_hash.encode()))
+	}
+
 	async fn query_clients(&self) -> Result<Vec<ClientId>, Self::Error> {
 		let response: Vec<IdentifiedClientState> = IbcApiClient::<
 			u32,
@@ -670,10 +700,15 @@ where
 			.into_iter()
 			.map(|identified_chan| {
 				Ok((
-					ChannelId::from_str(&identified_chan.channel_id)
-						.expect("Failed to convert invalid string to channel id"),
-					PortId::from_str(&identified_chan.port_id)
-						.expect("Failed to convert invalid string to port id"),
+					ChannelId::from_str(&identified_chan.channel_id).map_err(|e| {
+						Error::Custom(format!(
+							"Invalid channel id {}: {e}",
+							identified_chan.channel_id
+						))
+					})?,
+					PortId::from_str(&identified_chan.port_id).map_err(|e| {
+						Error::Custom(format!("Invalid port id {}: {e}", identified_chan.port_id))
+					})?,
 				))
 			})
 			.collect::<Result<Vec<_>, _>>()
@@ -0,0 +1,196 @@
+// Copyright 2022 ComposableFi
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ibc::core::ics24_host::identifier::{ChannelId, PortId};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{collections::HashSet, str::FromStr};
+
+const WILDCARD: &str = "*";
+
+/// An entry of the channel whitelist.
+///
+/// In the config, an entry is either a `[channel, port]` pair, a `["*", port]` pair that matches
+/// all the channels on the port, or a `"*"` string that matches all the channels.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ChannelFilterEntry {
+	/// The given channel on the given port
+	Channel(ChannelId, PortId),
+	/// All the channels on the given port
+	Port(PortId),
+	/// All the channels
+	All,
+}
+
+impl ChannelFilterEntry {
+	/// Returns `true` if the channel matches the entry.
+	pub fn matches(&self, channel_id: &ChannelId, port_id: &PortId) -> bool {
+		match self {
+			Self::Channel(channel, port) => channel == channel_id && port == port_id,
+			Self::Port(port) => port == port_id,
+			Self::All => true,
+		}
+	}
+}
+
+impl From<(ChannelId, PortId)> for ChannelFilterEntry {
+	fn from((channel_id, port_id): (ChannelId, PortId)) -> Self {
+		Self::Channel(channel_id, port_id)
+	}
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum RawChannelFilterEntry {
+	All(String),
+	Pair(String, String),
+}
+
+impl Serialize for ChannelFilterEntry {
+	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+	where
+		S: Serializer,
+	{
+		let raw = match self {
+			Self::Channel(channel_id, port_id) =>
+				RawChannelFilterEntry::Pair(channel_id.to_string(), port_id.to_string()),
+			Self::Port(port_id) =>
+				RawChannelFilterEntry::Pair(WILDCARD.to_string(), port_id.to_string()),
+			Self::All => RawChannelFilterEntry::All(WILDCARD.to_string()),
+		};
+		raw.serialize(serializer)
+	}
+}
+
+impl<'de> Deserialize<'de> for ChannelFilterEntry {
+	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+	where
+		D: Deserializer<'de>,
+	{
+		match RawChannelFilterEntry::deserialize(deserializer)? {
+			RawChannelFilterEntry::All(wildcard) if wildcard == WILDCARD => Ok(Self::All),
+			RawChannelFilterEntry::All(other) => Err(de::Error::custom(format!(
+				"expected a [channel, port] pair or \"{WILDCARD}\", got \"{other}\""
+			))),
+			RawChannelFilterEntry::Pair(channel, port) => {
+				let port_id = PortId::from_str(&port).map_err(de::Error::custom)?;
+				if channel == WILDCARD {
+					Ok(Self::Port(port_id))
+				} else {
+					let channel_id = ChannelId::from_str(&channel).map_err(de::Error::custom)?;
+					Ok(Self::Channel(channel_id, port_id))
+				}
+			},
+		}
+	}
+}
+
+/// The configured channel whitelist. A channel is relayed if it matches any of the entries, so
+/// an explicit entry never restricts a wildcard one.
+///
+/// Channels matching the wildcard entries are added to the chain's channel whitelist once they
+/// are found on the relayed connection, see [`crate::resolve_channel_whitelist`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChannelFilter {
+	entries: Vec<ChannelFilterEntry>,
+}
+
+impl ChannelFilter {
+	pub fn new(entries: Vec<ChannelFilterEntry>) -> Self {
+		Self { entries }
+	}
+
+	/// Returns `true` if the channel matches any of the entries.
+	pub fn matches(&self, channel_id: &ChannelId, port_id: &PortId) -> bool {
+		self.entries.iter().any(|entry| entry.matches(channel_id, port_id))
+	}
+
+	/// Returns `true` if there are entries matching channels that aren't known in advance.
+	pub fn has_wildcards(&self) -> bool {
+		self.entries
+			.iter()
+			.any(|entry| !matches!(entry, ChannelFilterEntry::Channel(..)))
+	}
+
+	/// Returns the explicitly whitelisted channels.
+	pub fn channels(&self) -> HashSet<(ChannelId, PortId)> {
+		self.entries
+			.iter()
+			.filter_map(|entry| match entry {
+				ChannelFilterEntry::Channel(channel_id, port_id) =>
+					Some((*channel_id, port_id.clone())),
+				_ => None,
+			})
+			.collect()
+	}
+
+	/// Adds the entries of the other filter.
+	pub fn extend(&mut self, other: &ChannelFilter) {
+		self.entries.extend(other.entries.iter().cloned());
+	}
+}
+
+#[cfg(test)]
+pub mod tests {
+	use super::{ChannelFilter, ChannelFilterEntry};
+	use ibc::core::ics24_host::identifier::{ChannelId, PortId};
+	use std::str::FromStr;
+
+	#[test]
+	fn deserializes_exact_pairs_and_wildcards() {
+		let entries: Vec<ChannelFilterEntry> =
+			serde_json::from_str(r#"[["channel-0", "transfer"], ["*", "oracle"], "*"]"#).unwrap();
+		assert_eq!(
+			entries,
+			vec![
+				ChannelFilterEntry::Channel(ChannelId::new(0), PortId::transfer()),
+				ChannelFilterEntry::Port(PortId::from_str("oracle").unwrap()),
+				ChannelFilterEntry::All,
+			]
+		);
+		assert_eq!(
+			serde_json::to_string(&entries).unwrap(),
+			r#"[["channel-0","transfer"],["*","oracle"],"*"]"#
+		);
+		assert!(serde_json::from_str::<ChannelFilterEntry>(r#""channel-0""#).is_err());
+	}
+
+	#[test]
+	fn wildcard_and_explicit_entries_are_combined() {
+		let oracle = PortId::from_str("oracle").unwrap();
+		let filter = ChannelFilter::new(vec![
+			ChannelFilterEntry::Port(PortId::transfer()),
+			ChannelFilterEntry::Channel(ChannelId::new(1), PortId::transfer()),
+			ChannelFilterEntry::Channel(ChannelId::new(5), oracle.clone()),
+		]);
+
+		// the explicit entry doesn't restrict the port wildcard to `channel-1`
+		assert!(filter.matches(&ChannelId::new(1), &PortId::transfer()));
+		assert!(filter.matches(&ChannelId::new(2), &PortId::transfer()));
+		// the explicit entry still applies to a port the wildcard doesn't cover
+		assert!(filter.matches(&ChannelId::new(5), &oracle));
+		assert!(!filter.matches(&ChannelId::new(6), &oracle));
+		assert!(filter.has_wildcards());
+		assert_eq!(
+			filter.channels(),
+			[(ChannelId::new(1), PortId::transfer()), (ChannelId::new(5), oracle.clone())]
+				.into_iter()
+				.collect()
+		);
+
+		// the global wildcard matches everything
+		let mut filter = filter;
+		filter.extend(&ChannelFilter::new(vec![ChannelFilterEntry::All]));
+		assert!(filter.matches(&ChannelId::new(6), &oracle));
+	}
+}
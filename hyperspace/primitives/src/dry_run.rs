@@ -0,0 +1,173 @@
+// Copyright 2022 ComposableFi
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::mock::LocalClientTypes;
+use ibc::{
+	core::{
+		ics02_client::msgs::update_client::{
+			MsgUpdateAnyClient, TYPE_URL as UPDATE_CLIENT_TYPE_URL,
+		},
+		ics04_channel::msgs::{
+			acknowledgement::{MsgAcknowledgement, TYPE_URL as ACK_TYPE_URL},
+			recv_packet::{MsgRecvPacket, TYPE_URL as RECV_TYPE_URL},
+			timeout::{MsgTimeout, TYPE_URL as TIMEOUT_TYPE_URL},
+			timeout_on_close::{MsgTimeoutOnClose, TYPE_URL as TIMEOUT_ON_CLOSE_TYPE_URL},
+		},
+	},
+	protobuf::Protobuf,
+};
+use ibc_proto::{cosmos::tx::v1beta1::TxBody, google::protobuf::Any};
+use prost::Message;
+use std::{
+	path::PathBuf,
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc,
+	},
+};
+
+/// Outgoing batches are printed and written to a directory instead of being submitted.
+///
+/// Each batch is written to `{dir}/{index}-{chain}.bin` as a protobuf encoded `TxBody`, the index
+/// is shared by all the chains, so the files sort in the order the batches were built. The
+/// messages are written as built by the relayer, before any chain-specific wrapping.
+#[derive(Debug, Clone)]
+pub struct DryRun {
+	dir: PathBuf,
+	batches: Arc<AtomicUsize>,
+}
+
+impl DryRun {
+	pub fn new(dir: impl Into<PathBuf>) -> Self {
+		Self { dir: dir.into(), batches: Default::default() }
+	}
+
+	/// Prints the messages that would be submitted to the chain and writes them to a file.
+	/// Returns the path of the file.
+	pub fn record(&self, chain: &str, msgs: &[Any]) -> Result<PathBuf, anyhow::Error> {
+		let index = self.batches.fetch_add(1, Ordering::SeqCst);
+		let path = self.dir.join(format!("{index:06}-{chain}.bin"));
+		let body = TxBody { messages: msgs.to_vec(), ..Default::default() };
+		std::fs::create_dir_all(&self.dir)?;
+		std::fs::write(&path, body.encode_to_vec())?;
+
+		println!("Dry run: {} messages to {chain}, written to {}", msgs.len(), path.display());
+		for msg in msgs {
+			println!("  {}", describe_message(msg));
+		}
+		Ok(path)
+	}
+}
+
+/// Returns a human readable description of the message. Packet and client update messages are
+/// decoded, other messages are described by their type and size.
+pub fn describe_message(msg: &Any) -> String {
+	let decoded = match msg.type_url.as_str() {
+		UPDATE_CLIENT_TYPE_URL => MsgUpdateAnyClient::<LocalClientTypes>::decode_vec(&msg.value)
+			.map(|msg| format!("client {}, signer {}", msg.client_id, msg.signer))
+			.ok(),
+		RECV_TYPE_URL => MsgRecvPacket::decode_vec(&msg.value)
+			.map(|msg| {
+				format!(
+					"packet {}, proof height {}, signer {}",
+					msg.packet,
+					msg.proofs.height(),
+					msg.signer
+				)
+			})
+			.ok(),
+		ACK_TYPE_URL => MsgAcknowledgement::decode_vec(&msg.value)
+			.map(|msg| {
+				format!(
+					"packet {}, acknowledgement {}, proof height {}, signer {}",
+					msg.packet,
+					hex::encode(msg.acknowledgement.as_ref()),
+					msg.proofs.height(),
+					msg.signer
+				)
+			})
+			.ok(),
+		TIMEOUT_TYPE_URL => MsgTimeout::decode_vec(&msg.value)
+			.map(|msg| {
+				format!(
+					"packet {}, next sequence recv {}, proof height {}, signer {}",
+					msg.packet,
+					msg.next_sequence_recv,
+					msg.proofs.height(),
+					msg.signer
+				)
+			})
+			.ok(),
+		TIMEOUT_ON_CLOSE_TYPE_URL => MsgTimeoutOnClose::decode_vec(&msg.value)
+			.map(|msg| {
+				format!(
+					"packet {}, next sequence recv {}, proof height {}, signer {}",
+					msg.packet,
+					msg.next_sequence_recv,
+					msg.proofs.height(),
+					msg.signer
+				)
+			})
+			.ok(),
+		_ => None,
+	};
+	match decoded {
+		Some(decoded) => format!("{}: {decoded}", msg.type_url),
+		None => format!("{}: {} bytes", msg.type_url, msg.value.len()),
+	}
+}
+
+#[cfg(test)]
+pub mod tests {
+	use super::{describe_message, DryRun};
+	use ibc::{
+		core::{
+			ics04_channel::{
+				msgs::recv_packet::MsgRecvPacket,
+				packet::{Packet, Sequence},
+			},
+			ics23_commitment::commitment::CommitmentProofBytes,
+		},
+		proofs::Proofs,
+		tx_msg::Msg,
+		Height,
+	};
+	use ibc_proto::{cosmos::tx::v1beta1::TxBody, google::protobuf::Any};
+	use prost::Message;
+
+	#[test]
+	fn batches_are_written_instead_of_submitted() {
+		let dir = std::env::temp_dir().join(format!("hyperspace-dry-run-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&dir);
+		let proof = CommitmentProofBytes::try_from(vec![1]).unwrap();
+		let proofs = Proofs::new(proof, None, None, None, Height::new(0, 7)).unwrap();
+		let packet = Packet { sequence: Sequence::from(3), data: vec![1], ..Default::default() };
+		let recv = MsgRecvPacket::new(packet, proofs, "signer".parse().unwrap()).to_any();
+		let unknown = Any { type_url: "/unknown".to_string(), value: vec![0; 4] };
+		let msgs = vec![unknown.clone(), recv.clone()];
+
+		let dry_run = DryRun::new(&dir);
+		let first = dry_run.record("a", &msgs).unwrap();
+		let second = dry_run.clone().record("b", &msgs[..1]).unwrap();
+		assert!(first < second);
+		let body = TxBody::decode(std::fs::read(&first).unwrap().as_slice()).unwrap();
+		assert_eq!(body.messages, msgs);
+
+		assert_eq!(describe_message(&unknown), "/unknown: 4 bytes");
+		let description = describe_message(&recv);
+		assert!(description.contains("proof height 0-7"), "{description}");
+		assert!(description.contains("signer signer"), "{description}");
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+}
@@ -0,0 +1,220 @@
+// Copyright 2022 ComposableFi
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ibc::{
+	core::{
+		ics04_channel::{
+			msgs::{
+				acknowledgement::{MsgAcknowledgement, TYPE_URL as ACK_TYPE_URL},
+				recv_packet::{MsgRecvPacket, TYPE_URL as RECV_TYPE_URL},
+				timeout::{MsgTimeout, TYPE_URL as TIMEOUT_TYPE_URL},
+				timeout_on_close::{MsgTimeoutOnClose, TYPE_URL as TIMEOUT_ON_CLOSE_TYPE_URL},
+			},
+			packet::{Packet, Sequence},
+		},
+		ics24_host::identifier::{ChannelId, PortId},
+	},
+	events::IbcEvent,
+	protobuf::Protobuf,
+};
+use ibc_proto::google::protobuf::Any;
+use std::{
+	collections::HashMap,
+	sync::{Arc, Mutex},
+	time::{Duration, Instant},
+};
+
+/// A kind of packet message that can be in flight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InFlightType {
+	RecvPacket,
+	Acknowledgement,
+	Timeout,
+}
+
+/// Identifies a packet message by the packet's source channel, port and sequence.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct InFlightKey {
+	pub channel_id: ChannelId,
+	pub port_id: PortId,
+	pub sequence: Sequence,
+	pub kind: InFlightType,
+}
+
+impl InFlightKey {
+	pub fn new(packet: &Packet, kind: InFlightType) -> Self {
+		Self {
+			channel_id: packet.source_channel,
+			port_id: packet.source_port.clone(),
+			sequence: packet.sequence,
+			kind,
+		}
+	}
+
+	/// Returns the key of a packet message, or `None` if it's not a packet message.
+	pub fn from_message(msg: &Any) -> Option<Self> {
+		let (packet, kind) = packet_of_message(msg)?;
+		Some(Self::new(&packet, kind))
+	}
+
+	/// Returns the key of the message that resulted in the event, or `None` if it's not a packet
+	/// delivery event.
+	pub fn from_event(event: &IbcEvent) -> Option<Self> {
+		match event {
+			IbcEvent::ReceivePacket(ev) => Some(Self::new(&ev.packet, InFlightType::RecvPacket)),
+			IbcEvent::AcknowledgePacket(ev) =>
+				Some(Self::new(&ev.packet, InFlightType::Acknowledgement)),
+			IbcEvent::TimeoutPacket(ev) => Some(Self::new(&ev.packet, InFlightType::Timeout)),
+			IbcEvent::TimeoutOnClosePacket(ev) =>
+				Some(Self::new(&ev.packet, InFlightType::Timeout)),
+			_ => None,
+		}
+	}
+}
+
+/// Returns the packet of a packet message with the kind of the message, or `None` if it's not a
+/// packet message.
+pub fn packet_of_message(msg: &Any) -> Option<(Packet, InFlightType)> {
+	let packet_and_kind = match msg.type_url.as_str() {
+		RECV_TYPE_URL =>
+			(MsgRecvPacket::decode_vec(&msg.value).ok()?.packet, InFlightType::RecvPacket),
+		ACK_TYPE_URL =>
+			(MsgAcknowledgement::decode_vec(&msg.value).ok()?.packet, InFlightType::Acknowledgement),
+		TIMEOUT_TYPE_URL =>
+			(MsgTimeout::decode_vec(&msg.value).ok()?.packet, InFlightType::Timeout),
+		TIMEOUT_ON_CLOSE_TYPE_URL =>
+			(MsgTimeoutOnClose::decode_vec(&msg.value).ok()?.packet, InFlightType::Timeout),
+		_ => return None,
+	};
+	Some(packet_and_kind)
+}
+
+/// Tracks packet messages that were submitted to a chain, but haven't been observed executed yet,
+/// so that they are not rebuilt and resubmitted while still pending in the mempool.
+///
+/// Entries are removed when the corresponding event is observed on the chain, or once they are
+/// older than the expiry.
+#[derive(Debug, Clone)]
+pub struct InFlightPackets {
+	expiry: Duration,
+	submitted_at: Arc<Mutex<HashMap<InFlightKey, Instant>>>,
+}
+
+impl Default for InFlightPackets {
+	fn default() -> Self {
+		Self::new(Duration::from_secs(60))
+	}
+}
+
+impl InFlightPackets {
+	pub fn new(expiry: Duration) -> Self {
+		Self { expiry, submitted_at: Default::default() }
+	}
+
+	/// Marks the packet messages as submitted, other messages are ignored.
+	pub fn insert_messages(&self, msgs: &[Any]) {
+		let now = Instant::now();
+		let mut submitted_at = self.submitted_at.lock().unwrap();
+		for key in msgs.iter().filter_map(InFlightKey::from_message) {
+			submitted_at.insert(key, now);
+		}
+	}
+
+	/// Removes the entries of the messages that resulted in the events, and the expired ones.
+	pub fn remove_events(&self, events: &[IbcEvent]) {
+		let mut submitted_at = self.submitted_at.lock().unwrap();
+		for key in events.iter().filter_map(InFlightKey::from_event) {
+			submitted_at.remove(&key);
+		}
+		submitted_at.retain(|_, at| at.elapsed() < self.expiry);
+	}
+
+	/// Forgets all the messages, so that they can be submitted again.
+	pub fn clear(&self) {
+		self.submitted_at.lock().unwrap().clear();
+	}
+
+	/// Returns `true` if the message was submitted less than the expiry ago and its event hasn't
+	/// been observed yet.
+	pub fn contains(&self, key: &InFlightKey) -> bool {
+		self.submitted_at
+			.lock()
+			.unwrap()
+			.get(key)
+			.map_or(false, |at| at.elapsed() < self.expiry)
+	}
+}
+
+#[cfg(test)]
+pub mod tests {
+	use super::{InFlightKey, InFlightPackets, InFlightType};
+	use ibc::{
+		core::{
+			ics04_channel::{
+				events::ReceivePacket,
+				msgs::recv_packet::MsgRecvPacket,
+				packet::{Packet, Sequence},
+			},
+			ics23_commitment::commitment::CommitmentProofBytes,
+		},
+		events::IbcEvent,
+		proofs::Proofs,
+		tx_msg::Msg,
+		Height,
+	};
+	use std::{thread::sleep, time::Duration};
+
+	fn packet(sequence: u64) -> Packet {
+		Packet { sequence: Sequence::from(sequence), data: vec![1], ..Default::default() }
+	}
+
+	fn recv_key(sequence: u64) -> InFlightKey {
+		InFlightKey::new(&packet(sequence), InFlightType::RecvPacket)
+	}
+
+	#[test]
+	fn second_round_skips_in_flight_packets() {
+		let in_flight = InFlightPackets::new(Duration::from_millis(200));
+		let proof = CommitmentProofBytes::try_from(vec![1]).unwrap();
+		let round_one = (1..=3)
+			.map(|sequence| {
+				let proofs =
+					Proofs::new(proof.clone(), None, None, None, Height::new(0, 1)).unwrap();
+				MsgRecvPacket::new(packet(sequence), proofs, "signer".parse().unwrap()).to_any()
+			})
+			.collect::<Vec<_>>();
+		in_flight.insert_messages(&round_one);
+
+		// the second round only builds the messages that aren't in flight
+		let round_two = (1..=5)
+			.filter(|sequence| !in_flight.contains(&recv_key(*sequence)))
+			.collect::<Vec<_>>();
+		assert_eq!(round_two, vec![4, 5]);
+
+		// other kinds of messages for the same packets are tracked separately
+		assert!(!in_flight.contains(&InFlightKey::new(&packet(1), InFlightType::Acknowledgement)));
+
+		// the packet was received, so it's not in flight anymore
+		in_flight.remove_events(&[IbcEvent::ReceivePacket(ReceivePacket {
+			height: Height::new(0, 1),
+			packet: packet(1),
+		})]);
+		assert!(!in_flight.contains(&recv_key(1)));
+		assert!(in_flight.contains(&recv_key(2)));
+
+		// the rest expire
+		sleep(Duration::from_millis(200));
+		assert!(!in_flight.contains(&recv_key(2)));
+	}
+}
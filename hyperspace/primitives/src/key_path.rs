@@ -0,0 +1,130 @@
+// Copyright 2022 ComposableFi
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::apply_prefix;
+use ibc::core::{
+	ics04_channel::packet::Packet,
+	ics23_commitment::commitment::CommitmentPrefix,
+	ics24_host::path::{AcksPath, ChannelEndsPath, CommitmentsPath, ReceiptsPath, SeqRecvsPath},
+};
+
+/// A kind of packet related state whose proof is queried to build a packet message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyPathType {
+	SeqRecv,
+	ReceiptPath,
+	CommitmentPath,
+	AcksPath,
+	ChannelPath,
+}
+
+/// Returns the bare ICS-24 path of the packet state, without any store prefix.
+pub fn get_key_path(key_path_type: KeyPathType, packet: &Packet) -> String {
+	match key_path_type {
+		KeyPathType::SeqRecv => {
+			format!("{}", SeqRecvsPath(packet.destination_port.clone(), packet.destination_channel))
+		},
+		KeyPathType::ReceiptPath => {
+			format!(
+				"{}",
+				ReceiptsPath {
+					port_id: packet.destination_port.clone(),
+					channel_id: packet.destination_channel,
+					sequence: packet.sequence
+				}
+			)
+		},
+		KeyPathType::CommitmentPath => {
+			format!(
+				"{}",
+				CommitmentsPath {
+					port_id: packet.source_port.clone(),
+					channel_id: packet.source_channel,
+					sequence: packet.sequence
+				}
+			)
+		},
+		KeyPathType::AcksPath => {
+			format!(
+				"{}",
+				AcksPath {
+					port_id: packet.destination_port.clone(),
+					channel_id: packet.destination_channel,
+					sequence: packet.sequence
+				}
+			)
+		},
+		KeyPathType::ChannelPath => {
+			format!(
+				"{}",
+				ChannelEndsPath(packet.destination_port.clone(), packet.destination_channel)
+			)
+		},
+	}
+}
+
+/// Returns the path of the packet state prepended with the store prefix, for chains whose
+/// [`crate::IbcProvider::query_proof`] expects fully prefixed keys.
+pub fn get_prefixed_key_path(
+	prefix: &CommitmentPrefix,
+	key_path_type: KeyPathType,
+	packet: &Packet,
+) -> Vec<u8> {
+	apply_prefix(prefix.clone().into_vec(), get_key_path(key_path_type, packet))
+}
+
+#[cfg(test)]
+pub mod tests {
+	use super::{get_key_path, get_prefixed_key_path, KeyPathType};
+	use ibc::core::{
+		ics04_channel::packet::{Packet, Sequence},
+		ics23_commitment::commitment::CommitmentPrefix,
+		ics24_host::identifier::{ChannelId, PortId},
+	};
+
+	fn packet() -> Packet {
+		Packet {
+			sequence: Sequence::from(7),
+			source_port: PortId::transfer(),
+			source_channel: ChannelId::new(1),
+			destination_port: PortId::transfer(),
+			destination_channel: ChannelId::new(2),
+			..Default::default()
+		}
+	}
+
+	const KEYS: [(KeyPathType, &str); 5] = [
+		(KeyPathType::SeqRecv, "nextSequenceRecv/ports/transfer/channels/channel-2"),
+		(KeyPathType::ReceiptPath, "receipts/ports/transfer/channels/channel-2/sequences/7"),
+		(KeyPathType::CommitmentPath, "commitments/ports/transfer/channels/channel-1/sequences/7"),
+		(KeyPathType::AcksPath, "acks/ports/transfer/channels/channel-2/sequences/7"),
+		(KeyPathType::ChannelPath, "channelEnds/ports/transfer/channels/channel-2"),
+	];
+
+	#[test]
+	fn key_paths_are_unchanged() {
+		for (key_path_type, expected) in KEYS {
+			assert_eq!(get_key_path(key_path_type, &packet()), expected);
+		}
+	}
+
+	#[test]
+	fn prefixed_key_paths_start_with_the_prefix() {
+		let prefix = CommitmentPrefix::try_from(b"ibc/".to_vec()).unwrap();
+		for (key_path_type, expected) in KEYS {
+			let key = get_prefixed_key_path(&prefix, key_path_type, &packet());
+			assert_eq!(key, [b"ibc/".as_slice(), expected.as_bytes()].concat());
+		}
+	}
+}
@@ -31,10 +31,10 @@ use rand::Rng;
 use serde::{Deserialize, Serialize};
 use std::{
 	collections::{HashMap, HashSet},
-	fmt::Debug,
+	fmt::{Debug, Display},
 	pin::Pin,
 	str::FromStr,
-	sync::{Arc, Mutex},
+	sync::{atomic::AtomicBool, Arc, Mutex},
 	time::Duration,
 };
 use tokio::{sync::Mutex as AsyncMutex, task::JoinSet, time::sleep};
@@ -64,15 +64,34 @@ use ibc::{
 	Height,
 };
 use ibc_proto::ibc::core::{
-	channel::v1::QueryChannelsResponse, connection::v1::IdentifiedConnection,
+	channel::v1::{QueryChannelsResponse, State as ChannelState},
+	connection::v1::IdentifiedConnection,
 };
 use ibc_rpc::PacketInfo;
 use pallet_ibc::light_clients::{AnyClientMessage, AnyClientState, AnyConsensusState};
 
+pub mod channel_filter;
+pub mod dry_run;
 pub mod error;
+pub mod in_flight;
+pub mod key_path;
 pub mod mock;
+pub mod packet_filter;
+pub mod pause;
+pub mod shutdown;
 pub mod utils;
 
+use channel_filter::ChannelFilter;
+use dry_run::DryRun;
+use in_flight::InFlightPackets;
+use key_path::{get_key_path, KeyPathType};
+use packet_filter::{
+	ChannelPacketFilter, FilterDecision, PacketFilter, PacketFilterHook, SkippedPackets,
+	TransferDenylistConfig,
+};
+use pause::PauseFlag;
+use shutdown::Shutdown;
+
 pub enum UpdateMessage {
 	Single(Any),
 	Batch(Vec<Any>),
@@ -103,6 +122,22 @@ fn max_packets_to_process() -> u32 {
 	50
 }
 
+fn max_concurrent_packet_proofs() -> u32 {
+	100
+}
+
+fn in_flight_packets_expiry() -> u64 {
+	60
+}
+
+fn max_submission_retries() -> u32 {
+	3
+}
+
+fn finality_regression_threshold() -> u64 {
+	10
+}
+
 // TODO: move other fields like `client_id`, `connection_id`, etc. here
 /// Common relayer parameters
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -112,6 +147,61 @@ pub struct CommonClientConfig {
 	pub skip_optional_client_updates: bool,
 	#[serde(default = "max_packets_to_process")]
 	pub max_packets_to_process: u32,
+	/// Only relay packets that match the filter
+	#[serde(default)]
+	pub packet_filter: PacketFilter,
+	/// Only relay ICS-20 transfers sent from the given channels that match the channel's filter.
+	/// Other packets and timeouts are always relayed.
+	#[serde(default)]
+	pub channel_packet_filters: Vec<ChannelPacketFilter>,
+	/// Don't relay ICS-20 transfers to the given receivers or with matching memos. Timeouts are
+	/// always relayed.
+	#[serde(default)]
+	pub transfer_denylist: TransferDenylistConfig,
+	/// Maximum number of messages submitted in a single transaction
+	#[serde(default, alias = "max_msgs_per_tx")]
+	pub max_messages_per_batch: Option<u32>,
+	/// Maximum number of transactions submitted per block, the following ones wait for the next
+	/// block
+	#[serde(default)]
+	pub max_txs_per_block: Option<u32>,
+	/// Maximum total size (in bytes) of messages submitted in a single transaction
+	#[serde(default)]
+	pub max_batch_bytes: Option<u32>,
+	/// Maximum number of packet messages constructed (and proofs queried) concurrently
+	#[serde(default = "max_concurrent_packet_proofs")]
+	pub max_concurrent_packet_proofs: u32,
+	/// Connection delay overrides (in seconds) for specific channels on this chain. The bigger of
+	/// the override and the connection delay is used.
+	#[serde(default)]
+	pub channel_delay_overrides: Vec<(ChannelId, PortId, u64)>,
+	/// Time (in seconds) after which a submitted packet message that wasn't observed executed on
+	/// the chain may be submitted again
+	#[serde(default = "in_flight_packets_expiry")]
+	pub in_flight_packets_expiry: u64,
+	/// Maximum number of times a submission failed with a transient error is retried
+	#[serde(default = "max_submission_retries")]
+	pub max_submission_retries: u32,
+	/// Relay the packets and acknowledgements of whitelisted channels as soon as their events are
+	/// finalized, instead of waiting for the next finality notification
+	#[serde(default)]
+	pub eager_flush: bool,
+	/// Time (in seconds) after which an optional client update is sent even if there are no
+	/// messages to relay
+	#[serde(default)]
+	pub max_client_staleness: Option<u64>,
+	/// Interval (in milliseconds) between polls of the chain, e.g. while waiting for a new block.
+	/// Defaults to the expected block time.
+	#[serde(default)]
+	pub poll_interval: Option<u64>,
+	/// Number of blocks the finalized height may go back by before the packets in flight and the
+	/// undelivered sequences are invalidated
+	#[serde(default = "finality_regression_threshold")]
+	pub finality_regression_threshold: u64,
+	/// Batches whose estimated fee (in the smallest unit of the fee token) is higher are not
+	/// submitted
+	#[serde(default)]
+	pub max_fee_per_batch: Option<u64>,
 }
 
 /// A common data that all clients should keep.
@@ -133,6 +223,56 @@ pub struct CommonClientState {
 	pub misbehaviour_client_msg_queue: Arc<AsyncMutex<Vec<AnyClientMessage>>>,
 	pub max_packets_to_process: usize,
 	pub skip_tokens_list: Vec<String>,
+	/// Channels matching the filter are added to the channel whitelist once they're found
+	pub channel_filter: ChannelFilter,
+	/// Packets that don't match the filter are not relayed
+	pub packet_filter: PacketFilter,
+	/// ICS-20 transfers sent from the given channels that don't match the filter are not relayed
+	pub channel_packet_filters: Vec<ChannelPacketFilter>,
+	/// Packets denied by any of the hooks are not relayed, see [`PacketFilterHook`]
+	pub packet_filter_hooks: Vec<Arc<dyn PacketFilterHook>>,
+	/// Packets that were skipped because of the filters, so that they're only reported once
+	pub skipped_packets: Arc<Mutex<SkippedPackets>>,
+	/// Outgoing batches with more messages are split before submission
+	pub max_messages_per_batch: Option<usize>,
+	/// Sub-batches submitted after this many wait for the next block
+	pub max_txs_per_block: Option<usize>,
+	/// Outgoing batches with bigger total size of messages are split before submission
+	pub max_batch_bytes: Option<usize>,
+	/// Maximum number of packet messages constructed concurrently
+	pub max_concurrent_packet_proofs: usize,
+	/// Delays that packets sent from the given channels should wait for instead of the connection
+	/// delay, if they are longer
+	pub channel_delay_overrides: HashMap<(ChannelId, PortId), Duration>,
+	/// Packet messages submitted to this chain that weren't observed executed yet
+	pub in_flight_packets: InFlightPackets,
+	/// Submissions failed with a transient error are retried at most this many times
+	pub max_submission_retries: usize,
+	/// Held while submitting to this chain. Both relaying directions and the eager flushes submit
+	/// with the same account, so their submissions are serialized to avoid nonce conflicts.
+	pub submission_lock: Arc<AsyncMutex<()>>,
+	/// Packet events are relayed as soon as they are finalized
+	pub eager_flush: bool,
+	/// Optional client updates are sent if the client wasn't updated for longer than this
+	pub max_client_staleness: Option<Duration>,
+	/// Interval between polls of the chain, see [`Chain::poll_interval`]
+	pub poll_interval: Option<Duration>,
+	/// Outgoing batches are written out instead of being submitted
+	pub dry_run: Option<DryRun>,
+	/// Packets are not relayed while the flag is set
+	pub pause_flag: PauseFlag,
+	/// Relaying stops once the shutdown is requested
+	pub shutdown: Shutdown,
+	/// The client of this chain on the counterparty is updated in the next round, even if the
+	/// update is optional
+	pub force_client_update: Arc<AtomicBool>,
+	/// The last height of this chain whose events were relayed to the counterparty, the events at
+	/// or below it don't need to be queried again
+	pub processed_height: Arc<Mutex<Option<Height>>>,
+	/// See [`CommonClientConfig::finality_regression_threshold`]
+	pub finality_regression_threshold: u64,
+	/// Batches with a higher estimated fee are skipped, see [`Chain::estimate_fee`]
+	pub max_fee_per_batch: Option<u128>,
 }
 
 impl Default for CommonClientState {
@@ -146,6 +286,29 @@ impl Default for CommonClientState {
 			misbehaviour_client_msg_queue: Arc::new(Default::default()),
 			max_packets_to_process: 100,
 			skip_tokens_list: Default::default(),
+			channel_filter: Default::default(),
+			packet_filter: Default::default(),
+			channel_packet_filters: Default::default(),
+			packet_filter_hooks: Default::default(),
+			skipped_packets: Default::default(),
+			max_messages_per_batch: None,
+			max_txs_per_block: None,
+			max_batch_bytes: None,
+			max_concurrent_packet_proofs: 100,
+			channel_delay_overrides: Default::default(),
+			in_flight_packets: Default::default(),
+			max_submission_retries: 3,
+			submission_lock: Default::default(),
+			eager_flush: false,
+			max_client_staleness: None,
+			poll_interval: None,
+			dry_run: None,
+			pause_flag: Default::default(),
+			shutdown: Default::default(),
+			force_client_update: Default::default(),
+			processed_height: Default::default(),
+			finality_regression_threshold: 10,
+			max_fee_per_batch: None,
 		}
 	}
 }
@@ -177,6 +340,93 @@ impl CommonClientState {
 	pub fn set_rpc_call_delay(&mut self, delay: Duration) {
 		self.rpc_call_delay = delay;
 	}
+
+	/// Returns the delay packets from the given channel should wait for: the connection delay or
+	/// the channel's override, whichever is longer.
+	pub fn channel_delay(
+		&self,
+		channel_id: ChannelId,
+		port_id: PortId,
+		connection_delay: Duration,
+	) -> Duration {
+		self.channel_delay_overrides
+			.get(&(channel_id, port_id))
+			.map_or(connection_delay, |delay| connection_delay.max(*delay))
+	}
+
+	/// Returns `true` if the packet matches both the packet filter and its channel's filter.
+	pub fn should_relay_packet(&self, packet: &Packet) -> bool {
+		self.packet_filter.matches(packet) &&
+			self.channel_packet_filters.iter().all(|filter| filter.matches(packet))
+	}
+
+	/// Returns the decision of the first packet filter hook that denies the packet, if any.
+	pub fn packet_filter_hooks_decision(&self, packet: &Packet) -> FilterDecision {
+		self.packet_filter_hooks
+			.iter()
+			.map(|hook| hook.allow(packet))
+			.find(|decision| *decision != FilterDecision::Allow)
+			.unwrap_or(FilterDecision::Allow)
+	}
+
+	pub fn processed_height(&self) -> Option<Height> {
+		*self.processed_height.lock().unwrap()
+	}
+
+	/// Records the processed height, heights lower than the recorded one are ignored.
+	pub fn set_processed_height(&self, height: Height) {
+		let mut processed_height = self.processed_height.lock().unwrap();
+		if processed_height.map_or(true, |processed_height| height > processed_height) {
+			*processed_height = Some(height);
+		}
+	}
+
+	/// Moves the processed height back after a finality regression, so that the events above it
+	/// are queried again.
+	pub fn rewind_processed_height(&self, height: Height) {
+		*self.processed_height.lock().unwrap() = Some(height);
+	}
+
+	/// Remembers that the packet was skipped. Returns `false` if it was already skipped before.
+	pub fn record_skipped_packet(&self, packet: &Packet) -> bool {
+		self.skipped_packets.lock().unwrap().insert(packet)
+	}
+}
+
+/// Class of a failed message submission, used to decide if the submission is retried and as the
+/// label of the failed submissions metric.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubmissionErrorClass {
+	/// The transaction's account sequence or nonce doesn't match the chain's.
+	AccountSequence,
+	/// The mempool is full or already contains the transaction.
+	Mempool,
+	/// The transaction wasn't included in a block in time.
+	Timeout,
+	/// The node couldn't be reached or failed to respond.
+	Rpc,
+	/// The transaction ran out of gas.
+	OutOfGas,
+	/// Any other failure, e.g. a message that failed verification.
+	Other,
+}
+
+impl SubmissionErrorClass {
+	/// Returns `true` if submitting the same messages again may succeed.
+	pub fn is_retryable(&self) -> bool {
+		matches!(self, Self::AccountSequence | Self::Mempool | Self::Timeout | Self::Rpc)
+	}
+
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Self::AccountSequence => "account_sequence",
+			Self::Mempool => "mempool",
+			Self::Timeout => "timeout",
+			Self::Rpc => "rpc",
+			Self::OutOfGas => "out_of_gas",
+			Self::Other => "other",
+		}
+	}
 }
 
 pub fn apply_prefix(mut commitment_prefix: Vec<u8>, path: impl Into<Vec<u8>>) -> Vec<u8> {
@@ -201,7 +451,7 @@ pub enum UndeliveredType {
 #[async_trait::async_trait]
 pub trait IbcProvider {
 	/// Finality event type, passed on to [`Chain::query_latest_ibc_events`]
-	type FinalityEvent: Debug + Send + 'static;
+	type FinalityEvent: Debug + Send + Sync + 'static;
 	/// A representation of the transaction id for the chain
 	type TransactionId: Debug;
 	/// Asset Id
@@ -221,6 +471,12 @@ pub trait IbcProvider {
 	where
 		T: Chain;
 
+	/// Returns the height of this chain finalized by the finality event, if it can be found. Used
+	/// to detect finality regressions.
+	async fn finality_event_height(&self, _finality_event: &Self::FinalityEvent) -> Option<Height> {
+		None
+	}
+
 	/// Return a stream that yields when new [`IbcEvents`] are parsed from a finality notification
 	async fn ibc_events(&self) -> Pin<Box<dyn Stream<Item = IbcEvent> + Send + 'static>>;
 
@@ -255,9 +511,31 @@ pub trait IbcProvider {
 		port_id: PortId,
 	) -> Result<QueryChannelResponse, Self::Error>;
 
+	/// Returns the key [`IbcProvider::query_proof`] expects for the packet state. Defaults to the
+	/// bare ICS-24 path, chains that need the store prefix in the key should override it with
+	/// [`key_path::get_prefixed_key_path`].
+	fn full_key_path(&self, key_path_type: KeyPathType, packet: &Packet) -> Vec<u8> {
+		get_key_path(key_path_type, packet).into_bytes()
+	}
+
 	/// Query proof for provided key path
 	async fn query_proof(&self, at: Height, keys: Vec<Vec<u8>>) -> Result<Vec<u8>, Self::Error>;
 
+	/// Query a separate proof for each of the provided key paths at the same height. The returned
+	/// proofs are in the same order as the keys. Chains that can fetch several proofs in a single
+	/// request should override this.
+	async fn query_proofs(
+		&self,
+		at: Height,
+		keys: Vec<Vec<u8>>,
+	) -> Result<Vec<Vec<u8>>, Self::Error> {
+		let mut proofs = Vec::with_capacity(keys.len());
+		for key in keys {
+			proofs.push(self.query_proof(at, vec![key]).await?);
+		}
+		Ok(proofs)
+	}
+
 	/// Query packet commitment with proof
 	async fn query_packet_commitment(
 		&self,
@@ -418,6 +696,10 @@ pub trait IbcProvider {
 	/// Should return timestamp in nanoseconds of chain at a given block height
 	async fn query_timestamp_at(&self, block_number: u64) -> Result<u64, Self::Error>;
 
+	/// Should return the hash of the block at the given height, or `None` if the chain has no
+	/// block at this height
+	async fn get_block(&self, height: Height) -> Result<Option<Vec<u8>>, Self::Error>;
+
 	/// Should return a list of all clients on the chain
 	async fn query_clients(&self) -> Result<Vec<ClientId>, Self::Error>;
 
@@ -463,6 +745,11 @@ pub trait IbcProvider {
 	) -> Result<(ChannelId, PortId), Self::Error>;
 
 	async fn upload_wasm(&self, wasm: Vec<u8>) -> Result<Vec<u8>, Self::Error>;
+
+	/// Should return `true` if the wasm passed to [`Self::upload_wasm`] must be gzip compressed.
+	fn requires_gzipped_wasm(&self) -> bool {
+		false
+	}
 }
 
 /// Provides an interface that allows us run the hyperspace-testsuite
@@ -497,12 +784,13 @@ pub trait KeyProvider {
 /// Provides an interface for managing IBC misbehaviour.
 #[async_trait::async_trait]
 pub trait MisbehaviourHandler {
-	/// Check the client message for misbehaviour and submit it to the chain if any.
+	/// Check the client message for misbehaviour. Returns the message reporting it to the
+	/// counterparty if any, the caller is responsible for submitting it.
 	async fn check_for_misbehaviour<C: Chain>(
 		&self,
 		counterparty: &C,
 		client_message: AnyClientMessage,
-	) -> Result<(), anyhow::Error>;
+	) -> Result<Option<Any>, anyhow::Error>;
 }
 
 /// Provides an interface for syncing light clients to the latest state
@@ -518,6 +806,23 @@ pub trait LightClientSync {
 	) -> Result<(Vec<Any>, Vec<IbcEvent>), anyhow::Error>;
 }
 
+/// Estimated cost of submitting a batch of messages, in the smallest unit of the fee token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeeEstimate {
+	pub amount: u128,
+	/// Denomination of the fee token, `None` for the chain's native token.
+	pub denom: Option<String>,
+}
+
+impl Display for FeeEstimate {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match &self.denom {
+			Some(denom) => write!(f, "{}{denom}", self.amount),
+			None => write!(f, "{}", self.amount),
+		}
+	}
+}
+
 /// Provides an interface for the chain to the relayer core for submitting IbcEvents as well as
 /// finality notifications
 #[async_trait::async_trait]
@@ -533,6 +838,10 @@ pub trait Chain:
 	/// Should return an estimate of the weight of a batch of messages.
 	async fn estimate_weight(&self, msg: Vec<Any>) -> Result<u64, Self::Error>;
 
+	/// Should return an estimate of the fee paid for submitting the messages in a single
+	/// transaction.
+	async fn estimate_fee(&self, msgs: &[Any]) -> Result<FeeEstimate, Self::Error>;
+
 	/// Return a stream that yields when new [`IbcEvents`] are ready to be queried.
 	async fn finality_notifications(
 		&self,
@@ -553,6 +862,12 @@ pub trait Chain:
 
 	async fn handle_error(&mut self, error: &anyhow::Error) -> Result<(), anyhow::Error>;
 
+	/// Classifies an error returned by [`Chain::submit`]. Only the retryable classes (see
+	/// [`SubmissionErrorClass::is_retryable`]) are submitted again.
+	fn submission_error_class(&self, _error: &Self::Error) -> SubmissionErrorClass {
+		SubmissionErrorClass::Other
+	}
+
 	fn common_state(&self) -> &CommonClientState;
 
 	fn common_state_mut(&mut self) -> &mut CommonClientState;
@@ -577,7 +892,54 @@ pub trait Chain:
 		self.common_state_mut().set_rpc_call_delay(delay)
 	}
 
+	/// Returns the interval between polls of this chain, e.g. while waiting for a new block. It's
+	/// the configured poll interval if set, and the expected block time otherwise. Unlike the
+	/// expected block time, it's never used to estimate the number of blocks in a delay.
+	fn poll_interval(&self) -> Duration {
+		self.common_state().poll_interval.unwrap_or_else(|| self.expected_block_time())
+	}
+
 	async fn reconnect(&mut self) -> anyhow::Result<()>;
+
+	/// Returns the relayer's signer for this chain, or an error if the configured signer is not
+	/// valid for this chain's address format. Should be used in place of
+	/// [`KeyProvider::account_id`] when building messages, so that a bad signer is caught before
+	/// the message is submitted.
+	fn validate_signer(&self) -> Result<Signer, Self::Error> {
+		let signer = self.account_id();
+		if signer.as_ref().trim().is_empty() {
+			return Err(format!("Configured signer for {} is empty", self.name()).into())
+		}
+		Ok(signer)
+	}
+
+	/// Returns the ids of the clients on this chain that will expire within `duration` from now,
+	/// based on the timestamp of their latest consensus state and their trusting period.
+	async fn query_clients_expiring_within(
+		&self,
+		duration: Duration,
+	) -> Result<Vec<ClientId>, Self::Error> {
+		let (at, now) = self.latest_height_and_timestamp().await?;
+		let mut expiring = vec![];
+		for client_id in self.query_clients().await? {
+			match client_expires_within(self, at, now, client_id.clone(), duration).await {
+				Ok(true) => expiring.push(client_id),
+				Ok(false) => {},
+				Err(e) => log::warn!(
+					target: "hyperspace",
+					"Failed to check expiry of client {client_id} on {}: {e}", self.name()
+				),
+			}
+		}
+		Ok(expiring)
+	}
+
+	/// Checks that the chain is reachable and the relayed path to the `counterparty` is configured
+	/// correctly, returning a descriptive error otherwise. Called at startup, so that a
+	/// misconfiguration is reported before relaying starts instead of failing later in the queries.
+	async fn health_check<C: Chain>(&self, counterparty: &C) -> Result<(), anyhow::Error> {
+		check_relayed_path(self, counterparty).await
+	}
 }
 
 /// Returns undelivered packet sequences that have been sent out from
@@ -683,6 +1045,60 @@ pub async fn query_undelivered_acks(
 	Ok(undelivered_acks)
 }
 
+/// Adds the open channels on the chain's connection that match the wildcard entries of its
+/// channel filter to the channel whitelist.
+pub async fn resolve_channel_whitelist(chain: &mut impl Chain) -> Result<(), anyhow::Error> {
+	let channel_filter = chain.common_state().channel_filter.clone();
+	if !channel_filter.has_wildcards() {
+		return Ok(())
+	}
+	let connection_id = match chain.connection_id() {
+		Some(connection_id) => connection_id,
+		None => return Ok(()),
+	};
+	let (latest_height, ..) = chain.latest_height_and_timestamp().await?;
+	let channels = chain.query_connection_channels(latest_height, &connection_id).await?.channels;
+	for channel in channels {
+		if channel.state != ChannelState::Open as i32 {
+			continue
+		}
+		let channel_id = ChannelId::from_str(&channel.channel_id)
+			.map_err(|e| Error::Custom(format!("Invalid channel id: {e:?}")))?;
+		let port_id = PortId::from_str(&channel.port_id)
+			.map_err(|e| Error::Custom(format!("Invalid port id: {e:?}")))?;
+		if channel_filter.matches(&channel_id, &port_id) &&
+			!chain.channel_whitelist().contains(&(channel_id, port_id.clone()))
+		{
+			log::info!(target: "hyperspace", "Adding channel {channel_id}/{port_id} to the whitelist of {}", chain.name());
+			chain.add_channel_to_whitelist((channel_id, port_id));
+		}
+	}
+	Ok(())
+}
+
+/// Adds the channels opened in the events that match the channel filter to the whitelist.
+pub fn add_opened_channels_to_whitelist(chain: &mut impl Chain, events: &[IbcEvent]) {
+	use ibc::core::ics04_channel::events::Attributes as ChannelAttributes;
+	for event in events {
+		let attributes = match event {
+			IbcEvent::OpenAckChannel(e) => ChannelAttributes::from(e.clone()),
+			IbcEvent::OpenConfirmChannel(e) => ChannelAttributes::from(e.clone()),
+			_ => continue,
+		};
+		let channel_id = match attributes.channel_id {
+			Some(channel_id) => channel_id,
+			None => continue,
+		};
+		let port_id = attributes.port_id;
+		if chain.common_state().channel_filter.matches(&channel_id, &port_id) &&
+			!chain.channel_whitelist().contains(&(channel_id, port_id.clone()))
+		{
+			log::info!(target: "hyperspace", "Adding opened channel {channel_id}/{port_id} to the whitelist of {}", chain.name());
+			chain.add_channel_to_whitelist((channel_id, port_id));
+		}
+	}
+}
+
 pub fn packet_info_to_packet(packet_info: &PacketInfo) -> Packet {
 	Packet {
 		sequence: packet_info.sequence.into(),
@@ -813,6 +1229,119 @@ pub async fn find_suitable_proof_height_for_client(
 	None
 }
 
+/// Returns `true` if the client with the given id on the `chain` will expire within `duration`
+/// from `now`, i.e. its latest consensus state will be older than its trusting period.
+pub async fn client_expires_within<C: IbcProvider + ?Sized>(
+	chain: &C,
+	at: Height,
+	now: Timestamp,
+	client_id: ClientId,
+	duration: Duration,
+) -> Result<bool, C::Error> {
+	let (client_state, elapsed) = client_state_and_age(chain, at, now, client_id).await?;
+	Ok(client_state.expired(elapsed.saturating_add(duration)))
+}
+
+/// Returns the time elapsed from the latest consensus state of the client with the given id on the
+/// `chain` to `now`, i.e. since the client was last updated.
+pub async fn client_update_age<C: IbcProvider + ?Sized>(
+	chain: &C,
+	at: Height,
+	now: Timestamp,
+	client_id: ClientId,
+) -> Result<Duration, C::Error> {
+	let (_, elapsed) = client_state_and_age(chain, at, now, client_id).await?;
+	Ok(elapsed)
+}
+
+/// Returns the trusting period of the client with the given id on the `chain`, or `None` if the
+/// client doesn't expire, and the time elapsed from its latest consensus state to `now`.
+pub async fn client_trusting_period_and_age<C: IbcProvider + ?Sized>(
+	chain: &C,
+	at: Height,
+	now: Timestamp,
+	client_id: ClientId,
+) -> Result<(Option<Duration>, Duration), C::Error> {
+	let (client_state, elapsed) = client_state_and_age(chain, at, now, client_id).await?;
+	Ok((client_state.trusting_period(), elapsed))
+}
+
+/// Checks the connectivity to the chain, the relayer's signer, that the chain's client exists on
+/// the `counterparty` and that the configured connection exists on the chain. Used by the default
+/// [`Chain::health_check`].
+pub async fn check_relayed_path<C: Chain + ?Sized, T: Chain>(
+	chain: &C,
+	counterparty: &T,
+) -> Result<(), anyhow::Error> {
+	let name = chain.name();
+	let (height, _) = chain.latest_height_and_timestamp().await.map_err(|e| {
+		anyhow::anyhow!("Failed to query the latest height of {name}, check the RPC endpoints: {e}")
+	})?;
+	chain.validate_signer()?;
+
+	// the client of the chain lives on the counterparty
+	let counterparty_name = counterparty.name();
+	let (counterparty_height, _) =
+		counterparty.latest_height_and_timestamp().await.map_err(|e| {
+			anyhow::anyhow!(
+			"Failed to query the latest height of {counterparty_name}, check the RPC endpoints: {e}"
+		)
+		})?;
+	let client_id = chain.client_id();
+	counterparty
+		.query_client_state(counterparty_height, client_id.clone())
+		.await
+		.map_err(|e| {
+			anyhow::anyhow!("Failed to query client {client_id} on {counterparty_name}: {e}")
+		})?
+		.client_state
+		.ok_or_else(|| {
+			anyhow::anyhow!(
+				"Client {client_id} of {name} not found on {counterparty_name}, check the configured client id"
+			)
+		})?;
+
+	if let Some(connection_id) = chain.connection_id() {
+		chain
+			.query_connection_end(height, connection_id.clone())
+			.await
+			.map_err(|e| {
+				anyhow::anyhow!("Failed to query connection {connection_id} on {name}: {e}")
+			})?
+			.connection
+			.ok_or_else(|| {
+				anyhow::anyhow!(
+					"Connection {connection_id} not found on {name}, check the configured connection id"
+				)
+			})?;
+	}
+	Ok(())
+}
+
+async fn client_state_and_age<C: IbcProvider + ?Sized>(
+	chain: &C,
+	at: Height,
+	now: Timestamp,
+	client_id: ClientId,
+) -> Result<(AnyClientState, Duration), C::Error> {
+	let client_state = chain
+		.query_client_state(at, client_id.clone())
+		.await?
+		.client_state
+		.ok_or_else(|| format!("Client state for {client_id} not found"))?;
+	let client_state = AnyClientState::try_from(client_state)
+		.map_err(|e| format!("Invalid client state for {client_id}: {e:?}"))?;
+	let consensus_state = chain
+		.query_client_consensus(at, client_id.clone(), client_state.latest_height())
+		.await?
+		.consensus_state
+		.ok_or_else(|| format!("Consensus state for {client_id} not found"))?;
+	let consensus_state = AnyConsensusState::try_from(consensus_state)
+		.map_err(|e| format!("Invalid consensus state for {client_id}: {e:?}"))?;
+	let elapsed = now.duration_since(&consensus_state.timestamp()).unwrap_or_default();
+	Ok((client_state, elapsed))
+}
+
 pub async fn query_maximum_height_for_timeout_proofs(
 	source: &impl Chain,
 	sink: &impl Chain,
@@ -889,6 +1418,7 @@ pub fn filter_events_by_ids(
 	client_ids: &[ClientId],
 	connection_ids: &[ConnectionId],
 	channel_and_port_ids: &HashSet<(ChannelId, PortId)>,
+	channel_filter: &ChannelFilter,
 ) -> bool {
 	use ibc::core::{
 		ics02_client::events::Attributes as ClientAttributes,
@@ -925,7 +1455,19 @@ pub fn filter_events_by_ids(
 				.counterparty_channel_id
 				.as_ref()
 				.map(|id| channel_ids.contains(&id))
-				.unwrap_or(false)
+				.unwrap_or(false) ||
+			// channels on the relayed connections matching the wildcards aren't whitelisted yet
+			connection_ids.contains(&packet.connection_id) &&
+				(packet
+					.channel_id
+					.as_ref()
+					.map(|id| channel_filter.matches(id, &packet.port_id))
+					.unwrap_or(false) ||
+					packet
+						.counterparty_channel_id
+						.as_ref()
+						.map(|id| channel_filter.matches(id, &packet.counterparty_port_id))
+						.unwrap_or(false))
 	};
 
 	let v = match ev {
@@ -966,3 +1508,40 @@ pub fn filter_events_by_ids(
 	}
 	v
 }
+
+#[cfg(test)]
+pub mod tests {
+	use crate::{mock::MockChain, Chain};
+	use ibc::core::ics24_host::identifier::{ClientId, ConnectionId};
+	use ibc_proto::{google::protobuf::Any, ibc::core::connection::v1::ConnectionEnd};
+
+	#[tokio::test]
+	async fn health_check_finds_the_client_on_the_counterparty() {
+		// the client of chain a lives on chain b and vice versa
+		let (client_a, client_b) =
+			(ClientId::new("07-tendermint", 0).unwrap(), ClientId::new("10-grandpa", 0).unwrap());
+		let mut chain_a = MockChain::new("a", client_a.clone());
+		let mut chain_b = MockChain::new("b", client_b.clone());
+		let (connection_a, connection_b) = (ConnectionId::new(0), ConnectionId::new(1));
+		chain_a.connection_id = Some(connection_a.clone());
+		chain_b.connection_id = Some(connection_b.clone());
+		chain_a.state().client_states.insert(client_b.clone(), Any::default());
+		chain_a.state().connections.insert(
+			connection_a,
+			ConnectionEnd { client_id: client_b.to_string(), ..Default::default() },
+		);
+		chain_b.state().client_states.insert(client_a.clone(), Any::default());
+		chain_b.state().connections.insert(
+			connection_b,
+			ConnectionEnd { client_id: client_a.to_string(), ..Default::default() },
+		);
+
+		chain_a.health_check(&chain_b).await.unwrap();
+		chain_b.health_check(&chain_a).await.unwrap();
+
+		// a missing client is reported for the chain it belongs to
+		chain_b.state().client_states.clear();
+		assert!(chain_a.health_check(&chain_b).await.is_err());
+		chain_b.health_check(&chain_a).await.unwrap();
+	}
+}
@@ -15,6 +15,11 @@
 use ibc::core::ics02_client::context::ClientTypes;
 use pallet_ibc::light_clients::{AnyClient, AnyClientMessage, AnyClientState, AnyConsensusState};
 
+#[cfg(any(feature = "testing", test))]
+mod chain;
+#[cfg(any(feature = "testing", test))]
+pub use chain::{MockChain, MockState};
+
 #[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
 pub struct LocalClientTypes;
 
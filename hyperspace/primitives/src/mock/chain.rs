@@ -0,0 +1,599 @@
+// Copyright 2022 ComposableFi
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+	error::Error, Chain, CommonClientState, FeeEstimate, IbcProvider, KeyProvider, LightClientSync,
+	MisbehaviourHandler, SubmissionErrorClass, UpdateType,
+};
+use futures::{stream, Stream, StreamExt};
+use ibc::{
+	applications::transfer::PrefixedCoin,
+	core::{
+		ics02_client::{client_state::ClientType, events::UpdateClient},
+		ics23_commitment::commitment::CommitmentPrefix,
+		ics24_host::identifier::{ChannelId, ClientId, ConnectionId, PortId},
+	},
+	events::IbcEvent,
+	signer::Signer,
+	timestamp::Timestamp,
+	Height,
+};
+use ibc_proto::{
+	google::protobuf::Any,
+	ibc::core::{
+		channel::v1::{
+			Channel, QueryChannelResponse, QueryChannelsResponse, QueryNextSequenceReceiveResponse,
+			QueryPacketAcknowledgementResponse, QueryPacketCommitmentResponse,
+			QueryPacketReceiptResponse,
+		},
+		client::v1::{QueryClientStateResponse, QueryConsensusStateResponse},
+		connection::v1::{ConnectionEnd, IdentifiedConnection, QueryConnectionResponse},
+	},
+};
+use ibc_rpc::PacketInfo;
+use pallet_ibc::light_clients::{AnyClientMessage, AnyClientState, AnyConsensusState};
+use std::{
+	collections::{BTreeMap, HashMap, HashSet, VecDeque},
+	pin::Pin,
+	sync::{Arc, Mutex, MutexGuard},
+	time::Duration,
+};
+
+/// Proof returned by all the queries of a [`MockChain`].
+const MOCK_PROOF: &[u8] = &[1];
+
+const SUBMISSION_ERROR_CLASSES: [SubmissionErrorClass; 6] = [
+	SubmissionErrorClass::AccountSequence,
+	SubmissionErrorClass::Mempool,
+	SubmissionErrorClass::Timeout,
+	SubmissionErrorClass::Rpc,
+	SubmissionErrorClass::OutOfGas,
+	SubmissionErrorClass::Other,
+];
+
+/// State of a [`MockChain`], shared between its clones, so that tests can set the chain up and
+/// inspect what the relayer did with it.
+#[derive(Default)]
+pub struct MockState {
+	/// Latest finalized height of the chain
+	pub latest_height: Height,
+	/// Timestamp of the latest finalized block
+	pub latest_timestamp: Timestamp,
+	/// Block hashes that differ from the default hash of their height, e.g. after a reorg
+	pub block_hashes: HashMap<Height, Vec<u8>>,
+	/// Client states on this chain
+	pub client_states: HashMap<ClientId, Any>,
+	/// Consensus states on this chain, by client id and consensus height
+	pub consensus_states: HashMap<(ClientId, Height), Any>,
+	/// Connection ends on this chain
+	pub connections: HashMap<ConnectionId, ConnectionEnd>,
+	/// Channel ends on this chain
+	pub channels: HashMap<(ChannelId, PortId), Channel>,
+	/// Finality events yielded by the next stream of finality notifications
+	pub finality_events: Vec<Height>,
+	/// Events yielded by the next stream of IBC events
+	pub ibc_events: Vec<IbcEvent>,
+	/// Client updates with the events of their heights, returned once a finality event reaches
+	/// them
+	pub updates: BTreeMap<Height, (Any, Vec<IbcEvent>, UpdateType)>,
+	/// Batches of messages submitted to the chain
+	pub submitted: Vec<Vec<Any>>,
+	/// Classes of the errors returned by the next submissions, the following submissions succeed
+	pub submit_errors: VecDeque<SubmissionErrorClass>,
+	/// Number of blocks between a block and its proof height, see [`Chain::get_proof_height`]
+	pub proof_height_offset: u64,
+	/// Returned by the misbehaviour check of every client message
+	pub misbehaviour: Option<Any>,
+	/// Returned as the client message of every client update
+	pub client_message: Option<AnyClientMessage>,
+	/// Returned as the estimated fee of every batch of messages
+	pub fee: u128,
+	/// Wasm codes uploaded to the chain, the code id of a code is its index
+	pub wasm_codes: Vec<Vec<u8>>,
+	/// Whether the uploaded wasm must be gzip compressed
+	pub requires_gzipped_wasm: bool,
+}
+
+/// A [`Chain`] kept in memory, for testing the relayer without running any nodes.
+#[derive(Clone)]
+pub struct MockChain {
+	pub name: String,
+	/// Id of this chain's client on the counterparty
+	pub client_id: ClientId,
+	pub connection_id: Option<ConnectionId>,
+	pub channel_whitelist: HashSet<(ChannelId, PortId)>,
+	pub state: Arc<Mutex<MockState>>,
+	pub common_state: CommonClientState,
+}
+
+impl MockChain {
+	/// Returns a chain at height 1 whose client on the counterparty has the given id.
+	pub fn new(name: &str, client_id: ClientId) -> Self {
+		let state = MockState {
+			latest_height: Height::new(0, 1),
+			latest_timestamp: Timestamp::now(),
+			..Default::default()
+		};
+		Self {
+			name: name.to_string(),
+			client_id,
+			connection_id: None,
+			channel_whitelist: Default::default(),
+			state: Arc::new(Mutex::new(state)),
+			common_state: Default::default(),
+		}
+	}
+
+	pub fn state(&self) -> MutexGuard<'_, MockState> {
+		self.state.lock().unwrap()
+	}
+
+	/// Returns the batches of messages submitted to the chain so far.
+	pub fn submitted(&self) -> Vec<Vec<Any>> {
+		self.state().submitted.clone()
+	}
+}
+
+#[async_trait::async_trait]
+impl IbcProvider for MockChain {
+	type FinalityEvent = Height;
+	type TransactionId = ();
+	type AssetId = ();
+	type Error = Error;
+
+	async fn query_latest_ibc_events<T>(
+		&mut self,
+		finality_event: Self::FinalityEvent,
+		_counterparty: &T,
+	) -> Result<Vec<(Any, Height, Vec<IbcEvent>, UpdateType)>, anyhow::Error>
+	where
+		T: Chain,
+	{
+		let mut state = self.state();
+		let pending = state.updates.split_off(&finality_event.increment());
+		let finalized = std::mem::replace(&mut state.updates, pending);
+		Ok(finalized
+			.into_iter()
+			.map(|(height, (msg, events, update_type))| (msg, height, events, update_type))
+			.collect())
+	}
+
+	async fn finality_event_height(&self, finality_event: &Self::FinalityEvent) -> Option<Height> {
+		Some(*finality_event)
+	}
+
+	async fn ibc_events(&self) -> Pin<Box<dyn Stream<Item = IbcEvent> + Send + 'static>> {
+		let events = std::mem::take(&mut self.state().ibc_events);
+		Box::pin(stream::iter(events).chain(stream::pending()))
+	}
+
+	async fn query_client_consensus(
+		&self,
+		at: Height,
+		client_id: ClientId,
+		consensus_height: Height,
+	) -> Result<QueryConsensusStateResponse, Self::Error> {
+		Ok(QueryConsensusStateResponse {
+			consensus_state: self
+				.state()
+				.consensus_states
+				.get(&(client_id, consensus_height))
+				.cloned(),
+			proof: MOCK_PROOF.to_vec(),
+			proof_height: Some(at.into()),
+		})
+	}
+
+	async fn query_client_state(
+		&self,
+		at: Height,
+		client_id: ClientId,
+	) -> Result<QueryClientStateResponse, Self::Error> {
+		Ok(QueryClientStateResponse {
+			client_state: self.state().client_states.get(&client_id).cloned(),
+			proof: MOCK_PROOF.to_vec(),
+			proof_height: Some(at.into()),
+		})
+	}
+
+	async fn query_connection_end(
+		&self,
+		at: Height,
+		connection_id: ConnectionId,
+	) -> Result<QueryConnectionResponse, Self::Error> {
+		Ok(QueryConnectionResponse {
+			connection: self.state().connections.get(&connection_id).cloned(),
+			proof: MOCK_PROOF.to_vec(),
+			proof_height: Some(at.into()),
+		})
+	}
+
+	async fn query_channel_end(
+		&self,
+		at: Height,
+		channel_id: ChannelId,
+		port_id: PortId,
+	) -> Result<QueryChannelResponse, Self::Error> {
+		Ok(QueryChannelResponse {
+			channel: self.state().channels.get(&(channel_id, port_id)).cloned(),
+			proof: MOCK_PROOF.to_vec(),
+			proof_height: Some(at.into()),
+		})
+	}
+
+	async fn query_proof(&self, _at: Height, _keys: Vec<Vec<u8>>) -> Result<Vec<u8>, Self::Error> {
+		Ok(MOCK_PROOF.to_vec())
+	}
+
+	async fn query_packet_commitment(
+		&self,
+		at: Height,
+		_port_id: &PortId,
+		_channel_id: &ChannelId,
+		_seq: u64,
+	) -> Result<QueryPacketCommitmentResponse, Self::Error> {
+		Ok(QueryPacketCommitmentResponse {
+			commitment: vec![],
+			proof: MOCK_PROOF.to_vec(),
+			proof_height: Some(at.into()),
+		})
+	}
+
+	async fn query_packet_acknowledgement(
+		&self,
+		at: Height,
+		_port_id: &PortId,
+		_channel_id: &ChannelId,
+		_seq: u64,
+	) -> Result<QueryPacketAcknowledgementResponse, Self::Error> {
+		Ok(QueryPacketAcknowledgementResponse {
+			acknowledgement: vec![],
+			proof: MOCK_PROOF.to_vec(),
+			proof_height: Some(at.into()),
+		})
+	}
+
+	async fn query_next_sequence_recv(
+		&self,
+		at: Height,
+		_port_id: &PortId,
+		_channel_id: &ChannelId,
+	) -> Result<QueryNextSequenceReceiveResponse, Self::Error> {
+		Ok(QueryNextSequenceReceiveResponse {
+			next_sequence_receive: 1,
+			proof: MOCK_PROOF.to_vec(),
+			proof_height: Some(at.into()),
+		})
+	}
+
+	async fn query_packet_receipt(
+		&self,
+		at: Height,
+		_port_id: &PortId,
+		_channel_id: &ChannelId,
+		_seq: u64,
+	) -> Result<QueryPacketReceiptResponse, Self::Error> {
+		Ok(QueryPacketReceiptResponse {
+			received: false,
+			proof: MOCK_PROOF.to_vec(),
+			proof_height: Some(at.into()),
+		})
+	}
+
+	async fn latest_height_and_timestamp(&self) -> Result<(Height, Timestamp), Self::Error> {
+		let state = self.state();
+		Ok((state.latest_height, state.latest_timestamp))
+	}
+
+	async fn query_packet_commitments(
+		&self,
+		_at: Height,
+		_channel_id: ChannelId,
+		_port_id: PortId,
+	) -> Result<Vec<u64>, Self::Error> {
+		Ok(vec![])
+	}
+
+	async fn query_packet_acknowledgements(
+		&self,
+		_at: Height,
+		_channel_id: ChannelId,
+		_port_id: PortId,
+	) -> Result<Vec<u64>, Self::Error> {
+		Ok(vec![])
+	}
+
+	async fn query_unreceived_packets(
+		&self,
+		_at: Height,
+		_channel_id: ChannelId,
+		_port_id: PortId,
+		_seqs: Vec<u64>,
+	) -> Result<Vec<u64>, Self::Error> {
+		Ok(vec![])
+	}
+
+	async fn query_unreceived_acknowledgements(
+		&self,
+		_at: Height,
+		_channel_id: ChannelId,
+		_port_id: PortId,
+		_seqs: Vec<u64>,
+	) -> Result<Vec<u64>, Self::Error> {
+		Ok(vec![])
+	}
+
+	fn channel_whitelist(&self) -> HashSet<(ChannelId, PortId)> {
+		self.channel_whitelist.clone()
+	}
+
+	async fn query_connection_channels(
+		&self,
+		_at: Height,
+		_connection_id: &ConnectionId,
+	) -> Result<QueryChannelsResponse, Self::Error> {
+		Ok(Default::default())
+	}
+
+	async fn query_send_packets(
+		&self,
+		_channel_id: ChannelId,
+		_port_id: PortId,
+		_seqs: Vec<u64>,
+	) -> Result<Vec<PacketInfo>, Self::Error> {
+		Ok(vec![])
+	}
+
+	async fn query_received_packets(
+		&self,
+		_channel_id: ChannelId,
+		_port_id: PortId,
+		_seqs: Vec<u64>,
+	) -> Result<Vec<PacketInfo>, Self::Error> {
+		Ok(vec![])
+	}
+
+	fn expected_block_time(&self) -> Duration {
+		Duration::from_millis(10)
+	}
+
+	async fn query_client_update_time_and_height(
+		&self,
+		_client_id: ClientId,
+		_client_height: Height,
+	) -> Result<(Height, Timestamp), Self::Error> {
+		let state = self.state();
+		Ok((state.latest_height, state.latest_timestamp))
+	}
+
+	async fn query_host_consensus_state_proof(
+		&self,
+		_client_state: &AnyClientState,
+	) -> Result<Option<Vec<u8>>, Self::Error> {
+		Ok(None)
+	}
+
+	async fn query_ibc_balance(
+		&self,
+		_asset_id: Self::AssetId,
+	) -> Result<Vec<PrefixedCoin>, Self::Error> {
+		Ok(vec![])
+	}
+
+	fn connection_prefix(&self) -> CommitmentPrefix {
+		CommitmentPrefix::try_from(b"ibc".to_vec()).expect("Prefix is not empty")
+	}
+
+	fn client_id(&self) -> ClientId {
+		self.client_id.clone()
+	}
+
+	fn set_client_id(&mut self, client_id: ClientId) {
+		self.client_id = client_id;
+	}
+
+	fn connection_id(&self) -> Option<ConnectionId> {
+		self.connection_id.clone()
+	}
+
+	fn set_channel_whitelist(&mut self, channel_whitelist: HashSet<(ChannelId, PortId)>) {
+		self.channel_whitelist = channel_whitelist;
+	}
+
+	fn add_channel_to_whitelist(&mut self, channel: (ChannelId, PortId)) {
+		self.channel_whitelist.insert(channel);
+	}
+
+	fn set_connection_id(&mut self, connection_id: ConnectionId) {
+		self.connection_id = Some(connection_id);
+	}
+
+	fn client_type(&self) -> ClientType {
+		"mock".to_string()
+	}
+
+	async fn query_timestamp_at(&self, _block_number: u64) -> Result<u64, Self::Error> {
+		Ok(self.state().latest_timestamp.nanoseconds())
+	}
+
+	async fn get_block(&self, height: Height) -> Result<Option<Vec<u8>>, Self::Error> {
+		let block_hash = self.state().block_hashes.get(&height).cloned();
+		Ok(Some(block_hash.unwrap_or_else(|| height.revision_height.to_be_bytes().to_vec())))
+	}
+
+	async fn query_clients(&self) -> Result<Vec<ClientId>, Self::Error> {
+		Ok(self.state().client_states.keys().cloned().collect())
+	}
+
+	async fn query_channels(&self) -> Result<Vec<(ChannelId, PortId)>, Self::Error> {
+		Ok(self.state().channels.keys().cloned().collect())
+	}
+
+	async fn query_connection_using_client(
+		&self,
+		_height: u32,
+		_client_id: String,
+	) -> Result<Vec<IdentifiedConnection>, Self::Error> {
+		Ok(vec![])
+	}
+
+	async fn is_update_required(
+		&self,
+		_latest_height: u64,
+		_latest_client_height_on_counterparty: u64,
+	) -> Result<bool, Self::Error> {
+		Ok(false)
+	}
+
+	async fn initialize_client_state(
+		&self,
+	) -> Result<(AnyClientState, AnyConsensusState), Self::Error> {
+		unimplemented!("mock chains don't create clients")
+	}
+
+	async fn query_client_id_from_tx_hash(
+		&self,
+		_tx_id: Self::TransactionId,
+	) -> Result<ClientId, Self::Error> {
+		unimplemented!("mock chains don't create clients")
+	}
+
+	async fn query_connection_id_from_tx_hash(
+		&self,
+		_tx_id: Self::TransactionId,
+	) -> Result<ConnectionId, Self::Error> {
+		unimplemented!("mock chains don't create connections")
+	}
+
+	async fn query_channel_id_from_tx_hash(
+		&self,
+		_tx_id: Self::TransactionId,
+	) -> Result<(ChannelId, PortId), Self::Error> {
+		unimplemented!("mock chains don't create channels")
+	}
+
+	async fn upload_wasm(&self, wasm: Vec<u8>) -> Result<Vec<u8>, Self::Error> {
+		let mut state = self.state();
+		state.wasm_codes.push(wasm);
+		Ok(vec![state.wasm_codes.len() as u8 - 1])
+	}
+
+	fn requires_gzipped_wasm(&self) -> bool {
+		self.state().requires_gzipped_wasm
+	}
+}
+
+impl KeyProvider for MockChain {
+	fn account_id(&self) -> Signer {
+		"relayer".parse().expect("Signer is not empty")
+	}
+}
+
+#[async_trait::async_trait]
+impl MisbehaviourHandler for MockChain {
+	async fn check_for_misbehaviour<C: Chain>(
+		&self,
+		_counterparty: &C,
+		_client_message: AnyClientMessage,
+	) -> Result<Option<Any>, anyhow::Error> {
+		Ok(self.state().misbehaviour.clone())
+	}
+}
+
+#[async_trait::async_trait]
+impl LightClientSync for MockChain {
+	async fn is_synced<C: Chain>(&self, _counterparty: &C) -> Result<bool, anyhow::Error> {
+		Ok(true)
+	}
+
+	async fn fetch_mandatory_updates<C: Chain>(
+		&self,
+		_counterparty: &C,
+	) -> Result<(Vec<Any>, Vec<IbcEvent>), anyhow::Error> {
+		Ok((vec![], vec![]))
+	}
+}
+
+#[async_trait::async_trait]
+impl Chain for MockChain {
+	fn name(&self) -> &str {
+		&self.name
+	}
+
+	fn block_max_weight(&self) -> u64 {
+		u64::MAX
+	}
+
+	async fn estimate_weight(&self, _msg: Vec<Any>) -> Result<u64, Self::Error> {
+		Ok(0)
+	}
+
+	async fn estimate_fee(&self, _msgs: &[Any]) -> Result<FeeEstimate, Self::Error> {
+		Ok(FeeEstimate { amount: self.state().fee, denom: None })
+	}
+
+	async fn finality_notifications(
+		&self,
+	) -> Result<Pin<Box<dyn Stream<Item = Self::FinalityEvent> + Send + Sync>>, Self::Error> {
+		let events = std::mem::take(&mut self.state().finality_events);
+		Ok(Box::pin(stream::iter(events).chain(stream::pending())))
+	}
+
+	async fn submit(&self, messages: Vec<Any>) -> Result<Self::TransactionId, Self::Error> {
+		let mut state = self.state();
+		if let Some(class) = state.submit_errors.pop_front() {
+			return Err(Error::Custom(class.as_str().to_string()))
+		}
+		state.submitted.push(messages);
+		Ok(())
+	}
+
+	async fn query_client_message(
+		&self,
+		_update: UpdateClient,
+	) -> Result<AnyClientMessage, Self::Error> {
+		self.state()
+			.client_message
+			.clone()
+			.ok_or_else(|| Error::Custom("Mock chain has no client message".to_string()))
+	}
+
+	async fn get_proof_height(&self, block_height: Height) -> Height {
+		block_height.add(self.state().proof_height_offset)
+	}
+
+	async fn handle_error(&mut self, _error: &anyhow::Error) -> Result<(), anyhow::Error> {
+		Ok(())
+	}
+
+	fn submission_error_class(&self, error: &Self::Error) -> SubmissionErrorClass {
+		// the submission errors of mock chains are the labels of their classes
+		SUBMISSION_ERROR_CLASSES
+			.into_iter()
+			.find(|class| error.to_string() == class.as_str())
+			.unwrap_or(SubmissionErrorClass::Other)
+	}
+
+	fn common_state(&self) -> &CommonClientState {
+		&self.common_state
+	}
+
+	fn common_state_mut(&mut self) -> &mut CommonClientState {
+		&mut self.common_state
+	}
+
+	async fn reconnect(&mut self) -> anyhow::Result<()> {
+		Ok(())
+	}
+}
@@ -0,0 +1,315 @@
+// Copyright 2022 ComposableFi
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ibc::{
+	applications::transfer::{packet::PacketData, Amount},
+	core::{
+		ics04_channel::packet::{Packet, Sequence},
+		ics24_host::identifier::{ChannelId, PortId},
+	},
+};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::{HashSet, VecDeque},
+	fmt::Debug,
+	sync::Arc,
+};
+
+/// Restricts which packets get relayed. An empty filter relays every packet.
+///
+/// All the conditions only apply to ICS-20 transfer packets. If any condition is set, packets
+/// with data that can't be decoded as [`PacketData`] are not relayed.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct PacketFilter {
+	/// Only relay transfers of tokens with these base denoms
+	#[serde(default)]
+	pub denoms: Vec<String>,
+	/// Only relay transfers of at least this amount
+	#[serde(default)]
+	pub min_amount: Option<u128>,
+	/// Only relay transfers sent by these addresses
+	#[serde(default)]
+	pub senders: Vec<String>,
+}
+
+impl PacketFilter {
+	/// Returns `true` if the filter doesn't restrict any packets.
+	pub fn is_empty(&self) -> bool {
+		self.denoms.is_empty() && self.min_amount.is_none() && self.senders.is_empty()
+	}
+
+	/// Returns `true` if the packet should be relayed.
+	pub fn matches(&self, packet: &Packet) -> bool {
+		if self.is_empty() {
+			return true
+		}
+		let data = match decode_transfer_packet_data(packet) {
+			Some(data) => data,
+			None => return false,
+		};
+		if !self.denoms.is_empty() &&
+			!self.denoms.iter().any(|denom| data.token.denom.base_denom.as_str() == denom)
+		{
+			return false
+		}
+		if let Some(min_amount) = self.min_amount {
+			if data.token.amount < Amount::from(min_amount) {
+				return false
+			}
+		}
+		if !self.senders.is_empty() &&
+			!self.senders.iter().any(|sender| data.sender.as_ref() == sender)
+		{
+			return false
+		}
+		true
+	}
+}
+
+/// A [`PacketFilter`] that only applies to ICS-20 transfer packets sent from the given channel.
+/// Packets that aren't transfers are always relayed.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ChannelPacketFilter {
+	pub channel_id: ChannelId,
+	pub port_id: PortId,
+	#[serde(flatten)]
+	pub filter: PacketFilter,
+}
+
+impl ChannelPacketFilter {
+	/// Returns `true` if the packet should be relayed.
+	pub fn matches(&self, packet: &Packet) -> bool {
+		if packet.source_channel != self.channel_id || packet.source_port != self.port_id {
+			return true
+		}
+		decode_transfer_packet_data(packet).is_none() || self.filter.matches(packet)
+	}
+}
+
+/// Whether a [`PacketFilterHook`] lets a packet be relayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterDecision {
+	Allow,
+	/// The reason is used as a metrics label, so it should come from a small set of values
+	Deny(&'static str),
+}
+
+/// A pluggable check of the packets before they're relayed to the counterparty. It only applies
+/// to the packets being received, timeouts are always relayed so that the packets get refunded.
+pub trait PacketFilterHook: Debug + Send + Sync {
+	fn allow(&self, packet: &Packet) -> FilterDecision;
+}
+
+/// Denies ICS-20 transfers to the given receivers, or with a memo matching one of the patterns.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct TransferDenylistConfig {
+	/// Don't relay transfers to these addresses
+	#[serde(default)]
+	pub receivers: Vec<String>,
+	/// Don't relay transfers with a memo matching any of these regular expressions
+	#[serde(default)]
+	pub memo_patterns: Vec<String>,
+	/// Don't relay the packets that can't be decoded as ICS-20 transfers either
+	#[serde(default)]
+	pub fail_closed: bool,
+}
+
+impl TransferDenylistConfig {
+	/// Returns the hooks the denylist is enforced by, none if it doesn't deny anything.
+	pub fn hooks(&self) -> Result<Vec<Arc<dyn PacketFilterHook>>, regex::Error> {
+		if self.receivers.is_empty() && self.memo_patterns.is_empty() && !self.fail_closed {
+			return Ok(vec![])
+		}
+		Ok(vec![Arc::new(TransferDenylist::new(self)?)])
+	}
+}
+
+/// The [`PacketFilterHook`] built from a [`TransferDenylistConfig`].
+#[derive(Debug, Clone)]
+pub struct TransferDenylist {
+	receivers: HashSet<String>,
+	memo_patterns: Vec<Regex>,
+	fail_closed: bool,
+}
+
+impl TransferDenylist {
+	pub fn new(config: &TransferDenylistConfig) -> Result<Self, regex::Error> {
+		Ok(Self {
+			receivers: config.receivers.iter().cloned().collect(),
+			memo_patterns: config
+				.memo_patterns
+				.iter()
+				.map(|pattern| Regex::new(pattern))
+				.collect::<Result<_, _>>()?,
+			fail_closed: config.fail_closed,
+		})
+	}
+}
+
+impl PacketFilterHook for TransferDenylist {
+	fn allow(&self, packet: &Packet) -> FilterDecision {
+		let data = match decode_transfer_packet_data(packet) {
+			Some(data) => data,
+			None if self.fail_closed => return FilterDecision::Deny("undecodable"),
+			None => return FilterDecision::Allow,
+		};
+		if self.receivers.contains(data.receiver.as_ref()) {
+			return FilterDecision::Deny("receiver")
+		}
+		if self.memo_patterns.iter().any(|pattern| pattern.is_match(&data.memo)) {
+			return FilterDecision::Deny("memo")
+		}
+		FilterDecision::Allow
+	}
+}
+
+/// Maximum number of packets remembered by [`SkippedPackets`].
+pub const MAX_SKIPPED_PACKETS: usize = 10_000;
+
+/// Packets that were skipped because of the filters, so that each one is only reported once. At
+/// most [`MAX_SKIPPED_PACKETS`] are remembered, the oldest ones are forgotten first and reported
+/// again if they're skipped later.
+#[derive(Debug, Default)]
+pub struct SkippedPackets {
+	order: VecDeque<(ChannelId, PortId, Sequence)>,
+	packets: HashSet<(ChannelId, PortId, Sequence)>,
+}
+
+impl SkippedPackets {
+	/// Remembers that the packet was skipped. Returns `false` if it was already skipped before.
+	pub fn insert(&mut self, packet: &Packet) -> bool {
+		let key = (packet.source_channel, packet.source_port.clone(), packet.sequence);
+		if !self.packets.insert(key.clone()) {
+			return false
+		}
+		self.order.push_back(key);
+		while self.order.len() > MAX_SKIPPED_PACKETS {
+			if let Some(oldest) = self.order.pop_front() {
+				self.packets.remove(&oldest);
+			}
+		}
+		true
+	}
+
+	pub fn len(&self) -> usize {
+		self.packets.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.packets.is_empty()
+	}
+}
+
+/// Decodes the data of an ICS-20 transfer packet, returns `None` if the packet is not a transfer.
+pub fn decode_transfer_packet_data(packet: &Packet) -> Option<PacketData> {
+	serde_json::from_slice(packet.data.as_ref()).ok()
+}
+
+#[cfg(test)]
+pub mod tests {
+	use super::{
+		ChannelPacketFilter, FilterDecision, PacketFilter, PacketFilterHook, SkippedPackets,
+		TransferDenylistConfig, MAX_SKIPPED_PACKETS,
+	};
+	use ibc::core::{
+		ics04_channel::packet::Packet,
+		ics24_host::identifier::{ChannelId, PortId},
+	};
+
+	fn transfer_packet(channel: u64, denom: &str, amount: u64) -> Packet {
+		let data = format!(
+			r#"{{"denom":"{denom}","amount":"{amount}","sender":"alice","receiver":"bob"}}"#
+		);
+		Packet {
+			source_channel: ChannelId::new(channel),
+			source_port: PortId::transfer(),
+			data: data.into_bytes(),
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn channel_filter_only_applies_to_its_channel_transfers() {
+		let filter = ChannelPacketFilter {
+			channel_id: ChannelId::new(0),
+			port_id: PortId::transfer(),
+			filter: PacketFilter {
+				denoms: vec!["uatom".to_string()],
+				min_amount: Some(100),
+				..Default::default()
+			},
+		};
+
+		assert!(filter.matches(&transfer_packet(0, "uatom", 100)));
+		assert!(!filter.matches(&transfer_packet(0, "uatom", 99)));
+		assert!(!filter.matches(&transfer_packet(0, "uosmo", 100)));
+		// other channels aren't filtered
+		assert!(filter.matches(&transfer_packet(1, "uosmo", 1)));
+		// packets that aren't transfers are always relayed
+		let mut packet = transfer_packet(0, "uatom", 1);
+		packet.data = b"not a transfer".to_vec();
+		assert!(filter.matches(&packet));
+	}
+
+	#[test]
+	fn denylist_rejects_receivers_and_memos() {
+		let config = TransferDenylistConfig {
+			receivers: vec!["mallory".to_string()],
+			memo_patterns: vec!["^swap:.*mallory".to_string()],
+			fail_closed: false,
+		};
+		let hooks = config.hooks().unwrap();
+		let decide = |receiver: &str, memo: &str| {
+			let mut packet = transfer_packet(0, "uatom", 1);
+			packet.data = format!(
+				r#"{{"denom":"uatom","amount":"1","sender":"alice","receiver":"{receiver}","memo":"{memo}"}}"#
+			)
+			.into_bytes();
+			hooks[0].allow(&packet)
+		};
+		assert_eq!(decide("bob", ""), FilterDecision::Allow);
+		assert_eq!(decide("mallory", ""), FilterDecision::Deny("receiver"));
+		assert_eq!(decide("bob", "swap:to-mallory"), FilterDecision::Deny("memo"));
+		assert_eq!(decide("bob", "forward:to-mallory"), FilterDecision::Allow);
+
+		// packets that aren't transfers pass, unless the denylist fails closed
+		let mut packet = transfer_packet(0, "uatom", 1);
+		packet.data = b"not a transfer".to_vec();
+		assert_eq!(hooks[0].allow(&packet), FilterDecision::Allow);
+		let config = TransferDenylistConfig { fail_closed: true, ..Default::default() };
+		assert_eq!(config.hooks().unwrap()[0].allow(&packet), FilterDecision::Deny("undecodable"));
+
+		assert!(TransferDenylistConfig::default().hooks().unwrap().is_empty());
+		let invalid = TransferDenylistConfig { memo_patterns: vec!["(".to_string()], ..config };
+		assert!(invalid.hooks().is_err());
+	}
+
+	#[test]
+	fn skipped_packets_are_bounded() {
+		let mut skipped = SkippedPackets::default();
+		let packet =
+			|sequence: u64| Packet { sequence: sequence.into(), ..transfer_packet(0, "uatom", 1) };
+		assert!(skipped.insert(&packet(0)));
+		assert!(!skipped.insert(&packet(0)));
+		for sequence in 1..=MAX_SKIPPED_PACKETS as u64 {
+			assert!(skipped.insert(&packet(sequence)));
+		}
+		assert_eq!(skipped.len(), MAX_SKIPPED_PACKETS);
+		// the oldest packet was forgotten, the recent ones are still remembered
+		assert!(!skipped.insert(&packet(MAX_SKIPPED_PACKETS as u64)));
+		assert!(skipped.insert(&packet(0)));
+		assert_eq!(skipped.len(), MAX_SKIPPED_PACKETS);
+	}
+}
@@ -0,0 +1,81 @@
+// Copyright 2022 ComposableFi
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+	path::PathBuf,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc,
+	},
+};
+
+/// Pauses packet relaying, e.g. after a misbehaviour of the counterparty was detected.
+///
+/// If backed by a file, the flag is set while the file exists, so the operator clears it by
+/// removing the file and it survives restarts. Otherwise it's kept in memory and only cleared by
+/// a restart.
+#[derive(Debug, Clone, Default)]
+pub struct PauseFlag {
+	path: Option<PathBuf>,
+	set: Arc<AtomicBool>,
+}
+
+impl PauseFlag {
+	pub fn with_file(path: impl Into<PathBuf>) -> Self {
+		Self { path: Some(path.into()), set: Default::default() }
+	}
+
+	pub fn is_set(&self) -> bool {
+		match &self.path {
+			Some(path) => path.exists(),
+			None => self.set.load(Ordering::SeqCst),
+		}
+	}
+
+	/// Sets the flag, the reason is written to the file.
+	pub fn set(&self, reason: &str) -> Result<(), anyhow::Error> {
+		self.set.store(true, Ordering::SeqCst);
+		if let Some(path) = &self.path {
+			std::fs::write(path, reason)?;
+		}
+		Ok(())
+	}
+
+	/// Returns the file to remove to clear the flag, if any.
+	pub fn path(&self) -> Option<&PathBuf> {
+		self.path.as_ref()
+	}
+}
+
+#[cfg(test)]
+pub mod tests {
+	use super::PauseFlag;
+
+	#[test]
+	fn file_flag_is_cleared_by_removing_the_file() {
+		let path = std::env::temp_dir().join(format!("hyperspace-paused-{}", std::process::id()));
+		let _ = std::fs::remove_file(&path);
+		let flag = PauseFlag::with_file(&path);
+		assert!(!flag.is_set());
+		flag.clone().set("misbehaviour").unwrap();
+		assert!(flag.is_set());
+		assert_eq!(std::fs::read_to_string(&path).unwrap(), "misbehaviour");
+		std::fs::remove_file(&path).unwrap();
+		assert!(!flag.is_set());
+
+		let flag = PauseFlag::default();
+		flag.clone().set("misbehaviour").unwrap();
+		assert!(flag.is_set());
+	}
+}
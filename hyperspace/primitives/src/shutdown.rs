@@ -0,0 +1,67 @@
+// Copyright 2022 ComposableFi
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// Asks the relaying loops to stop. Once it's requested, no new relaying rounds are started, the
+/// ones in progress are finished, so that their submissions complete and their processed heights
+/// are persisted.
+#[derive(Debug, Clone)]
+pub struct Shutdown {
+	requested: Arc<watch::Sender<bool>>,
+}
+
+impl Default for Shutdown {
+	fn default() -> Self {
+		Self { requested: Arc::new(watch::channel(false).0) }
+	}
+}
+
+impl Shutdown {
+	pub fn request(&self) {
+		self.requested.send_replace(true);
+	}
+
+	pub fn is_requested(&self) -> bool {
+		*self.requested.borrow()
+	}
+
+	/// Resolves once the shutdown is requested.
+	pub async fn requested(&self) {
+		let mut receiver = self.requested.subscribe();
+		// the sender is owned by `self`, so the channel can't be closed while waiting
+		let _ = receiver.wait_for(|requested| *requested).await;
+	}
+}
+
+#[cfg(test)]
+pub mod tests {
+	use super::Shutdown;
+	use futures::{executor::block_on, FutureExt};
+
+	#[test]
+	fn shutdown_is_shared_by_the_clones() {
+		let shutdown = Shutdown::default();
+		let chain_shutdown = shutdown.clone();
+		assert!(!chain_shutdown.is_requested());
+		assert!(chain_shutdown.requested().now_or_never().is_none());
+
+		shutdown.request();
+		assert!(chain_shutdown.is_requested());
+		block_on(chain_shutdown.requested());
+		// the loops started after the request stop right away
+		assert!(Shutdown::clone(&shutdown).requested().now_or_never().is_some());
+	}
+}
@@ -27,7 +27,10 @@ async fn main() -> Result<()> {
 		Subcommand::Relay(cmd) => cmd.run().await,
 		Subcommand::UploadWasm(cmd) => {
 			let new_config = cmd.run().await?;
-			cmd.save_config(&new_config).await
+			if cmd.save {
+				cmd.save_config(&new_config).await?;
+			}
+			Ok(())
 		},
 		Subcommand::CreateClients(cmd) => {
 			let new_config = cmd.create_clients().await?;
@@ -42,5 +45,6 @@ async fn main() -> Result<()> {
 			cmd.save_config(&new_config).await
 		},
 		Subcommand::Fish(cmd) => cmd.fish().await,
+		Subcommand::ClearPackets(cmd) => cmd.run().await,
 	}
 }
@@ -16,7 +16,7 @@
 
 use crate::utils::assert_timeout_packet;
 use futures::{future, StreamExt};
-use hyperspace_core::send_packet_relay::set_relay_status;
+use hyperspace_core::{send_packet_relay::set_relay_status, RelayOptions};
 use hyperspace_primitives::{
 	utils::{create_channel, create_connection, timeout_after, timeout_future},
 	TestProvider,
@@ -35,7 +35,10 @@ use ibc::{
 };
 use ibc_proto::google::protobuf::Any;
 use pallet_ibc::Timeout;
-use std::{str::FromStr, time::Duration};
+use std::{
+	str::FromStr,
+	time::{Duration, Instant},
+};
 use tendermint_proto::Protobuf;
 use tokio::task::JoinHandle;
 
@@ -62,7 +65,7 @@ where
 	let client_b_clone = chain_b.clone();
 	// Start relayer loop
 	let handle = tokio::task::spawn(async move {
-		hyperspace_core::relay(client_a_clone, client_b_clone, None, None, None)
+		hyperspace_core::relay(client_a_clone, client_b_clone, RelayOptions::default())
 			.await
 			.unwrap()
 	});
@@ -213,6 +216,7 @@ async fn assert_send_transfer<A>(
 	A::FinalityEvent: Send + Sync,
 {
 	// wait for the acknowledgment
+	let started_at = Instant::now();
 	let future = chain
 		.ibc_events()
 		.await
@@ -226,6 +230,13 @@ async fn assert_send_transfer<A>(
 		format!("Didn't see AcknowledgePacket on {}", chain.name()),
 	)
 	.await;
+	log::info!(
+		target: "hyperspace",
+		"Transfer from {} was acknowledged in {:?}, eager flush: {}",
+		chain.name(),
+		started_at.elapsed(),
+		chain.common_state().eager_flush
+	);
 
 	let balance = chain
 		.query_ibc_balance(asset_id)
@@ -499,7 +510,7 @@ pub async fn ibc_messaging_packet_height_timeout_with_connection_delay<A, B>(
 	let client_a_clone = chain_a.clone();
 	let client_b_clone = chain_b.clone();
 	let handle = tokio::task::spawn(async move {
-		hyperspace_core::relay(client_a_clone, client_b_clone, None, None, None)
+		hyperspace_core::relay(client_a_clone, client_b_clone, RelayOptions::default())
 			.await
 			.unwrap()
 	});
@@ -525,7 +536,7 @@ pub async fn ibc_messaging_packet_timestamp_timeout_with_connection_delay<A, B>(
 	let client_a_clone = chain_a.clone();
 	let client_b_clone = chain_b.clone();
 	let handle = tokio::task::spawn(async move {
-		hyperspace_core::relay(client_a_clone, client_b_clone, None, None, None)
+		hyperspace_core::relay(client_a_clone, client_b_clone, RelayOptions::default())
 			.await
 			.unwrap()
 	});
@@ -553,7 +564,7 @@ pub async fn ibc_messaging_with_connection_delay<A, B>(
 	let client_a_clone = chain_a.clone();
 	let client_b_clone = chain_b.clone();
 	let handle = tokio::task::spawn(async move {
-		hyperspace_core::relay(client_a_clone, client_b_clone, None, None, None)
+		hyperspace_core::relay(client_a_clone, client_b_clone, RelayOptions::default())
 			.await
 			.unwrap()
 	});
@@ -562,6 +573,39 @@ pub async fn ibc_messaging_with_connection_delay<A, B>(
 	handle.abort()
 }
 
+/// Send packets over a connection without a connection delay with eager flushing enabled on both
+/// chains and assert they're acknowledged.
+pub async fn ibc_messaging_with_eager_flush<A, B>(
+	chain_a: &mut A,
+	chain_b: &mut B,
+	asset_a: A::AssetId,
+	asset_b: B::AssetId,
+	channel_a: ChannelId,
+	channel_b: ChannelId,
+) where
+	A: TestProvider,
+	A::FinalityEvent: Send + Sync,
+	A::Error: From<B::Error>,
+	B: TestProvider,
+	B::FinalityEvent: Send + Sync,
+	B::Error: From<A::Error>,
+{
+	chain_a.common_state_mut().eager_flush = true;
+	chain_b.common_state_mut().eager_flush = true;
+	let client_a_clone = chain_a.clone();
+	let client_b_clone = chain_b.clone();
+	let handle = tokio::task::spawn(async move {
+		hyperspace_core::relay(client_a_clone, client_b_clone, RelayOptions::default())
+			.await
+			.unwrap()
+	});
+	send_packet_with_connection_delay(chain_a, chain_b, channel_a, channel_b, asset_a, asset_b)
+		.await;
+	handle.abort();
+	chain_a.common_state_mut().eager_flush = false;
+	chain_b.common_state_mut().eager_flush = false;
+}
+
 ///
 pub async fn ibc_channel_close<A, B>(chain_a: &mut A, chain_b: &mut B)
 where
@@ -585,7 +629,7 @@ where
 	let client_a_clone = chain_a.clone();
 	let client_b_clone = chain_b.clone();
 	let handle = tokio::task::spawn(async move {
-		hyperspace_core::relay(client_a_clone, client_b_clone, None, None, None)
+		hyperspace_core::relay(client_a_clone, client_b_clone, RelayOptions::default())
 			.await
 			.unwrap()
 	});
@@ -610,7 +654,7 @@ pub async fn ibc_messaging_packet_timeout_on_channel_close<A, B>(
 	let client_a_clone = chain_a.clone();
 	let client_b_clone = chain_b.clone();
 	let handle = tokio::task::spawn(async move {
-		hyperspace_core::relay(client_a_clone, client_b_clone, None, None, None)
+		hyperspace_core::relay(client_a_clone, client_b_clone, RelayOptions::default())
 			.await
 			.unwrap()
 	});
@@ -633,7 +677,7 @@ where
 	let client_a_clone = chain_a.clone();
 	let client_b_clone = chain_b.clone();
 	let handle = tokio::task::spawn(async move {
-		hyperspace_core::relay(client_a_clone, client_b_clone, None, None, None)
+		hyperspace_core::relay(client_a_clone, client_b_clone, RelayOptions::default())
 			.await
 			.unwrap()
 	});
@@ -14,7 +14,7 @@
 
 use crate::{assert_timeout_packet, timeout_future, StreamExt};
 use futures::future;
-use hyperspace_core::send_packet_relay::set_relay_status;
+use hyperspace_core::{send_packet_relay::set_relay_status, RelayOptions};
 use hyperspace_primitives::{
 	utils::{create_channel, create_connection},
 	TestProvider,
@@ -51,7 +51,7 @@ where
 	let client_b_clone = chain_b.clone();
 	// Start relayer loop
 	let handle = tokio::task::spawn(async move {
-		hyperspace_core::relay(client_a_clone, client_b_clone, None, None, None)
+		hyperspace_core::relay(client_a_clone, client_b_clone, RelayOptions::default())
 			.await
 			.unwrap()
 	});
@@ -250,7 +250,7 @@ pub async fn ibc_messaging_ordered_packet_with_connection_delay<A, B>(
 	let client_a_clone = chain_a.clone();
 	let client_b_clone = chain_b.clone();
 	let handle = tokio::task::spawn(async move {
-		hyperspace_core::relay(client_a_clone, client_b_clone, None, None, None)
+		hyperspace_core::relay(client_a_clone, client_b_clone, RelayOptions::default())
 			.await
 			.unwrap()
 	});
@@ -287,7 +287,7 @@ pub async fn ibc_messaging_ordered_packet_timeout<A, B>(
 	let client_a_clone = chain_a.clone();
 	let client_b_clone = chain_b.clone();
 	let handle = tokio::task::spawn(async move {
-		hyperspace_core::relay(client_a_clone, client_b_clone, None, None, None)
+		hyperspace_core::relay(client_a_clone, client_b_clone, RelayOptions::default())
 			.await
 			.unwrap()
 	});
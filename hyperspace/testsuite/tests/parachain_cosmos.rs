@@ -26,8 +26,8 @@ use hyperspace_testsuite::{
 	ibc_channel_close, ibc_messaging_packet_height_timeout_with_connection_delay,
 	ibc_messaging_packet_timeout_on_channel_close,
 	ibc_messaging_packet_timestamp_timeout_with_connection_delay,
-	ibc_messaging_with_connection_delay, misbehaviour::ibc_messaging_submit_misbehaviour,
-	setup_connection_and_channel,
+	ibc_messaging_with_connection_delay, ibc_messaging_with_eager_flush,
+	misbehaviour::ibc_messaging_submit_misbehaviour, setup_connection_and_channel,
 };
 use ibc::core::ics24_host::identifier::PortId;
 use sp_core::hashing::sha2_256;
@@ -111,6 +111,21 @@ async fn setup_clients() -> (AnyChain, AnyChain) {
 		common: CommonClientConfig {
 			skip_optional_client_updates: true,
 			max_packets_to_process: 200,
+			packet_filter: Default::default(),
+			channel_packet_filters: vec![],
+			transfer_denylist: Default::default(),
+			max_messages_per_batch: None,
+			max_txs_per_block: None,
+			max_batch_bytes: None,
+			max_concurrent_packet_proofs: 100,
+			channel_delay_overrides: vec![],
+			in_flight_packets_expiry: 60,
+			max_submission_retries: 3,
+			eager_flush: false,
+			max_client_staleness: None,
+			poll_interval: None,
+			finality_regression_threshold: 10,
+			max_fee_per_batch: None,
 		},
 		skip_tokens_list: None,
 	};
@@ -235,6 +250,38 @@ async fn parachain_to_cosmos_ibc_messaging_full_integration_test() {
 	// ibc_messaging_submit_misbehaviour(&mut chain_a, &mut chain_b).await;
 }
 
+#[tokio::test]
+#[ignore]
+async fn parachain_to_cosmos_ibc_messaging_with_eager_flush() {
+	logging::setup_logging();
+
+	let asset_id_a = AnyAssetId::Parachain(1);
+	let asset_id_b = AnyAssetId::Cosmos(
+		"ibc/47B97D8FF01DA03FCB2F4B1FFEC931645F254E21EF465FA95CBA6888CB964DC4".to_string(),
+	);
+	let (mut chain_a, mut chain_b) = setup_clients().await;
+	// packets of channels with a connection delay are not flushed eagerly
+	let (handle, channel_a, channel_b, connection_id_a, connection_id_b) =
+		setup_connection_and_channel(&mut chain_a, &mut chain_b, Duration::ZERO).await;
+	handle.abort();
+
+	chain_a.set_connection_id(connection_id_a);
+	chain_b.set_connection_id(connection_id_b);
+
+	chain_a.set_channel_whitelist(vec![(channel_a, PortId::transfer())].into_iter().collect());
+	chain_b.set_channel_whitelist(vec![(channel_b, PortId::transfer())].into_iter().collect());
+
+	ibc_messaging_with_eager_flush(
+		&mut chain_a,
+		&mut chain_b,
+		asset_id_a,
+		asset_id_b,
+		channel_a,
+		channel_b,
+	)
+	.await;
+}
+
 #[tokio::test]
 #[ignore]
 async fn cosmos_to_parachain_ibc_messaging_full_integration_test() {
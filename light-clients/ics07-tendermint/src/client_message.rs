@@ -14,7 +14,7 @@
 
 #![allow(deprecated)]
 
-use crate::error::Error;
+use crate::{consensus_state::ConsensusState, error::Error, HostFunctionsProvider, ProdVerifier};
 use alloc::{string::ToString, vec::Vec};
 use bytes::Buf;
 use core::cmp::Ordering;
@@ -34,6 +34,11 @@ use ibc_proto::{
 use prost::Message;
 use serde::{Deserialize, Serialize};
 use tendermint::{block::signed_header::SignedHeader, validator::Set as ValidatorSet};
+use tendermint_light_client_verifier::{
+	options::Options,
+	types::{TrustedBlockState, UntrustedBlockState},
+	Verdict, Verifier,
+};
 use tendermint_proto::Protobuf;
 
 pub const TENDERMINT_HEADER_TYPE_URL: &str = "/ibc.lightclients.tendermint.v1.Header";
@@ -128,6 +133,32 @@ impl From<Misbehaviour> for RawMisbehaviour {
 	}
 }
 
+impl Misbehaviour {
+	/// Checks that the headers constitute a valid misbehaviour of the chain `chain_id`: they must
+	/// conflict with each other (fork or BFT time violation), and each of them must be verifiable
+	/// from the consensus state at its trusted height (`trusted_consensus_state1` and
+	/// `trusted_consensus_state2` respectively) according to the trust threshold from `options`.
+	pub fn verify<H: HostFunctionsProvider>(
+		&self,
+		chain_id: &tendermint::chain::Id,
+		trusted_consensus_state1: &ConsensusState,
+		trusted_consensus_state2: &ConsensusState,
+		options: &Options,
+	) -> Result<(), Error> {
+		if self.header1.compatible_with(&self.header2) {
+			return Err(Error::misbehaviour_headers_compatible(
+				self.header1.height(),
+				self.header2.height(),
+			))
+		}
+		self.header1
+			.verify_against_trusted::<H>(chain_id, trusted_consensus_state1, options)?;
+		self.header2
+			.verify_against_trusted::<H>(chain_id, trusted_consensus_state2, options)?;
+		Ok(())
+	}
+}
+
 impl core::fmt::Display for Misbehaviour {
 	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> Result<(), core::fmt::Error> {
 		write!(
@@ -174,6 +205,70 @@ impl Header {
 	pub fn compatible_with(&self, other_header: &Header) -> bool {
 		headers_compatible(&self.signed_header, &other_header.signed_header)
 	}
+
+	/// Verifies the header of the chain `chain_id` from the consensus state at its trusted height:
+	/// the trusted validator set must be the next validator set of the consensus state, the header
+	/// must be correctly signed by its validator set, and enough of the trusted validators must
+	/// have signed it according to the trust threshold from `options`.
+	fn verify_against_trusted<H: HostFunctionsProvider>(
+		&self,
+		chain_id: &tendermint::chain::Id,
+		trusted_consensus_state: &ConsensusState,
+		options: &Options,
+	) -> Result<(), Error> {
+		if self.signed_header.header.chain_id != *chain_id {
+			return Err(Error::validation(format!(
+				"header chain id {} doesn't match the client chain id {chain_id}",
+				self.signed_header.header.chain_id
+			)))
+		}
+		if self.height() <= self.trusted_height {
+			return Err(Error::invalid_trusted_header_height(self.trusted_height, self.height()))
+		}
+		let trusted_validators_hash = self.trusted_validator_set.hash_with::<H>();
+		if trusted_validators_hash != trusted_consensus_state.next_validators_hash {
+			return Err(Error::invalid_validator_set(
+				trusted_validators_hash,
+				trusted_consensus_state.next_validators_hash,
+			))
+		}
+
+		let untrusted_state = UntrustedBlockState {
+			signed_header: &self.signed_header,
+			validators: &self.validator_set,
+			next_validators: None,
+		};
+		let trusted_state = TrustedBlockState {
+			chain_id,
+			header_time: trusted_consensus_state.timestamp,
+			height: self
+				.trusted_height
+				.revision_height
+				.try_into()
+				.map_err(|_| Error::invalid_header_height(self.trusted_height))?,
+			next_validators: &self.trusted_validator_set,
+			next_validators_hash: trusted_consensus_state.next_validators_hash,
+		};
+
+		let verifier = ProdVerifier::<H>::default();
+		let verdicts = [
+			verifier.verify_validator_sets(&untrusted_state),
+			verifier.verify_commit(&untrusted_state),
+			verifier.verify_commit_against_trusted(&untrusted_state, &trusted_state, options),
+		];
+		for verdict in verdicts {
+			match verdict {
+				Verdict::Success => {},
+				Verdict::NotEnoughTrust(voting_power_tally) =>
+					return Err(Error::not_enough_trusted_vals_signed(format!(
+						"voting power tally: {}",
+						voting_power_tally
+					))),
+				Verdict::Invalid(detail) => return Err(Error::verification_error(detail)),
+			}
+		}
+		Ok(())
+	}
 }
 
 pub fn headers_compatible(header: &SignedHeader, other: &SignedHeader) -> bool {
@@ -304,3 +399,80 @@ pub mod test_util {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::{Header, Misbehaviour};
+	use crate::{
+		consensus_state::ConsensusState,
+		error::ErrorDetail,
+		mock::{host::MockHostBlock, Crypto},
+	};
+	use core::time::Duration;
+	use ibc::{core::ics24_host::identifier::ChainId, timestamp::Timestamp, Height};
+	use tendermint_light_client_verifier::{options::Options, types::TrustThreshold};
+	use tendermint_testgen::light_block::TmLightBlock;
+
+	fn light_block(height: u64, seconds: u64) -> TmLightBlock {
+		let timestamp = Timestamp::from_nanoseconds(seconds * 1_000_000_000).unwrap();
+		MockHostBlock::generate_tm_block(ChainId::new("mockgaia".to_string(), 1), height, timestamp)
+	}
+
+	/// Header of the block, trusting the validators of the `trusted` block.
+	fn header(block: TmLightBlock, trusted: &TmLightBlock) -> Header {
+		Header {
+			signed_header: block.signed_header,
+			validator_set: block.validators,
+			trusted_height: Height::new(1, trusted.signed_header.header.height.value()),
+			trusted_validator_set: trusted.next_validators.clone(),
+		}
+	}
+
+	#[test]
+	fn conflicting_headers_are_verified_from_the_trusted_consensus_state() {
+		let trusted = light_block(1, 1);
+		let trusted_consensus_state = ConsensusState::from(trusted.signed_header.header.clone());
+		let chain_id = trusted.signed_header.header.chain_id.clone();
+		let options = Options {
+			trust_threshold: TrustThreshold::TWO_THIRDS,
+			trusting_period: Duration::from_secs(64000),
+			clock_drift: Duration::from_secs(3),
+		};
+		// two blocks signed at the same height with different times, i.e. a fork
+		let misbehaviour = Misbehaviour {
+			client_id: Default::default(),
+			header1: header(light_block(5, 10), &trusted),
+			header2: header(light_block(5, 20), &trusted),
+		};
+		misbehaviour
+			.verify::<Crypto>(
+				&chain_id,
+				&trusted_consensus_state,
+				&trusted_consensus_state,
+				&options,
+			)
+			.unwrap();
+
+		let compatible =
+			Misbehaviour { header2: misbehaviour.header1.clone(), ..misbehaviour.clone() };
+		let err = compatible
+			.verify::<Crypto>(
+				&chain_id,
+				&trusted_consensus_state,
+				&trusted_consensus_state,
+				&options,
+			)
+			.unwrap_err();
+		assert!(matches!(err.detail(), ErrorDetail::MisbehaviourHeadersCompatible(_)));
+
+		// the trusted validators of the headers must be the ones of the trusted consensus state
+		let other_consensus_state = ConsensusState {
+			next_validators_hash: tendermint::Hash::None,
+			..trusted_consensus_state.clone()
+		};
+		let err = misbehaviour
+			.verify::<Crypto>(&chain_id, &trusted_consensus_state, &other_consensus_state, &options)
+			.unwrap_err();
+		assert!(matches!(err.detail(), ErrorDetail::InvalidValidatorSet(_)));
+	}
+}
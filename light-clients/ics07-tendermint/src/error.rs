@@ -284,6 +284,15 @@ define_error! {
 			| e | {
 				format_args!("the client is frozen: frozen_height={0} target_height={1}", e.frozen_height, e.target_height)
 			},
+
+		MisbehaviourHeadersCompatible
+			{
+				height1: Height,
+				height2: Height,
+			}
+			| e | {
+				format_args!("headers at heights {0} and {1} are compatible and don't constitute misbehaviour", e.height1, e.height2)
+			},
 	}
 }
 